@@ -0,0 +1,173 @@
+use proc_macro::{Delimiter, TokenStream, TokenTree};
+
+// 为 Rust 函数生成实现 Callable 的原生函数结构体，只能在 lox-rs 内部使用
+//
+// #[lox_native]
+// #[arity(2)]
+// fn my_fn(args: &[Option<LoxType>]) -> Option<LoxType> { ... }
+//
+// 会生成 MyFnNativeFunction，调用时把参数依次包装为 Some，返回 None 时得到 nil，
+// MyFnNativeFunction::register(&mut interpreter) 以函数名 my_fn 注册为全局变量
+#[proc_macro_attribute]
+pub fn lox_native(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return compile_error("#[lox_native] takes no arguments, use #[arity(N)] instead.");
+    }
+    match expand(item) {
+        Ok(output) => output,
+        Err(message) => compile_error(&message),
+    }
+}
+
+fn expand(item: TokenStream) -> Result<TokenStream, String> {
+    let tokens = item.into_iter().collect::<Vec<_>>();
+    let mut function = Vec::with_capacity(tokens.len());
+    let mut arity = None;
+    let mut index = 0;
+    // #[arity(N)] 只供本宏读取，输出时从函数的属性中去掉
+    while let (Some(TokenTree::Punct(pound)), Some(TokenTree::Group(group))) =
+        (tokens.get(index), tokens.get(index + 1))
+    {
+        if pound.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        match parse_arity(group.stream())? {
+            Some(value) => arity = Some(value),
+            None => function.extend_from_slice(&tokens[index..index + 2]),
+        }
+        index += 2;
+    }
+    let arity = arity.ok_or("#[lox_native] requires an #[arity(N)] attribute.")?;
+
+    let rest = &tokens[index..];
+    let fn_index = rest
+        .iter()
+        .position(|token| matches!(token, TokenTree::Ident(ident) if ident.to_string() == "fn"))
+        .ok_or("#[lox_native] can only be applied to a function.")?;
+    let Some(TokenTree::Ident(name)) = rest.get(fn_index + 1) else {
+        return Err(String::from("Expect function name after 'fn'."));
+    };
+    let name = name.to_string();
+    // 生成的结构体与 register 沿用函数的可见性，例如 pub 或 pub(crate)
+    let visibility = match rest.first() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => match rest.get(1) {
+            Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                format!("pub{}", group)
+            }
+            _ => String::from("pub"),
+        },
+        _ => String::new(),
+    };
+    function.extend_from_slice(rest);
+
+    let generated = generate(&name, &visibility, arity)
+        .parse::<TokenStream>()
+        .map_err(|error| error.to_string())?;
+    let mut output = function.into_iter().collect::<TokenStream>();
+    output.extend(generated);
+    Ok(output)
+}
+
+// 属性不是 arity 时返回 None
+fn parse_arity(attribute: TokenStream) -> Result<Option<usize>, String> {
+    let tokens = attribute.into_iter().collect::<Vec<_>>();
+    let [TokenTree::Ident(ident), TokenTree::Group(group)] = tokens.as_slice() else {
+        return Ok(None);
+    };
+    if ident.to_string() != "arity" || group.delimiter() != Delimiter::Parenthesis {
+        return Ok(None);
+    }
+    let value = group.stream().to_string();
+    value
+        .trim()
+        .parse::<usize>()
+        .map(Some)
+        .map_err(|_| format!("Invalid arity '{}', expect a non-negative integer.", value))
+}
+
+fn generate(name: &str, visibility: &str, arity: usize) -> String {
+    let struct_name = format!("{}NativeFunction", to_camel_case(name));
+    format!(
+        "#[derive(Debug, Clone)]
+        {visibility} struct {struct_name};
+
+        impl {struct_name} {{
+            {visibility} fn register(interpreter: &mut crate::ast::interpreter::Interpreter) {{
+                interpreter.define_global(\"{name}\", ::std::boxed::Box::new({struct_name}));
+            }}
+        }}
+
+        impl crate::scanner::token::Callable for {struct_name} {{
+            fn call(
+                &mut self,
+                _interpreter: &mut crate::ast::interpreter::Interpreter,
+                _paren: &crate::scanner::Token,
+                arguments: &[crate::scanner::LoxType],
+            ) -> ::std::result::Result<crate::scanner::LoxType, crate::ast::error::LoxRuntimeError> {{
+                let arguments = arguments
+                    .iter()
+                    .cloned()
+                    .map(::std::option::Option::Some)
+                    .collect::<::std::vec::Vec<_>>();
+                ::std::result::Result::Ok(
+                    {name}(&arguments).unwrap_or(crate::scanner::LoxType::Nil),
+                )
+            }}
+
+            fn arity(&self) -> usize {{
+                {arity}
+            }}
+
+            fn clone_box(&self) -> ::std::boxed::Box<dyn crate::scanner::token::Callable> {{
+                ::std::boxed::Box::new(self.clone())
+            }}
+
+            fn eq_callable(&self, other: &dyn crate::scanner::token::Callable) -> bool {{
+                other.as_any().is::<{struct_name}>()
+            }}
+
+            fn as_any(&self) -> &dyn ::std::any::Any {{
+                self
+            }}
+        }}"
+    )
+}
+
+// my_fn -> MyFn
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().map(|c| c.to_ascii_uppercase());
+            first.into_iter().chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?});", message)
+        .parse()
+        .expect("compile_error! invocation should be valid tokens")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_camel_case() {
+        assert_eq!(to_camel_case("my_fn"), "MyFn");
+        assert_eq!(to_camel_case("type_of"), "TypeOf");
+        assert_eq!(to_camel_case("_len"), "Len");
+        assert_eq!(to_camel_case("abs"), "Abs");
+    }
+
+    #[test]
+    fn test_generate() {
+        let code = generate("type_of", "pub(crate)", 1);
+        assert!(code.contains("pub(crate) struct TypeOfNativeFunction;"));
+        assert!(code.contains("define_global(\"type_of\""));
+        assert!(code.contains("type_of(&arguments)"));
+    }
+}
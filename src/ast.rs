@@ -1,15 +1,21 @@
+pub mod error;
 pub mod interpreter;
 mod macros;
+pub mod printer;
 
 use paste::paste;
 use std::fmt::Debug;
+use std::rc::Rc;
 
 use crate::generate_ast;
 use crate::scanner::LoxType;
-use crate::scanner::token::{LoxReturn, Token};
+use crate::scanner::token::{LoxInterrupt, Token};
+
+// switch 语句中的一个分支：待比较的值与分支内的语句
+pub type SwitchCase = (Box<dyn Expr>, Vec<Box<dyn Stmt>>);
 
 generate_ast! {
-    Expr {
+    Expr -> LoxType {
         Assign(assign_visit) {
             name: Token,
             value: Box<dyn Expr>,
@@ -23,7 +29,7 @@ generate_ast! {
             expression: Box<dyn Expr>,
         },
         Literal(literal_visit) {
-            value: Option<LoxType>,
+            value: LoxType,
         },
         Logical(logical_visit) {
             left: Box<dyn Expr>,
@@ -41,11 +47,61 @@ generate_ast! {
             callee: Box<dyn Expr>,
             paren: Token,
             arguments: Vec<Box<dyn Expr>>,
+        },
+        Get(get_visit) {
+            object: Box<dyn Expr>,
+            name: Token,
+        },
+        // operator 为复合赋值（例如 +=）对应的二元运算符，普通赋值时为 None
+        Set(set_visit) {
+            object: Box<dyn Expr>,
+            name: Token,
+            operator: Option<Token>,
+            value: Box<dyn Expr>,
+        },
+        Super(super_visit) {
+            keyword: Token,
+            method: Token,
+        },
+        This(this_visit) {
+            keyword: Token,
+        },
+        Ternary(ternary_visit) {
+            condition: Box<dyn Expr>,
+            then_branch: Box<dyn Expr>,
+            else_branch: Box<dyn Expr>,
+        },
+        Lambda(lambda_visit) {
+            function: Function,
+        },
+        ListLiteral(list_literal_visit) {
+            bracket: Token,
+            elements: Vec<Box<dyn Expr>>,
+        },
+        Subscript(subscript_visit) {
+            object: Box<dyn Expr>,
+            bracket: Token,
+            index: Box<dyn Expr>,
+        },
+        SubscriptAssign(subscript_assign_visit) {
+            object: Box<dyn Expr>,
+            bracket: Token,
+            index: Box<dyn Expr>,
+            operator: Option<Token>,
+            value: Box<dyn Expr>,
+        },
+        MapLiteral(map_literal_visit) {
+            brace: Token,
+            keys: Vec<String>,
+            values: Vec<Box<dyn Expr>>,
         }
     },
-    Stmt {
+    Stmt -> Option<LoxType> {
         Print(print_visit) {
+            keyword: Token,
             expression: Box<dyn Expr>,
+            // print 输出后换行，write 不换行
+            newline: bool,
         },
         If(if_visit) {
             condition: Box<dyn Expr>,
@@ -62,77 +118,83 @@ generate_ast! {
             name: Token,
             initializer: Box<dyn Expr>
         },
+        // var [a, b, ...rest] = list; 按位置绑定列表元素，rest 收集剩余的元素
+        VarList(var_list_visit) {
+            bracket: Token,
+            names: Vec<Token>,
+            rest: Option<Token>,
+            initializer: Box<dyn Expr>,
+        },
+        Const(const_visit) {
+            name: Token,
+            initializer: Box<dyn Expr>,
+        },
         While(while_visit) {
             condition: Box<dyn Expr>,
-            body: Box<dyn Stmt>
+            body: Box<dyn Stmt>,
+            increment: Option<Box<dyn Expr>>,
         },
         Function(function_visit) {
             name: Token,
             params: Vec<Token>,
-            body: Vec<Box<dyn Stmt>>,
+            // 函数体在 LoxFunction 之间共享，保证 Resolver 记录的节点地址始终有效
+            body: Rc<Vec<Box<dyn Stmt>>>,
         },
         Return(return_visit) {
             keyword: Token,
             value: Option<Box<dyn Expr>>,
+        },
+        Break(break_visit) {
+            keyword: Token,
+        },
+        Continue(continue_visit) {
+            keyword: Token,
+        },
+        ForIn(for_in_visit) {
+            name: Token,
+            iterable: Box<dyn Expr>,
+            body: Box<dyn Stmt>,
+        },
+        // 依次比较各个 case 的值，只执行第一个相等的分支，不会贯穿到下一个分支
+        Switch(switch_visit) {
+            keyword: Token,
+            scrutinee: Box<dyn Expr>,
+            cases: Vec<SwitchCase>,
+            default_case: Option<Vec<Box<dyn Stmt>>>,
+        },
+        Try(try_visit) {
+            keyword: Token,
+            body: Vec<Box<dyn Stmt>>,
+            name: Token,
+            handler: Vec<Box<dyn Stmt>>,
+        },
+        Throw(throw_visit) {
+            keyword: Token,
+            value: Box<dyn Expr>,
+        },
+        // path 相对于 import 语句所在文件的目录，只能出现在顶层
+        Import(import_visit) {
+            keyword: Token,
+            path: String,
+        },
+        Class(class_visit) {
+            name: Token,
+            superclass: Option<Box<dyn Expr>>,
+            methods: Vec<Function>,
         }
     },
 }
 
-pub struct PrintExprVisitor;
-
-impl ExprVisitor for PrintExprVisitor {
-    fn assign_visit(&mut self, expr: &Assign) -> Result<Option<LoxType>, LoxReturn> {
-        todo!()
-    }
-
-    fn binary_visit(&mut self, expr: &Binary) -> Result<Option<LoxType>, LoxReturn> {
-        print!("([binary] ");
-        expr.left.accept(self);
-        print!(" {} ", expr.operator.lexeme);
-        expr.right.accept(self);
-        print!(")");
-        Ok(None)
-    }
-
-    fn grouping_visit(&mut self, expr: &Grouping) -> Result<Option<LoxType>, LoxReturn> {
-        print!("([group] ");
-        expr.expression.accept(self);
-        print!(")");
-        Ok(None)
-    }
-
-    fn literal_visit(&mut self, expr: &Literal) -> Result<Option<LoxType>, LoxReturn> {
-        Ok(None)
-    }
-
-    fn logical_visit(&mut self, expr: &Logical) -> Result<Option<LoxType>, LoxReturn> {
-        todo!()
-    }
-
-    fn unary_visit(&mut self, expr: &Unary) -> Result<Option<LoxType>, LoxReturn> {
-        print!("([unary] {} ", expr.operator.lexeme);
-        expr.right.accept(self);
-        print!(")");
-        Ok(None)
-    }
-
-    fn variable_visit(&mut self, expr: &Variable) -> Result<Option<LoxType>, LoxReturn> {
-        todo!()
-    }
-
-    fn call_visit(&mut self, expr: &Call) -> Result<Option<LoxType>, LoxReturn> {
-        todo!()
-    }
-}
-
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::ast::printer::AstPrinter;
+
     #[test]
     fn test_expr() {
-        let left = Box::new(Literal::new(Some(LoxType::new_str("1"))));
-        let right = Box::new(Literal::new(Some(LoxType::new_str("2"))));
+        let left = Box::new(Literal::new(LoxType::new_str("1")));
+        let right = Box::new(Literal::new(LoxType::new_str("2")));
         let operator = Token::new(
             crate::scanner::token::TokenType::Plus,
             "+".to_string(),
@@ -140,21 +202,114 @@ mod tests {
             2,
             2,
             None,
+            None,
         );
         let binary_expr = Binary::new(left, operator, right);
         println!("{:?}", binary_expr);
 
-        let mut printer = PrintExprVisitor;
-        binary_expr.accept(&mut printer);
-        println!();
+        assert_eq!(AstPrinter::print_expr(&binary_expr), "(+ \"1\" \"2\")");
 
         assert_eq!(
             format!("{:?}", binary_expr.left),
-            "Literal { value: Some(Str(\"1\")) }"
+            "Literal { value: Str(\"1\") }"
         );
         assert_eq!(
             format!("{:?}", binary_expr.right),
-            "Literal { value: Some(Str(\"2\")) }"
+            "Literal { value: Str(\"2\") }"
+        );
+    }
+
+    // 不含任何状态的访问者，只取出字面量的值
+    struct LiteralVisitor;
+
+    impl ExprVisitor for LiteralVisitor {
+        fn assign_visit(&mut self, _expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn binary_visit(&mut self, _expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+            expr.expression.accept(self)
+        }
+
+        fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+            Ok(expr.value.clone())
+        }
+
+        fn logical_visit(&mut self, _expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn unary_visit(&mut self, _expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn variable_visit(&mut self, _expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn call_visit(&mut self, _expr: &Call) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn get_visit(&mut self, _expr: &Get) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn set_visit(&mut self, _expr: &Set) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn super_visit(&mut self, _expr: &Super) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn this_visit(&mut self, _expr: &This) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn ternary_visit(&mut self, _expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn lambda_visit(&mut self, _expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn list_literal_visit(&mut self, _expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn subscript_visit(&mut self, _expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn subscript_assign_visit(
+            &mut self,
+            _expr: &SubscriptAssign,
+        ) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+
+        fn map_literal_visit(&mut self, _expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_zero_state_visitor() {
+        let literal = Box::new(Literal::new(LoxType::new_num(1.0)));
+        let grouping = Grouping::new(Box::new(Grouping::new(literal)));
+        let mut visitor = LiteralVisitor;
+        assert_eq!(
+            grouping.accept(&mut visitor).unwrap(),
+            LoxType::new_num(1.0)
         );
+        // 访问者通过 &mut dyn 传入，嵌套节点递归时复用同一个可变引用
+        let visitor: &mut dyn ExprVisitor = &mut visitor;
+        assert_eq!(grouping.accept(visitor).unwrap(), LoxType::new_num(1.0));
     }
 }
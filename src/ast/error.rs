@@ -0,0 +1,40 @@
+use crate::scanner::{LoxType, Token};
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug)]
+pub struct LoxRuntimeError {
+    // 装箱以减小错误类型的体积，它会出现在解释器几乎所有函数的返回值中
+    pub token: Box<Token>,
+    pub message: String,
+    // 出错时的调用栈，由外向内排列，在顶层代码中出错时为空
+    pub call_stack: Vec<String>,
+    // 由 throw 语句抛出的值，解释器自身产生的错误没有这个值
+    pub value: Option<LoxType>,
+}
+
+impl LoxRuntimeError {
+    pub fn new(token: Token, message: &str) -> Self {
+        LoxRuntimeError {
+            token: Box::new(token),
+            message: message.to_string(),
+            call_stack: Vec::new(),
+            value: None,
+        }
+    }
+
+    pub fn thrown(token: Token, value: LoxType) -> Self {
+        let mut error = LoxRuntimeError::new(token, &value.to_string());
+        error.value = Some(value);
+        error
+    }
+}
+
+impl fmt::Display for LoxRuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for LoxRuntimeError {}
@@ -1,223 +1,630 @@
-use crate::ast::{Assign, Block, Call, Function, If, Logical, Return, Var, Variable, While};
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::{
+    Assign, Block, Break, Call, Class, Const, Continue, ForIn, Function, Get, If, Import, Lambda,
+    ListLiteral, Logical, MapLiteral, Return, Set, Subscript, SubscriptAssign, Super, Switch,
+    Ternary, This, Throw, Try, Var, VarList, Variable, While,
+};
+use crate::class::LoxClass;
 use crate::environment::Environment;
 use crate::function::LoxFunction;
-use crate::function::native::ClockNativeFunction;
-use crate::scanner::token::LoxReturn;
+use crate::function::native;
+use crate::function::native::random::{RandomNativeFunction, RandomRangeNativeFunction};
+use crate::function::native::{
+    AssertNativeFunction, BoolNativeFunction, ExitNativeFunction, InputNativeFunction,
+    LenNativeFunction, ListGetNativeFunction, MapDeleteNativeFunction, MapGetNativeFunction,
+    MapHasNativeFunction, MapSetNativeFunction, NativeFn, NumNativeFunction, PopNativeFunction,
+    PrintlnNativeFunction, PushNativeFunction, StrNativeFunction, TypeOfNativeFunction,
+};
+use crate::optimizer::Optimizer;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::error::TypeError;
+use crate::scanner::token::{
+    Callable, LoxBreak, LoxContinue, LoxInterrupt, LoxReturn, LoxTailCall,
+};
 use crate::{
     ast::{
         Binary, Expr, ExprVisitor, Expression, Grouping, Literal, Print, Stmt, StmtVisitor, Unary,
     },
     log_info,
-    scanner::{LoxType, Token, TokenType},
+    scanner::{LoxType, Scanner, Token, TokenType},
 };
-use maplit::hashmap;
 use std::cell::RefCell;
-use std::mem;
+use std::collections::HashMap;
+use std::fmt::Formatter;
+use std::io::{self, BufRead, Write};
+use std::ops::Rem;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use unescape::unescape;
+use std::{fmt, mem};
+
+// 同一个文件的不同写法（例如 ./a.lox 与 a.lox）视为同一个路径，文件不存在时保持原样
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+// 只取数据指针作为键，避免同一节点因 vtable 地址不同而无法匹配
+fn expr_key(expr: &dyn Expr) -> *const () {
+    expr as *const dyn Expr as *const ()
+}
 
-#[derive(Debug)]
 pub struct Interpreter {
     pub globals: Rc<RefCell<Environment>>,
     pub environment: Rc<RefCell<Environment>>,
+    // Resolver 计算出的局部变量作用域深度，以表达式节点地址作为键
+    locals: HashMap<*const (), usize>,
+    // print 语句的输出目标
+    output: Rc<RefCell<dyn Write>>,
+    // input 函数读取输入的来源
+    input: Rc<RefCell<dyn BufRead>>,
+    // 当前正在执行的 Lox 函数名，由外向内排列，由 CallFrameGuard 负责压入与弹出
+    call_stack: Rc<RefCell<Vec<String>>>,
+    max_call_depth: usize,
+    // 每条语句执行前调用，供调试器或性能分析工具观察执行过程
+    step_callback: Option<Box<StepCallback>>,
+    // 正在执行的被导入文件，用于发现循环导入
+    importing: Vec<PathBuf>,
+}
+
+// 单步回调，参数为即将执行的语句
+type StepCallback = dyn FnMut(&dyn Stmt);
+
+// 默认的最大调用深度，超过后报告运行时错误而不是让 Rust 栈溢出
+const DEFAULT_MAX_CALL_DEPTH: usize = 256;
+
+// 顶层代码在调用栈中的名称
+const SCRIPT_FRAME: &str = "<script>";
+
+// 进入函数调用时压入调用帧，离开作用域时自动弹出
+pub struct CallFrameGuard {
+    call_stack: Rc<RefCell<Vec<String>>>,
+}
+
+impl CallFrameGuard {
+    // 尾调用复用当前调用帧，只替换其中的函数名
+    pub fn rename(&self, name: String) {
+        if let Some(frame) = self.call_stack.borrow_mut().last_mut() {
+            *frame = name;
+        }
+    }
+}
+
+impl Drop for CallFrameGuard {
+    fn drop(&mut self) {
+        self.call_stack.borrow_mut().pop();
+    }
+}
+
+// 输出目标没有实现 Debug，输出时跳过
+impl fmt::Debug for Interpreter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interpreter")
+            .field("globals", &self.globals)
+            .field("environment", &self.environment)
+            .field("locals", &self.locals)
+            .field("call_stack", &self.call_stack.borrow())
+            .field("max_call_depth", &self.max_call_depth)
+            .field("step_callback", &self.step_callback.is_some())
+            .finish_non_exhaustive()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let globals = Rc::new(RefCell::new(Environment::new_with_values(hashmap! {
-            "clock".to_string() => Some(LoxType::Function(Box::new(
-                ClockNativeFunction::new()
-            ))),
-        })));
-        Interpreter {
+        Interpreter::new_with_io(
+            Rc::new(RefCell::new(io::stdout())),
+            Rc::new(RefCell::new(io::BufReader::new(io::stdin()))),
+        )
+    }
+
+    pub fn new_with_io(output: Rc<RefCell<dyn Write>>, input: Rc<RefCell<dyn BufRead>>) -> Self {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+        let mut interpreter = Interpreter {
             globals: Rc::clone(&globals),
-            environment: Rc::new(RefCell::new(Environment::new())),
+            environment: globals,
+            locals: HashMap::new(),
+            output,
+            input,
+            call_stack: Rc::new(RefCell::new(Vec::new())),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            step_callback: None,
+            importing: Vec::new(),
+        };
+        interpreter.define_natives();
+        interpreter
+    }
+
+    // 丢弃所有全局变量与解析结果，回到刚创建时只有原生函数的状态
+    // 适用于同一个解释器先后运行互不相关的脚本，交互模式下不应调用
+    pub fn reset(&mut self) {
+        self.globals = Rc::new(RefCell::new(Environment::new()));
+        self.environment = Rc::clone(&self.globals);
+        self.locals.clear();
+        self.define_natives();
+    }
+
+    fn define_natives(&mut self) {
+        self.define_native("clock", 0, native::clock);
+        self.define_global("len", Box::new(LenNativeFunction));
+        self.define_global("str", Box::new(StrNativeFunction));
+        self.define_global("num", Box::new(NumNativeFunction));
+        self.define_global("bool", Box::new(BoolNativeFunction));
+        self.define_global("type_of", Box::new(TypeOfNativeFunction));
+        self.define_global("push", Box::new(PushNativeFunction));
+        self.define_global("pop", Box::new(PopNativeFunction));
+        self.define_global("list_get", Box::new(ListGetNativeFunction));
+        self.define_global("map_get", Box::new(MapGetNativeFunction));
+        self.define_global("map_set", Box::new(MapSetNativeFunction));
+        self.define_global("map_has", Box::new(MapHasNativeFunction));
+        self.define_global("map_delete", Box::new(MapDeleteNativeFunction));
+        self.define_global("input", Box::new(InputNativeFunction));
+        self.define_global("exit", Box::new(ExitNativeFunction));
+        self.define_global("assert", Box::new(AssertNativeFunction));
+        self.define_global("println", Box::new(PrintlnNativeFunction));
+        self.define_global("random", Box::new(RandomNativeFunction));
+        self.define_global("random_range", Box::new(RandomRangeNativeFunction));
+        for function in native::math::functions() {
+            self.define_global(function.name(), Box::new(function));
+        }
+        for function in native::string_fns::functions() {
+            self.define_global(function.name(), Box::new(function));
+        }
+    }
+
+    // 供 #[lox_native] 生成的 register 使用
+    pub(crate) fn define_global(&mut self, name: &str, function: Box<dyn Callable>) {
+        self.globals
+            .borrow_mut()
+            .define(name.to_string(), LoxType::new_function(function));
+    }
+
+    // 输出提示后读取一行输入，去掉行尾的换行符，输入结束时返回空字符串
+    pub fn read_line(&mut self, prompt: &str) -> io::Result<String> {
+        {
+            let mut output = self.output.borrow_mut();
+            write!(output, "{}", prompt)?;
+            output.flush()?;
+        }
+        let mut line = String::new();
+        self.input.borrow_mut().read_line(&mut line)?;
+        let len = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(len);
+        Ok(line)
+    }
+
+    // 向 print 语句使用的输出目标写入一个值并换行
+    pub fn write_line(&mut self, value: &LoxType) -> io::Result<()> {
+        writeln!(self.output.borrow_mut(), "{}", value)
+    }
+
+    pub fn flush_output(&mut self) -> io::Result<()> {
+        self.output.borrow_mut().flush()
+    }
+
+    // 只在全局环境中查找，不会看到局部作用域中的同名变量
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        self.globals.borrow().get_at(0, name).ok()
+    }
+
+    // 在全局环境中定义变量，已存在时直接替换
+    pub fn set_global(&mut self, name: &str, value: LoxType) {
+        self.globals.borrow_mut().define(name.to_string(), value);
+    }
+
+    // 在全局环境中注册一个由 Rust 闭包实现的原生函数
+    pub fn define_native<F>(&mut self, name: impl Into<String>, arity: usize, func: F)
+    where
+        F: Fn(&mut Interpreter, &[LoxType]) -> LoxType + 'static,
+    {
+        let name = name.into();
+        let function = NativeFn::new(name.clone(), arity, func);
+        self.define_global(&name, Box::new(function));
+    }
+
+    // 求值被调用者与参数，并检查被调用者可以调用且参数数量匹配
+    fn evaluate_call(
+        &mut self,
+        expr: &Call,
+    ) -> Result<(Box<dyn Callable>, Vec<LoxType>), LoxInterrupt> {
+        let callee = self.evaluate(expr.callee.as_ref())?;
+        let mut arguments = Vec::new();
+        for argument in &expr.arguments {
+            arguments.push(self.evaluate(argument.as_ref())?);
+        }
+        // 需要确保 callee 是一个函数
+        let LoxType::Function(function) = callee else {
+            return Err(
+                LoxRuntimeError::new(expr.paren.clone(), "Can only call functions.").into(),
+            );
+        };
+        // 检查调用的参数数量是否匹配
+        if arguments.len() != function.arity() {
+            let message = format!(
+                "Expected {} arguments but got {}.",
+                function.arity(),
+                arguments.len()
+            );
+            return Err(LoxRuntimeError::new(expr.paren.clone(), &message).into());
+        }
+        Ok((function, arguments))
+    }
+
+    pub fn set_max_call_depth(&mut self, limit: usize) {
+        self.max_call_depth = limit;
+    }
+
+    // 注册单步回调，代码块与函数体中的语句同样会触发
+    // 回调只能观察语句，不能再重入调用解释器的方法
+    pub fn set_step_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(&dyn Stmt) + 'static,
+    {
+        self.step_callback = Some(Box::new(callback));
+    }
+
+    // 进入一层函数调用，超过最大调用深度时返回运行时错误
+    pub fn enter_call(
+        &mut self,
+        paren: &Token,
+        name: String,
+    ) -> Result<CallFrameGuard, LoxRuntimeError> {
+        if self.call_stack.borrow().len() >= self.max_call_depth {
+            let message = format!(
+                "Stack overflow: maximum call depth of {} exceeded.",
+                self.max_call_depth
+            );
+            return Err(LoxRuntimeError::new(paren.clone(), &message));
         }
+        self.call_stack.borrow_mut().push(name);
+        Ok(CallFrameGuard {
+            call_stack: self.call_stack.clone(),
+        })
+    }
+
+    // 当前调用栈的快照，由外向内排列，最外层为顶层代码
+    // 尾调用会复用调用帧，因此被尾调用替换的函数不会出现在其中
+    pub fn call_stack(&self) -> Vec<String> {
+        let mut call_stack = vec![SCRIPT_FRAME.to_string()];
+        call_stack.extend(self.call_stack.borrow().iter().cloned());
+        call_stack
+    }
+
+    // 记录变量表达式所引用的作用域深度，depth 为 None 时表示全局变量
+    pub fn resolve(&mut self, expr: &dyn Expr, depth: Option<usize>) {
+        let key = expr_key(expr);
+        match depth {
+            Some(depth) => self.locals.insert(key, depth),
+            None => self.locals.remove(&key),
+        };
+    }
+
+    fn look_up_variable(&self, name: &Token, expr: &dyn Expr) -> Result<LoxType, LoxRuntimeError> {
+        let key = expr_key(expr);
+        let value = match self.locals.get(&key) {
+            Some(depth) => self.environment.borrow().get_at(*depth, &name.lexeme),
+            None => self.globals.borrow().get(&name.lexeme),
+        };
+        value.map_err(|err| LoxRuntimeError::new(name.clone(), &err))
     }
 
-    pub fn interpret(&mut self, statements: &Vec<Box<dyn Stmt>>) {
+    pub fn interpret(&mut self, statements: &Vec<Box<dyn Stmt>>) -> Result<(), LoxRuntimeError> {
         for statement in statements {
-            self.execute(statement);
+            match self.execute(statement.as_ref()) {
+                Ok(_) => {}
+                Err(LoxInterrupt::Error(error)) => return Err(error),
+                // Resolver 会拒绝顶层的 return，未经检查时直接结束执行
+                Err(_) => break,
+            }
         }
+        Ok(())
     }
 
-    fn execute(&mut self, stmt: &Box<dyn Stmt>) -> Result<Option<LoxType>, LoxReturn> {
+    fn execute(&mut self, stmt: &dyn Stmt) -> Result<Option<LoxType>, LoxInterrupt> {
+        if let Some(callback) = self.step_callback.as_mut() {
+            callback(stmt);
+        }
         stmt.accept(self)
     }
 
+    // 扫描、解析并检查被导入的文件，返回第一个错误的描述
+    fn load_module(&mut self, path: &Path) -> Result<Vec<Box<dyn Stmt>>, String> {
+        let source = std::fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut scanner = Scanner::new(source, path.to_str());
+        let tokens = scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(error.to_string());
+        }
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| errors[0].to_string())?;
+        // 与主脚本一样先做常量折叠与死代码删除，再解析变量作用域
+        let statements = Optimizer::optimize(&statements);
+        let mut resolver = Resolver::new(self);
+        resolver.resolve_module(&statements);
+        if let Some(error) = resolver.errors().first() {
+            return Err(error.to_string());
+        }
+        Ok(statements)
+    }
+
+    fn import_module(
+        &mut self,
+        stmt: &Import,
+        path: PathBuf,
+    ) -> Result<Option<LoxType>, LoxInterrupt> {
+        let key = canonical_path(&path);
+        if self.importing.contains(&key) {
+            let message = format!("Circular import of '{}'.", stmt.path);
+            return Err(LoxRuntimeError::new(stmt.keyword.clone(), &message).into());
+        }
+        let statements = self.load_module(&path).map_err(|error| {
+            let message = format!("Failed to import '{}': {}", stmt.path, error);
+            LoxRuntimeError::new(stmt.keyword.clone(), &message)
+        })?;
+        self.importing.push(key);
+        let module = Rc::new(RefCell::new(Environment::new_with_enclosing(
+            self.globals.clone(),
+        )));
+        let previous = mem::replace(&mut self.environment, module.clone());
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement.as_ref()).map(|_| ()));
+        self.environment = previous;
+        result?;
+        for (name, value) in module.borrow().iter() {
+            self.globals
+                .borrow_mut()
+                .define(name.to_string(), value.clone());
+        }
+        Ok(None)
+    }
+
     pub fn execute_block(
         &mut self,
         statements: &Vec<Box<dyn Stmt>>,
         environment: Environment,
-    ) -> Result<(), LoxReturn> {
+    ) -> Result<(), LoxInterrupt> {
         let new_rc_environment = Rc::new(RefCell::new(environment));
         let original_env = mem::replace(&mut self.environment, new_rc_environment);
+        let mut result = Ok(());
         for statement in statements {
-            self.execute(statement)?;
+            if let Err(lox_return) = self.execute(statement.as_ref()) {
+                result = Err(lox_return);
+                break;
+            }
         }
+        // 无论是否提前 return，都需要恢复原来的环境
         self.environment = original_env;
-        Ok(())
+        result
     }
 
-    fn evaluate(&mut self, expr: &dyn Expr) -> Result<Option<LoxType>, LoxReturn> {
+    pub fn evaluate(&mut self, expr: &dyn Expr) -> Result<LoxType, LoxInterrupt> {
         expr.accept(self)
     }
 
-    fn is_truthy(&self, value: &Option<LoxType>) -> bool {
+    pub fn is_truthy(&self, value: &LoxType) -> bool {
+        // 只有 nil 与 false 为假，其余值（包括 0 与空字符串）均为真
         match value {
-            None => true,
-            Some(lox_type) => match lox_type {
-                LoxType::Str(str) => str.len() > 0,
-                LoxType::Num(num) => **num != 0.0,
-                LoxType::Bool(boolean) => boolean.as_ref().clone(),
-                LoxType::Function(function) => {
-                    panic!("Cannot evaluate truthiness of function.");
+            LoxType::Nil => false,
+            LoxType::Bool(boolean) => *boolean,
+            _ => true,
+        }
+    }
+
+    fn is_equal(&self, a: LoxType, b: LoxType) -> bool {
+        a == b
+    }
+
+    // 对两个已经求值的操作数执行二元运算，复合赋值同样使用
+    fn apply_binary(
+        &self,
+        operator: &Token,
+        left: LoxType,
+        right: LoxType,
+    ) -> Result<LoxType, LoxInterrupt> {
+        match operator.token_type {
+            TokenType::Plus => self.operate(operator, left + right),
+            TokenType::Minus => self.operate(operator, left - right),
+            TokenType::Star => self.operate(operator, left * right),
+            TokenType::Slash => self.operate(operator, left / right),
+            TokenType::Percent => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                if right == 0.0 {
+                    return Err(LoxRuntimeError::new(operator.clone(), "Modulo by zero.").into());
                 }
-            },
+                Ok(LoxType::new_num(left.rem(right)))
+            }
+            TokenType::StarStar => {
+                let (left, right) = self.check_number_operands(operator, &left, &right)?;
+                Ok(LoxType::new_num(left.powf(right)))
+            }
+            // Comparison operators
+            TokenType::Greater => self.compare(operator, left, right, |left, right| left > right),
+            TokenType::GreaterEqual => {
+                self.compare(operator, left, right, |left, right| left >= right)
+            }
+            TokenType::Less => self.compare(operator, left, right, |left, right| left < right),
+            TokenType::LessEqual => {
+                self.compare(operator, left, right, |left, right| left <= right)
+            }
+            TokenType::BangEqual => Ok(LoxType::new_bool(!self.is_equal(left, right))),
+            TokenType::EqualEqual => Ok(LoxType::new_bool(self.is_equal(left, right))),
+            _ => Ok(LoxType::Nil),
         }
     }
 
-    fn panic_none_or_nil(&self, lists: Vec<&Option<LoxType>>) {
-        for item in lists {
-            if item.is_none() {
-                panic!("Operand must not be nil.");
+    fn index_value(
+        &self,
+        bracket: &Token,
+        object: &LoxType,
+        index: &LoxType,
+    ) -> Result<LoxType, LoxInterrupt> {
+        match object {
+            LoxType::List(elements) => {
+                let elements = elements.borrow();
+                let index = self.list_index(bracket, elements.len(), index)?;
+                Ok(elements[index].clone())
+            }
+            LoxType::Map(entries) => {
+                let key = self.map_key(bracket, index)?;
+                match entries.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => {
+                        let message = format!("Undefined key '{}'.", key);
+                        Err(LoxRuntimeError::new(bracket.clone(), &message).into())
+                    }
+                }
+            }
+            _ => {
+                let message = "Only lists and maps can be indexed.";
+                Err(LoxRuntimeError::new(bracket.clone(), message).into())
             }
         }
     }
 
-    fn is_equal(&self, a: Option<LoxType>, b: Option<LoxType>) -> bool {
-        match (a, b) {
-            (None, None) => true,
-            (Some(_), None) | (None, Some(_)) => false,
-            (Some(val_a), Some(val_b)) => val_a == val_b,
+    // 下标赋值要写入的值，复合赋值先读取下标处当前的值再与右侧的值运算
+    // 对象与下标已经求值，这里不会再次求值，其中的副作用只发生一次
+    fn subscript_assigned_value(
+        &mut self,
+        expr: &SubscriptAssign,
+        object: &LoxType,
+        index: &LoxType,
+    ) -> Result<LoxType, LoxInterrupt> {
+        let Some(operator) = &expr.operator else {
+            return self.evaluate(expr.value.as_ref());
+        };
+        let current = self.index_value(&expr.bracket, object, index)?;
+        let right = self.evaluate(expr.value.as_ref())?;
+        self.apply_binary(operator, current, right)
+    }
+
+    fn check_number_operands(
+        &self,
+        operator: &Token,
+        left: &LoxType,
+        right: &LoxType,
+    ) -> Result<(f64, f64), LoxRuntimeError> {
+        match (left, right) {
+            (LoxType::Num(left), LoxType::Num(right)) => Ok((*left, *right)),
+            _ => Err(LoxRuntimeError::new(
+                operator.clone(),
+                "Operands must be numbers.",
+            )),
+        }
+    }
+
+    // 列表下标必须是处于范围内的非负整数
+    pub fn list_index(
+        &self,
+        bracket: &Token,
+        len: usize,
+        index: &LoxType,
+    ) -> Result<usize, LoxRuntimeError> {
+        let LoxType::Num(index) = index else {
+            return Err(LoxRuntimeError::new(
+                bracket.clone(),
+                "List index must be a number.",
+            ));
+        };
+        if index.fract() != 0.0 || *index < 0.0 {
+            return Err(LoxRuntimeError::new(
+                bracket.clone(),
+                "List index must be a non-negative integer.",
+            ));
         }
+        let index = *index as usize;
+        if index >= len {
+            return Err(LoxRuntimeError::new(
+                bracket.clone(),
+                "List index out of bounds.",
+            ));
+        }
+        Ok(index)
     }
 
-    fn check_number_operand(&self, operator: &Token, operand: &Option<LoxType>) {
-        if let Some(LoxType::Num(_)) = operand {
-            return;
+    // 字典的键必须是字符串
+    pub fn map_key(&self, bracket: &Token, key: &LoxType) -> Result<String, LoxRuntimeError> {
+        match key {
+            LoxType::Str(key) => Ok(key.to_string()),
+            _ => Err(LoxRuntimeError::new(
+                bracket.clone(),
+                "Map key must be a string.",
+            )),
         }
-        panic!("Operand must be a number for operator {:?}", operator);
     }
 
-    fn compare_numbers<F>(
+    // 只有两个数字或两个字符串之间可以比较大小
+    fn compare<F>(
         &self,
-        left: Option<LoxType>,
-        right: Option<LoxType>,
+        operator: &Token,
+        left: LoxType,
+        right: LoxType,
         compare: F,
-    ) -> Result<Option<LoxType>, LoxReturn>
+    ) -> Result<LoxType, LoxInterrupt>
     where
-        F: FnOnce(f64, f64) -> bool,
+        F: FnOnce(&LoxType, &LoxType) -> bool,
     {
-        self.panic_none_or_nil(vec![&left, &right]);
-        match (left.unwrap(), right.unwrap()) {
-            (LoxType::Num(left), LoxType::Num(right)) => {
-                Ok(Some(LoxType::new_bool(compare(*left, *right))))
+        match (&left, &right) {
+            (LoxType::Num(_), LoxType::Num(_)) | (LoxType::Str(_), LoxType::Str(_)) => {
+                Ok(LoxType::new_bool(compare(&left, &right)))
             }
-            _ => panic!("Operand must be numbers"),
+            _ => Err(LoxRuntimeError::new(
+                operator.clone(),
+                "Operands must be two numbers or two strings.",
+            )
+            .into()),
         }
     }
 
-    fn calculate_number<F>(
+    // 将 LoxType 运算的类型错误转换为指向运算符的运行时错误
+    fn operate(
         &self,
-        left: Option<LoxType>,
-        right: Option<LoxType>,
-        calculate: F,
-    ) -> Result<Option<LoxType>, LoxReturn>
-    where
-        F: FnOnce(f64, f64) -> f64,
-    {
-        self.panic_none_or_nil(vec![&left, &right]);
-        match (left.unwrap(), right.unwrap()) {
-            (LoxType::Num(left), LoxType::Num(right)) => {
-                Ok(Some(LoxType::new_num(calculate(*left, *right))))
-            }
-            _ => panic!("Operand must be numbers"),
-        }
+        operator: &Token,
+        result: Result<LoxType, TypeError>,
+    ) -> Result<LoxType, LoxInterrupt> {
+        result.map_err(|error| LoxRuntimeError::new(operator.clone(), &error.to_string()).into())
     }
 }
 
 impl ExprVisitor for Interpreter {
-    fn assign_visit(&mut self, expr: &Assign) -> Result<Option<LoxType>, LoxReturn> {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
         let value = self.evaluate(expr.value.as_ref())?;
 
-        self.environment
-            .borrow_mut()
-            .assign(expr.name.lexeme.clone(), value.clone())
-            .unwrap_or_else(|err| {
-                panic!("{}", err);
-            });
+        let key = expr_key(expr);
+        let result = match self.locals.get(&key) {
+            Some(depth) => {
+                self.environment
+                    .borrow_mut()
+                    .assign_at(*depth, &expr.name.lexeme, value.clone())
+            }
+            None => self
+                .globals
+                .borrow_mut()
+                .assign(expr.name.lexeme.clone(), value.clone()),
+        };
+        result.map_err(|err| LoxRuntimeError::new(expr.name.clone(), &err))?;
         Ok(value)
     }
 
-    fn binary_visit(&mut self, expr: &Binary) -> Result<Option<LoxType>, LoxReturn> {
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
         log_info!("Visiting Binary Expression: {:?}", expr);
         let left = self.evaluate(expr.left.as_ref())?;
         let right = self.evaluate(expr.right.as_ref())?;
 
-        match expr.operator.token_type {
-            TokenType::Plus => {
-                self.panic_none_or_nil(vec![&left, &right]);
-                match (left.unwrap(), right.unwrap()) {
-                    (LoxType::Str(left_str), LoxType::Str(right_str)) => {
-                        return Ok(Some(LoxType::Str(Box::new(format!(
-                            "{}{}",
-                            *left_str, *right_str
-                        )))));
-                    }
-                    (LoxType::Num(left_num), LoxType::Num(right_str)) => {
-                        return Ok(Some(LoxType::Num(Box::new(*left_num + *right_str))));
-                    }
-                    // 一侧为字符串，另一侧为数字时，进行字符串拼接
-                    (LoxType::Str(left_str), LoxType::Num(right_num)) => {
-                        return Ok(Some(LoxType::Str(Box::new(format!(
-                            "{}{}",
-                            *left_str, *right_num
-                        )))));
-                    }
-                    (LoxType::Num(left_num), LoxType::Str(right_str)) => {
-                        return Ok(Some(LoxType::Str(Box::new(format!(
-                            "{}{}",
-                            *left_num, *right_str
-                        )))));
-                    }
-                    _ => {
-                        panic!("Operands must be numbers or strings.");
-                    }
-                }
-            }
-            TokenType::Minus => self.calculate_number(left, right, |left, right| left - right),
-            TokenType::Star => self.calculate_number(left, right, |left, right| left * right),
-            TokenType::Slash => self.calculate_number(left, right, |left, right| {
-                if right == 0.0 {
-                    panic!("Division by zero.");
-                }
-                left / right
-            }),
-            // Comparison operators
-            TokenType::Greater => self.compare_numbers(left, right, |left, right| left > right),
-            TokenType::GreaterEqual => {
-                self.compare_numbers(left, right, |left, right| left >= right)
-            }
-            TokenType::Less => self.compare_numbers(left, right, |left, right| left < right),
-            TokenType::LessEqual => self.compare_numbers(left, right, |left, right| left <= right),
-            TokenType::BangEqual => Ok(Some(LoxType::new_bool(!self.is_equal(left, right)))),
-            TokenType::EqualEqual => Ok(Some(LoxType::new_bool(self.is_equal(left, right)))),
-            _ => Ok(None),
-        }
+        self.apply_binary(&expr.operator, left, right)
     }
 
-    fn grouping_visit(&mut self, expr: &Grouping) -> Result<Option<LoxType>, LoxReturn> {
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
         log_info!("Visiting Grouping Expression: {:?}", expr);
         expr.expression.accept(self)
     }
 
-    fn literal_visit(&mut self, expr: &Literal) -> Result<Option<LoxType>, LoxReturn> {
+    fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
         log_info!("Visiting Literal Expression: {:?}", expr);
         Ok(expr.value.clone())
     }
 
-    fn logical_visit(&mut self, expr: &Logical) -> Result<Option<LoxType>, LoxReturn> {
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
         let left = self.evaluate(expr.left.as_ref())?;
 
         if expr.operator.token_type == TokenType::Or {
@@ -233,105 +640,179 @@ impl ExprVisitor for Interpreter {
         self.evaluate(expr.right.as_ref())
     }
 
-    fn unary_visit(&mut self, expr: &Unary) -> Result<Option<LoxType>, LoxReturn> {
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        let condition = self.evaluate(expr.condition.as_ref())?;
+        if self.is_truthy(&condition) {
+            self.evaluate(expr.then_branch.as_ref())
+        } else {
+            self.evaluate(expr.else_branch.as_ref())
+        }
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        let function = LoxFunction::new(expr.function.clone(), self.environment.clone());
+        Ok(LoxType::new_function(Box::new(function)))
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
         log_info!("Visiting Unary Expression: {:?}", expr);
         let right = self.evaluate(expr.right.as_ref())?;
 
         match expr.operator.token_type {
-            TokenType::Minus => {
-                if let Some(LoxType::Num(num)) = right {
-                    Ok(Some(LoxType::new_num(-*num)))
-                } else {
-                    panic!("Operand must be a number.");
+            TokenType::Bang => Ok(LoxType::new_bool(!self.is_truthy(&right))),
+            TokenType::Minus => self.operate(&expr.operator, -right),
+            _ => Ok(LoxType::Nil),
+        }
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        Ok(self.look_up_variable(&expr.name, expr)?)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        let (mut function, arguments) = self.evaluate_call(expr)?;
+        Ok(function.call(self, &expr.paren, &arguments)?)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        let object = self.evaluate(expr.object.as_ref())?;
+        if let LoxType::Instance(instance) = object {
+            return Ok(instance.get(&expr.name)?);
+        }
+        Err(LoxRuntimeError::new(expr.name.clone(), "Only instances have properties.").into())
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        let object = self.evaluate(expr.object.as_ref())?;
+        if let LoxType::Instance(instance) = object {
+            // 复合赋值先读取字段当前的值，对象表达式只求值一次
+            let value = match &expr.operator {
+                Some(operator) => {
+                    let current = instance.get(&expr.name)?;
+                    let right = self.evaluate(expr.value.as_ref())?;
+                    self.apply_binary(operator, current, right)?
                 }
-            }
-            _ => Ok(None),
+                None => self.evaluate(expr.value.as_ref())?,
+            };
+            instance.set(&expr.name, value.clone());
+            return Ok(value);
         }
+        Err(LoxRuntimeError::new(expr.name.clone(), "Only instances have fields.").into())
     }
 
-    fn variable_visit(&mut self, expr: &Variable) -> Result<Option<LoxType>, LoxReturn> {
-        Ok(self
-            .environment
-            .borrow()
-            .get(expr.name.lexeme.as_str())
-            .clone())
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        let mut elements = Vec::with_capacity(expr.elements.len());
+        for element in expr.elements.iter() {
+            elements.push(self.evaluate(element.as_ref())?);
+        }
+        Ok(LoxType::new_list(elements))
     }
 
-    fn call_visit(&mut self, expr: &Call) -> Result<Option<LoxType>, LoxReturn> {
-        let callee = self.evaluate(expr.callee.as_ref())?;
-        let mut arguments = Vec::new();
-        for argument in &expr.arguments {
-            arguments.push(self.evaluate(argument.as_ref())?);
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        let object = self.evaluate(expr.object.as_ref())?;
+        let index = self.evaluate(expr.index.as_ref())?;
+        self.index_value(&expr.bracket, &object, &index)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        let object = self.evaluate(expr.object.as_ref())?;
+        let index = self.evaluate(expr.index.as_ref())?;
+        match &object {
+            LoxType::List(elements) => {
+                let value = self.subscript_assigned_value(expr, &object, &index)?;
+                let mut elements = elements.borrow_mut();
+                let index = self.list_index(&expr.bracket, elements.len(), &index)?;
+                elements[index] = value.clone();
+                Ok(value)
+            }
+            LoxType::Map(entries) => {
+                let key = self.map_key(&expr.bracket, &index)?;
+                let value = self.subscript_assigned_value(expr, &object, &index)?;
+                entries.borrow_mut().insert(key, value.clone());
+                Ok(value)
+            }
+            _ => {
+                let message = "Only lists and maps can be indexed.";
+                Err(LoxRuntimeError::new(expr.bracket.clone(), message).into())
+            }
         }
-        // 需要确保 callee 是一个函数
-        if let Some(LoxType::Function(mut function)) = callee {
-            // 检查调用的参数数量是否匹配
-            if arguments.len() != function.arity() {
-                panic!(
-                    "Expected {} arguments but got {}.",
-                    function.arity(),
-                    arguments.len()
-                );
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        let mut entries = HashMap::new();
+        for (key, value) in expr.keys.iter().zip(expr.values.iter()) {
+            entries.insert(key.clone(), self.evaluate(value.as_ref())?);
+        }
+        Ok(LoxType::new_map(entries))
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        let superclass = self.look_up_variable(&expr.keyword, expr)?;
+        let superclass = match &superclass {
+            LoxType::Function(callable) => callable.as_any().downcast_ref::<LoxClass>(),
+            _ => None,
+        };
+        // this 所在的环境总是紧挨在 super 所在环境的内侧
+        let this = match self.locals.get(&expr_key(expr)) {
+            Some(depth) if *depth > 0 => self.environment.borrow().get_at(depth - 1, "this"),
+            _ => Ok(LoxType::Nil),
+        };
+        let Ok(LoxType::Instance(instance)) = this else {
+            let message = "Can't find 'this' for 'super'.";
+            return Err(LoxRuntimeError::new(expr.keyword.clone(), message).into());
+        };
+        match superclass.and_then(|class| class.find_method(&expr.method.lexeme)) {
+            Some(method) => Ok(LoxType::new_function(Box::new(method.bind(*instance)))),
+            None => {
+                let message = format!("Undefined property '{}'.", expr.method.lexeme);
+                Err(LoxRuntimeError::new(expr.method.clone(), &message).into())
             }
-            Ok(function.call(self, &arguments))
-        } else {
-            panic!("Can only call functions.");
         }
     }
+
+    fn this_visit(&mut self, expr: &This) -> Result<LoxType, LoxInterrupt> {
+        Ok(self.look_up_variable(&expr.keyword, expr)?)
+    }
 }
 
 impl StmtVisitor for Interpreter {
-    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxReturn> {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
         let value = self.evaluate(stmt.expression.as_ref())?;
-        match value {
-            Some(v) => match v {
-                LoxType::Str(s) => match unescape(&*s.as_str()) {
-                    Some(unescaped_str) => print!("{}", unescaped_str),
-                    None => print!("{}", *s),
-                },
-                LoxType::Num(n) => {
-                    print!("{}", *n);
-                }
-                LoxType::Bool(b) => {
-                    print!("{}", *b);
-                }
-                LoxType::Function(_) => {
-                    print!("<function>");
-                }
-            },
-            None => {
-                print!("<nil>");
-            }
+        let mut output = self.output.borrow_mut();
+        if stmt.newline {
+            writeln!(output, "{}", value).expect("Failed to write output");
+        } else {
+            write!(output, "{}", value).expect("Failed to write output");
         }
         Ok(None)
     }
 
-    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxReturn> {
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
         let condition_result = self.evaluate(stmt.condition.as_ref())?;
         if self.is_truthy(&condition_result) {
-            self.execute(&stmt.then_branch);
+            self.execute(stmt.then_branch.as_ref())?;
             return Ok(None);
         }
         if let Some(else_branch) = stmt.else_branch.as_ref() {
-            self.execute(else_branch);
+            self.execute(else_branch.as_ref())?;
         }
         Ok(None)
     }
 
-    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxReturn> {
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
         self.execute_block(
             &stmt.statements,
             Environment::new_with_enclosing(self.environment.clone()),
-        );
+        )?;
         Ok(None)
     }
 
-    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxReturn> {
-        self.evaluate(stmt.expression.as_ref());
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.evaluate(stmt.expression.as_ref())?;
         Ok(None)
     }
 
-    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxReturn> {
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
         let value = self.evaluate(stmt.initializer.as_ref())?;
         self.environment
             .borrow_mut()
@@ -339,53 +820,1436 @@ impl StmtVisitor for Interpreter {
         Ok(None)
     }
 
-    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxReturn> {
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        let LoxType::List(elements) = self.evaluate(stmt.initializer.as_ref())? else {
+            let message = "Can only destructure a list.";
+            return Err(LoxRuntimeError::new(stmt.bracket.clone(), message).into());
+        };
+        let elements = elements.borrow();
+        let mut environment = self.environment.borrow_mut();
+        // 列表元素不足时多出的名称绑定为 nil
+        for (index, name) in stmt.names.iter().enumerate() {
+            let value = elements.get(index).cloned().unwrap_or(LoxType::Nil);
+            environment.define(name.lexeme.clone(), value);
+        }
+        if let Some(rest) = &stmt.rest {
+            let remaining = elements.iter().skip(stmt.names.len()).cloned().collect();
+            environment.define(rest.lexeme.clone(), LoxType::new_list(remaining));
+        }
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = self.evaluate(stmt.initializer.as_ref())?;
+        self.environment
+            .borrow_mut()
+            .define_constant(stmt.name.lexeme.clone(), value);
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
         let mut condition_result = self.evaluate(stmt.condition.as_ref())?;
         while self.is_truthy(&condition_result) {
-            self.execute(&stmt.body);
+            match self.execute(stmt.body.as_ref()) {
+                Err(LoxInterrupt::Break(_)) => break,
+                // continue 只跳过本次循环体剩余部分，自增部分依然需要执行
+                Ok(_) | Err(LoxInterrupt::Continue(_)) => {}
+                Err(interrupt) => return Err(interrupt),
+            }
+            if let Some(increment) = stmt.increment.as_ref() {
+                self.evaluate(increment.as_ref())?;
+            }
             condition_result = self.evaluate(stmt.condition.as_ref())?;
         }
         Ok(None)
     }
 
-    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxReturn> {
-        let function = LoxFunction::new(stmt.clone());
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        let iterable = self.evaluate(stmt.iterable.as_ref())?;
+        let LoxType::List(elements) = iterable else {
+            let message = "Can only iterate over a list.";
+            return Err(LoxRuntimeError::new(stmt.name.clone(), message).into());
+        };
+        // 遍历开始时的快照，循环体内修改列表不会影响本次遍历
+        let elements = elements.borrow().clone();
+        for element in elements {
+            let mut environment = Environment::new_with_enclosing(self.environment.clone());
+            environment.define(stmt.name.lexeme.clone(), element);
+            let original_env =
+                mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+            let result = self.execute(stmt.body.as_ref());
+            self.environment = original_env;
+            match result {
+                Err(LoxInterrupt::Break(_)) => break,
+                Ok(_) | Err(LoxInterrupt::Continue(_)) => {}
+                Err(interrupt) => return Err(interrupt),
+            }
+        }
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        let function = LoxFunction::new(stmt.clone(), self.environment.clone());
         self.environment.borrow_mut().define(
             stmt.name.lexeme.clone(),
-            Some(LoxType::new_function(Box::new(function))),
+            LoxType::new_function(Box::new(function)),
         );
         Ok(None)
     }
 
-    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxReturn> {
-        let mut value = None;
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut value = LoxType::Nil;
         if let Some(return_value) = stmt.value.as_ref() {
+            // 函数内返回调用表达式时不在此处调用，交给当前函数的调用循环执行，避免栈增长
+            let call = return_value.as_any().downcast_ref::<Call>();
+            if let Some(call) = call.filter(|_| !self.call_stack.borrow().is_empty()) {
+                let (callee, arguments) = self.evaluate_call(call)?;
+                let tail_call = LoxTailCall::new(callee, call.paren.clone(), arguments);
+                return Err(LoxInterrupt::TailCall(Box::new(tail_call)));
+            }
             value = self.evaluate(return_value.as_ref())?;
         }
-        Err(LoxReturn::new(value))
+        Err(LoxInterrupt::Return(LoxReturn::new(value)))
+    }
+
+    fn break_visit(&mut self, _stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        Err(LoxInterrupt::Break(LoxBreak))
+    }
+
+    fn continue_visit(&mut self, _stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        Err(LoxInterrupt::Continue(LoxContinue))
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = self.evaluate(stmt.scrutinee.as_ref())?;
+        let mut matched = stmt.default_case.as_ref();
+        for (case, body) in stmt.cases.iter() {
+            let candidate = self.evaluate(case.as_ref())?;
+            if self.is_equal(value.clone(), candidate) {
+                matched = Some(body);
+                break;
+            }
+        }
+        if let Some(body) = matched {
+            // 每个分支都有独立的作用域
+            let environment = Environment::new_with_enclosing(self.environment.clone());
+            self.execute_block(body, environment)?;
+        }
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        let environment = Environment::new_with_enclosing(self.environment.clone());
+        let result = match self.execute_block(&stmt.body, environment) {
+            // try 中 return 的调用需要在这里完成，调用中产生的错误才能被 catch 捕获
+            Err(LoxInterrupt::TailCall(tail_call)) => {
+                let LoxTailCall {
+                    mut callee,
+                    paren,
+                    arguments,
+                } = *tail_call;
+                match callee.call(self, &paren, &arguments) {
+                    Ok(value) => Err(LoxInterrupt::Return(LoxReturn::new(value))),
+                    Err(error) => Err(LoxInterrupt::Error(error)),
+                }
+            }
+            result => result,
+        };
+        match result {
+            // 解释器自身产生的错误以错误信息作为捕获到的值
+            Err(LoxInterrupt::Error(error)) => {
+                let value = error
+                    .value
+                    .unwrap_or_else(|| LoxType::new_str(&error.message));
+                let mut environment = Environment::new_with_enclosing(self.environment.clone());
+                environment.define(stmt.name.lexeme.clone(), value);
+                self.execute_block(&stmt.handler, environment)?;
+                Ok(None)
+            }
+            result => result.map(|_| None),
+        }
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = self.evaluate(stmt.value.as_ref())?;
+        Err(LoxRuntimeError::thrown(stmt.keyword.clone(), value).into())
+    }
+
+    // 在以全局环境为外层的新环境中执行被导入的文件，再把其中的顶层定义合并到全局环境
+    // Resolver 保证 import 只出现在顶层，被导入的文件中的 import 同样合并到全局环境
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        let importer = stmt.keyword.source_file.as_deref().map(Path::new);
+        let path = match importer {
+            Some(file) => file.parent().unwrap_or(Path::new("")).join(&stmt.path),
+            None => PathBuf::from(&stmt.path),
+        };
+        // 最外层的导入同时记录发起导入的文件，导入回该文件同样视为循环
+        let depth = self.importing.len();
+        if depth == 0 {
+            self.importing.extend(importer.map(canonical_path));
+        }
+        let result = self.import_module(stmt, path);
+        self.importing.truncate(depth);
+        result
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut superclass = None;
+        if let Some(superclass_expr) = &stmt.superclass {
+            let value = self.evaluate(superclass_expr.as_ref())?;
+            superclass = match value {
+                LoxType::Function(callable) => {
+                    callable.as_any().downcast_ref::<LoxClass>().cloned()
+                }
+                _ => None,
+            };
+            if superclass.is_none() {
+                let token = match superclass_expr.as_any().downcast_ref::<Variable>() {
+                    Some(variable) => variable.name.clone(),
+                    None => stmt.name.clone(),
+                };
+                return Err(LoxRuntimeError::new(token, "Superclass must be a class.").into());
+            }
+        }
+        // 存在父类时，方法的闭包外再包一层只包含 super 的环境
+        let enclosing = self.environment.clone();
+        if let Some(superclass) = &superclass {
+            let mut environment = Environment::new_with_enclosing(enclosing.clone());
+            environment.define(
+                "super".to_string(),
+                LoxType::new_function(Box::new(superclass.clone())),
+            );
+            self.environment = Rc::new(RefCell::new(environment));
+        }
+        let mut methods = HashMap::new();
+        for method in stmt.methods.iter() {
+            let function = LoxFunction::new(method.clone(), self.environment.clone());
+            methods.insert(method.name.lexeme.clone(), function);
+        }
+        self.environment = enclosing;
+        let class = LoxClass::new(stmt.name.lexeme.clone(), superclass.map(Box::new), methods);
+        self.environment.borrow_mut().define(
+            stmt.name.lexeme.clone(),
+            LoxType::new_function(Box::new(class)),
+        );
+        Ok(None)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::{Scanner, Token};
+    use lox_rs_macros::lox_native;
 
     fn get_number_one() -> Box<Literal> {
-        Box::new(Literal::new(Some(LoxType::new_num(1.0))))
+        Box::new(Literal::new(LoxType::new_num(1.0)))
     }
 
     fn get_number_two() -> Box<Literal> {
-        Box::new(Literal::new(Some(LoxType::new_num(2.0))))
+        Box::new(Literal::new(LoxType::new_num(2.0)))
+    }
+
+    fn try_run_source(source: &str) -> Result<Interpreter, LoxRuntimeError> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(&statements);
+        assert!(resolver.errors().is_empty());
+        interpreter.interpret(&statements)?;
+        Ok(interpreter)
+    }
+
+    fn run_source(source: &str) -> Interpreter {
+        try_run_source(source).unwrap()
+    }
+
+    fn get_value(interpreter: &Interpreter, name: &str) -> LoxType {
+        interpreter.environment.borrow().get(name).unwrap()
     }
 
     #[test]
     fn test_interpreter_plus() {
-        // let left = get_number_one();
-        // let right = get_number_two();
-        // let plus_operator = Token::new(TokenType::Plus, "+".to_string(), 1, 2, 2, None);
-        // let binary_expr = Binary::new(left, plus_operator, right);
+        let left = get_number_one();
+        let right = get_number_two();
+        let plus_operator = Token::new(TokenType::Plus, "+".to_string(), 1, 2, 2, None, None);
+        let binary_expr = Binary::new(left, plus_operator, right);
+
+        let mut interpreter = Interpreter::new();
+        let result = interpreter.evaluate(&binary_expr).unwrap();
+        assert_eq!(result, LoxType::new_num(3.0));
+    }
+
+    #[test]
+    fn test_if_else() {
+        let interpreter = run_source(
+            "var a; var b; var c;
+            if (true) { a = 1; }
+            if (false) { b = 1; } else { b = 2; }
+            if (false) c = 1; else if (false) c = 2; else c = 3;",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_num(3.0));
+    }
+
+    #[test]
+    fn test_return_inside_if() {
+        let interpreter = run_source(
+            "fun sign(n) { if (n < 0) { return -1; } return 1; }
+            var a = sign(-5);",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(-1.0));
+    }
+
+    #[test]
+    fn test_while_loop() {
+        let interpreter = run_source(
+            "var i = 0; var sum = 0;
+            while (i < 5) { i = i + 1; sum = sum + i; }
+            var count = 0;
+            while (false) { count = count + 1; }",
+        );
+        assert_eq!(get_value(&interpreter, "i"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(15.0));
+        assert_eq!(get_value(&interpreter, "count"), LoxType::new_num(0.0));
+    }
 
-        // let mut interpreter = Interpreter::new();
-        // interpreter.interpret(&binary_expr);
+    #[test]
+    fn test_block_scope() {
+        let interpreter = run_source(
+            "var a = 1; var inner; var middle;
+            {
+                var a = 2;
+                {
+                    var a = 3;
+                    inner = a;
+                }
+                middle = a;
+            }",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "middle"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "inner"), LoxType::new_num(3.0));
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let interpreter = run_source(
+            "var sum = 0;
+            for (var i = 1; i <= 4; i = i + 1) { sum = sum + i; }
+            var j = 0;
+            for (; j < 3;) { j = j + 1; }",
+        );
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(10.0));
+        assert_eq!(get_value(&interpreter, "j"), LoxType::new_num(3.0));
+    }
+
+    #[test]
+    fn test_function_call() {
+        let interpreter = run_source(
+            "var called = false;
+            fun mark() { called = true; }
+            mark();
+            fun add(a, b, c) { return a + b + c; }
+            var sum = add(1, 2, 3);
+            fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+            var result = fib(10);",
+        );
+        assert_eq!(get_value(&interpreter, "called"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(6.0));
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(55.0));
+    }
+
+    #[test]
+    fn test_return_value() {
+        let interpreter = run_source(
+            "var x = \"outer\";
+            fun inner() { var x = \"inner\"; return x; }
+            fun outer() { var got = inner(); return x + got; }
+            var a = outer();
+            fun nothing() { return; }
+            var b = nothing();
+            fun first() { while (true) { return 1; } }
+            var c = first();",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_str("outerinner"));
+        assert_eq!(get_value(&interpreter, "x"), LoxType::new_str("outer"));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::Nil);
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_num(1.0));
+    }
+
+    #[test]
+    fn test_break_continue() {
+        let interpreter = run_source(
+            "var i = 0;
+            while (true) { if (i == 3) break; i = i + 1; }
+            var odd_sum = 0; var even = false;
+            for (var n = 0; n < 10; n = n + 1) {
+                even = even == false;
+                if (even) continue;
+                odd_sum = odd_sum + n;
+            }
+            var j = 0; var skipped = 0;
+            while (j < 5) { j = j + 1; if (j == 2) { skipped = skipped + 1; continue; } }",
+        );
+        assert_eq!(get_value(&interpreter, "i"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "j"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "skipped"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "odd_sum"), LoxType::new_num(25.0));
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let error = try_run_source("var a = 1 / 0;").unwrap_err();
+        assert_eq!(error.message, "Division by zero.");
+        assert_eq!(error.token.token_type, TokenType::Slash);
+
+        let error = try_run_source("print missing;").unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+        assert_eq!(error.token.lexeme, "missing");
+
+        let error = try_run_source("missing = 1;").unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+
+        let error = try_run_source("var a = \"a\" - 1;").unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers.");
+
+        let error = try_run_source("var a = nil + 1;").unwrap_err();
+        assert_eq!(error.message, "Operand must not be nil.");
+
+        let error = try_run_source("var a = -\"a\";").unwrap_err();
+        assert_eq!(error.message, "Operand must be a number.");
+
+        let error = try_run_source("var a = 1; a();").unwrap_err();
+        assert_eq!(error.message, "Can only call functions.");
+
+        // 函数内部的错误会传递到调用方，并保留出错的 token
+        let error = try_run_source("fun f(a) { return a < \"b\"; } f(1, 2);").unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+        let error = try_run_source("fun f(a) { return a < \"b\"; } f(1);").unwrap_err();
+        assert_eq!(error.token.token_type, TokenType::Less);
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        let interpreter = Interpreter::new();
+        assert!(!interpreter.is_truthy(&LoxType::Nil));
+        assert!(!interpreter.is_truthy(&LoxType::new_bool(false)));
+        assert!(interpreter.is_truthy(&LoxType::new_bool(true)));
+        assert!(interpreter.is_truthy(&LoxType::new_num(0.0)));
+        assert!(interpreter.is_truthy(&LoxType::new_num(1.0)));
+        assert!(interpreter.is_truthy(&LoxType::new_str("")));
+    }
+
+    #[test]
+    fn test_unary_bang() {
+        let interpreter =
+            run_source("var a = !false; var b = !nil; var c = !0; var d = !!true; var e = !\"\";");
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "d"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "e"), LoxType::new_bool(false));
+    }
+
+    #[test]
+    fn test_modulo() {
+        let interpreter = run_source("var a = 7 % 3; var b = 5 % 5; var c = 1 + 8 % 3 * 2;");
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_num(0.0));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_num(5.0));
+
+        let error = try_run_source("var a = 5 % 0;").unwrap_err();
+        assert_eq!(error.message, "Modulo by zero.");
+        let error = try_run_source("var a = \"5\" % 2;").unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers.");
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let interpreter = run_source(
+            "var x = 5; x += 3;
+            var y = 10; y -= 4; y *= 2; y /= 3;
+            var s = \"a\"; s += \"b\";",
+        );
+        assert_eq!(get_value(&interpreter, "x"), LoxType::new_num(8.0));
+        assert_eq!(get_value(&interpreter, "y"), LoxType::new_num(4.0));
+        assert_eq!(get_value(&interpreter, "s"), LoxType::new_str("ab"));
+
+        let interpreter = run_source(
+            "class Counter {
+                init() { this.count = 0; }
+                increment() { this.count += 1; return this.count; }
+            }
+            var obj = Counter();
+            obj.count += 5;
+            var count = obj.increment();
+            var list = [1, 2];
+            list[1] *= 10;
+            var second = list[1];",
+        );
+        assert_eq!(get_value(&interpreter, "count"), LoxType::new_num(6.0));
+        assert_eq!(get_value(&interpreter, "second"), LoxType::new_num(20.0));
+
+        // 左侧的对象与下标只求值一次，先于右侧的值求值
+        let interpreter = run_source(
+            "var log = \"\";
+            var l = [1, 2, 3];
+            var i = 0;
+            fun idx() { i = i + 1; log = log + \"i\"; return i; }
+            l[idx()] += 10;
+            class Box { init() { this.v = 1; } }
+            var box = Box();
+            var calls = 0;
+            fun get() { calls = calls + 1; log = log + \"g\"; return box; }
+            fun rhs() { log = log + \"r\"; return 5; }
+            get().v += rhs();
+            var m = {\"k\": 1};
+            fun key() { log = log + \"k\"; return \"k\"; }
+            m[key()] -= rhs();",
+        );
+        assert_eq!(get_value(&interpreter, "l").to_string(), "[1, 12, 3]");
+        assert_eq!(get_value(&interpreter, "i"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "calls"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "box").to_string(), "<Box instance>");
+        assert_eq!(get_value(&interpreter, "m").to_string(), "{k: -4}");
+        assert_eq!(get_value(&interpreter, "log"), LoxType::new_str("igrkr"));
+        let interpreter = run_source("class A {} var a = A(); a.v = 1; a.v += 5; var v = a.v;");
+        assert_eq!(get_value(&interpreter, "v"), LoxType::new_num(6.0));
+
+        let error = try_run_source("class A {} A().missing += 1;").unwrap_err();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+        let error = try_run_source("var l = [1]; l[1] += 1;").unwrap_err();
+        assert_eq!(error.message, "List index out of bounds.");
+
+        let error = try_run_source("missing += 1;").unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'missing'.");
+    }
+
+    #[test]
+    fn test_resolved_closure_scope() {
+        let interpreter = run_source(
+            "var a = \"global\";
+            var first; var second;
+            {
+                fun show_a() { return a; }
+                first = show_a();
+                var a = \"block\";
+                second = show_a();
+            }
+            var captured;
+            {
+                var x = \"outer\";
+                fun get_x() { return x; }
+                {
+                    var x = \"inner\";
+                    captured = get_x();
+                }
+            }",
+        );
+        assert_eq!(get_value(&interpreter, "first"), LoxType::new_str("global"));
+        assert_eq!(
+            get_value(&interpreter, "second"),
+            LoxType::new_str("global")
+        );
+        assert_eq!(
+            get_value(&interpreter, "captured"),
+            LoxType::new_str("outer")
+        );
+    }
+
+    #[test]
+    fn test_class_instance() {
+        let interpreter = run_source(
+            "var created = 0;
+            class Counter {
+                init(start) { created = created + start; }
+                increment(counter) { counter.count = counter.count + 1; }
+                get(counter) { return counter.count; }
+            }
+            var counter = Counter(1);
+            counter.count = 0;
+            counter.increment(counter);
+            counter.increment(counter);
+            var result = counter.get(counter);",
+        );
+        assert_eq!(get_value(&interpreter, "created"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(2.0));
+
+        let error = try_run_source("class A {} var a = A(); print a.missing;").unwrap_err();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+        let error = try_run_source("var a = 1; a.field = 2;").unwrap_err();
+        assert_eq!(error.message, "Only instances have fields.");
+        let error = try_run_source("class A { init(a) {} } var a = A();").unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 0.");
+    }
+
+    #[test]
+    fn test_class_inheritance() {
+        let interpreter = run_source(
+            "class A {
+                name() { return \"A\"; }
+                greet() { return \"hello\"; }
+            }
+            class B < A {
+                name() { return \"B\"; }
+                parent_name() { return super.name(); }
+            }
+            class C < B {}
+            var c = C();
+            var inherited = c.greet();
+            var overridden = c.name();
+            var parent = c.parent_name();",
+        );
+        assert_eq!(
+            get_value(&interpreter, "inherited"),
+            LoxType::new_str("hello")
+        );
+        assert_eq!(get_value(&interpreter, "overridden"), LoxType::new_str("B"));
+        assert_eq!(get_value(&interpreter, "parent"), LoxType::new_str("A"));
+
+        let error = try_run_source("var A = 1; class B < A {}").unwrap_err();
+        assert_eq!(error.message, "Superclass must be a class.");
+        let error =
+            try_run_source("class A {} class B < A { f() { return super.missing; } } B().f();")
+                .unwrap_err();
+        assert_eq!(error.message, "Undefined property 'missing'.");
+    }
+
+    #[test]
+    fn test_class_this() {
+        let interpreter = run_source(
+            "class Counter {
+                init() { this.count = 0; }
+                increment() { this.count = this.count + 1; return this; }
+                get() { return this.count; }
+            }
+            class Named < Counter {
+                get() { return super.get() * 10; }
+            }
+            var counter = Counter();
+            counter.increment();
+            counter.increment().increment();
+            var result = counter.get();
+            var method = counter.get;
+            var bound = method();
+            var named = Named();
+            named.increment();
+            var scaled = named.get();",
+        );
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "bound"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "scaled"), LoxType::new_num(10.0));
+    }
+
+    #[test]
+    fn test_nil_value() {
+        let interpreter = run_source(
+            "fun nothing() {}
+            fun explicit() { return nil; }
+            var declared;
+            var implicit_result = nothing();
+            var explicit_result = explicit();
+            var same = declared == nil;
+            var not_false = nil == false;",
+        );
+        assert_eq!(get_value(&interpreter, "declared"), LoxType::Nil);
+        assert_eq!(get_value(&interpreter, "implicit_result"), LoxType::Nil);
+        assert_eq!(get_value(&interpreter, "explicit_result"), LoxType::Nil);
+        assert_eq!(get_value(&interpreter, "same"), LoxType::new_bool(true));
+        assert_eq!(
+            get_value(&interpreter, "not_false"),
+            LoxType::new_bool(false)
+        );
+
+        let error = try_run_source("var a = nil + 1;").unwrap_err();
+        assert_eq!(error.message, "Operand must not be nil.");
+    }
+
+    #[test]
+    fn test_define_native() {
+        let tokens = Scanner::new(
+            "var result = square(4);
+            var time = clock();"
+                .to_string(),
+            None,
+        )
+        .scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        interpreter.define_native("square", 1, |_, arguments| match &arguments[0] {
+            LoxType::Num(n) => LoxType::new_num(*n * *n),
+            _ => LoxType::Nil,
+        });
+        Resolver::new(&mut interpreter).resolve(&statements);
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(16.0));
+        assert!(matches!(get_value(&interpreter, "time"), LoxType::Num(_)));
+
+        let error = try_run_source("clock(1);").unwrap_err();
+        assert_eq!(error.message, "Expected 0 arguments but got 1.");
+    }
+
+    #[test]
+    fn test_native_len() {
+        let interpreter = run_source(
+            "var word = len(\"hello\");
+            var empty = len(\"\");",
+        );
+        assert_eq!(get_value(&interpreter, "word"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "empty"), LoxType::new_num(0.0));
+
+        // 按 Unicode 标量值计数，而不是按字节计数
+        let mut interpreter = Interpreter::new();
+        let paren = Token::new(TokenType::RightParen, ")".to_string(), 1, 0, 1, None, None);
+        let unicode = LenNativeFunction
+            .call(&mut interpreter, &paren, &[LoxType::new_str("日本語")])
+            .unwrap();
+        assert_eq!(unicode, LoxType::new_num(3.0));
+
+        let error = try_run_source("len(42);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Argument to 'len' must be a string or a list."
+        );
+        assert_eq!(error.token.lexeme, ")");
+    }
+
+    #[test]
+    fn test_native_conversion() {
+        let interpreter = run_source(
+            "var from_number = str(42);
+            var from_nil = str(nil);
+            var parsed = num(\"2.75\");
+            var from_true = num(true);
+            var zero = bool(0);
+            var empty = bool(\"\");
+            var none = bool(nil);",
+        );
+        assert_eq!(
+            get_value(&interpreter, "from_number"),
+            LoxType::new_str("42")
+        );
+        assert_eq!(get_value(&interpreter, "from_nil"), LoxType::new_str("nil"));
+        assert_eq!(get_value(&interpreter, "parsed"), LoxType::new_num(2.75));
+        assert_eq!(get_value(&interpreter, "from_true"), LoxType::new_num(1.0));
+        // 0 与空字符串在 Lox 中均为真值
+        assert_eq!(get_value(&interpreter, "zero"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "empty"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "none"), LoxType::new_bool(false));
+
+        let error = try_run_source("num(\"abc\");").unwrap_err();
+        assert_eq!(error.message, "Can't convert 'abc' to a number.");
+        let error = try_run_source("num(nil);").unwrap_err();
+        assert_eq!(error.message, "Can't convert 'nil' to a number.");
+    }
+
+    #[test]
+    fn test_native_math() {
+        let interpreter = run_source(
+            "var root = sqrt(4);
+            var floored = floor(2.7);
+            var ceiled = ceil(2.1);
+            var absolute = abs(-5);
+            var rounded = round(2.5);
+            var power = pow(2, 8);
+            var logged = log(1);
+            var nan = sqrt(-1);",
+        );
+        assert_eq!(get_value(&interpreter, "root"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "floored"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "ceiled"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "absolute"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "rounded"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "power"), LoxType::new_num(256.0));
+        assert_eq!(get_value(&interpreter, "logged"), LoxType::new_num(0.0));
+        // 负数开方按 IEEE 754 得到 NaN，而不是运行时错误
+        assert!(matches!(get_value(&interpreter, "nan"), LoxType::Num(n) if n.is_nan()));
+
+        let error = try_run_source("sqrt(\"4\");").unwrap_err();
+        assert_eq!(error.message, "Argument to 'sqrt' must be a number.");
+        let error = try_run_source("pow(2, nil);").unwrap_err();
+        assert_eq!(error.message, "Arguments to 'pow' must be numbers.");
+        let error = try_run_source("pow(2);").unwrap_err();
+        assert_eq!(error.message, "Expected 2 arguments but got 1.");
+    }
+
+    #[test]
+    fn test_native_string() {
+        let interpreter = run_source(
+            "var length = str_len(\"hello\");
+            var upper = str_upper(\"Lox\");
+            var lower = str_lower(\"Lox\");
+            var trimmed = str_trim(\"  lox \");
+            var contains = str_contains(\"hello\", \"ell\");
+            var missing = str_contains(\"hello\", \"xyz\");
+            var starts = str_starts_with(\"hello\", \"he\");
+            var ends = str_ends_with(\"hello\", \"he\");
+            var sub = str_substr(\"hello\", 1, 3);
+            var empty = str_substr(\"hello\", 5, 0);",
+        );
+        assert_eq!(get_value(&interpreter, "length"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "upper"), LoxType::new_str("LOX"));
+        assert_eq!(get_value(&interpreter, "lower"), LoxType::new_str("lox"));
+        assert_eq!(get_value(&interpreter, "trimmed"), LoxType::new_str("lox"));
+        assert_eq!(get_value(&interpreter, "contains"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "missing"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "starts"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "ends"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "sub"), LoxType::new_str("ell"));
+        assert_eq!(get_value(&interpreter, "empty"), LoxType::new_str(""));
+
+        // 与 len 一致，按 Unicode 标量值而不是字节计算长度与下标
+        let interpreter = run_source(
+            "var length = str_len(\"日本\");
+            var sub = str_substr(\"日本語\", 1, 2);",
+        );
+        assert_eq!(get_value(&interpreter, "length"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "sub"), LoxType::new_str("本語"));
+
+        let error = try_run_source("str_upper(1);").unwrap_err();
+        assert_eq!(error.message, "Argument to 'str_upper' must be a string.");
+        let error = try_run_source("str_contains(nil, \"a\");").unwrap_err();
+        assert_eq!(
+            error.message,
+            "First argument to 'str_contains' must be a string."
+        );
+        let error = try_run_source("str_ends_with(\"a\", 1);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Second argument to 'str_ends_with' must be a string."
+        );
+        let error = try_run_source("str_substr(\"hello\", 3, 5);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Substring range 3..8 out of bounds for length 5."
+        );
+        let error = try_run_source("str_substr(\"hello\", -1, 2);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Substring start and length must be non-negative integers."
+        );
+    }
+
+    #[test]
+    fn test_native_random() {
+        let interpreter = run_source(
+            "var low = 1;
+            var high = 0;
+            var in_range = true;
+            for (var i = 0; i < 1000; i = i + 1) {
+                var r = random();
+                if (r < low) low = r;
+                if (r > high) high = r;
+                var n = random_range(-2, 3);
+                if (n < -2 or n >= 3) in_range = false;
+            }",
+        );
+        let low: f64 = get_value(&interpreter, "low").try_into().unwrap();
+        let high: f64 = get_value(&interpreter, "high").try_into().unwrap();
+        assert!((0.0..1.0).contains(&low) && (0.0..1.0).contains(&high));
+        // 1000 次采样应当覆盖区间的大部分
+        assert!(low < 0.1 && high > 0.9);
+        assert_eq!(get_value(&interpreter, "in_range"), LoxType::new_bool(true));
+
+        let error = try_run_source("random_range(3, 3);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Minimum 3 must be less than maximum 3 in 'random_range'."
+        );
+        let error = try_run_source("random_range(\"a\", 3);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Arguments to 'random_range' must be numbers."
+        );
+    }
+
+    #[test]
+    fn test_native_assert() {
+        let interpreter = run_source("var result = assert(1 + 1 == 2, \"math is broken\");");
+        assert_eq!(get_value(&interpreter, "result"), LoxType::Nil);
+
+        let error = try_run_source("assert(1 + 1 == 3, \"math is broken\");").unwrap_err();
+        assert_eq!(error.message, "math is broken");
+        assert_eq!(error.token.lexeme, ")");
+        // nil 同样是假值
+        let error = try_run_source("assert(nil, \"nil is falsy\");").unwrap_err();
+        assert_eq!(error.message, "nil is falsy");
+
+        let error = try_run_source("assert(true, 1);").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Second argument to 'assert' must be a string."
+        );
+    }
+
+    #[test]
+    fn test_native_type_of() {
+        let interpreter = run_source(
+            "fun f() {}
+            class A {}
+            var types = type_of(1) + \",\" + type_of(\"s\") + \",\" + type_of(true) + \",\"
+                + type_of(nil) + \",\" + type_of(f) + \",\" + type_of(clock) + \",\"
+                + type_of(A) + \",\" + type_of(A());",
+        );
+        assert_eq!(
+            get_value(&interpreter, "types"),
+            LoxType::new_str("number,string,boolean,nil,function,function,class,instance")
+        );
+    }
+
+    #[lox_native]
+    #[arity(1)]
+    fn macro_type_of(arguments: &[Option<LoxType>]) -> Option<LoxType> {
+        let value = arguments[0].as_ref()?;
+        Some(LoxType::from(value.type_name()))
+    }
+
+    #[lox_native]
+    #[arity(0)]
+    fn macro_nothing(_arguments: &[Option<LoxType>]) -> Option<LoxType> {
+        None
+    }
+
+    #[test]
+    fn test_lox_native_macro() {
+        let tokens = Scanner::new(
+            "fun f() {}
+            var same = true;
+            var values = [1, \"s\", true, nil, f, clock, [1]];
+            for (var i = 0; i < len(values); i = i + 1) {
+                same = same and macro_type_of(values[i]) == type_of(values[i]);
+            }
+            var nothing = macro_nothing();"
+                .to_string(),
+            None,
+        )
+        .scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        MacroTypeOfNativeFunction::register(&mut interpreter);
+        MacroNothingNativeFunction::register(&mut interpreter);
+        Resolver::new(&mut interpreter).resolve(&statements);
+        interpreter.interpret(&statements).unwrap();
+        assert_eq!(get_value(&interpreter, "same"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "nothing"), LoxType::Nil);
+
+        assert_eq!(
+            MacroTypeOfNativeFunction.arity(),
+            TypeOfNativeFunction.arity()
+        );
+        assert!(MacroTypeOfNativeFunction.eq_callable(&MacroTypeOfNativeFunction));
+        assert!(!MacroTypeOfNativeFunction.eq_callable(&TypeOfNativeFunction));
+
+        let tokens = Scanner::new("macro_type_of(1, 2);".to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let error = interpreter.interpret(&statements).unwrap_err();
+        assert_eq!(error.message, "Expected 1 arguments but got 2.");
+    }
+
+    #[test]
+    fn test_ternary() {
+        let interpreter = run_source(
+            "var a = true ? 1 : 2;
+            var b = false ? 1 : 2;
+            var c = false ? 1 : nil ? 2 : 3;
+            var d = true ? false ? 1 : 2 : 3;
+            var e;
+            e = 1 > 2 ? \"gt\" : \"le\";",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "d"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "e"), LoxType::new_str("le"));
+    }
+
+    #[test]
+    fn test_lambda() {
+        let interpreter = run_source(
+            "var add = fun(a, b) { return a + b; };
+            var sum = add(1, 2);
+            var result = (fun(x) { return x; })(42);
+            fun make_adder(n) { return fun(x) { return x + n; }; }
+            var add_ten = make_adder(10);
+            var eleven = add_ten(1);",
+        );
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(42.0));
+        assert_eq!(get_value(&interpreter, "eleven"), LoxType::new_num(11.0));
+    }
+
+    #[test]
+    fn test_closure_counter() {
+        let interpreter = run_source(
+            "fun make_counter() {
+                var n = 0;
+                fun count() { n = n + 1; return n; }
+                return count;
+            }
+            var first = make_counter();
+            var second = make_counter();
+            first();
+            first();
+            var a = first();
+            var b = second();",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_num(1.0));
+    }
+
+    #[test]
+    fn test_const() {
+        let interpreter = run_source("const X = 5; var y = X + 1;");
+        assert_eq!(get_value(&interpreter, "X"), LoxType::new_num(5.0));
+        assert_eq!(get_value(&interpreter, "y"), LoxType::new_num(6.0));
+
+        let error = try_run_source("const X = 5; X = 6;").unwrap_err();
+        assert_eq!(error.message, "Can't assign to constant 'X'.");
+        let error = try_run_source("{ const X = 5; X = 6; }").unwrap_err();
+        assert_eq!(error.message, "Can't assign to constant 'X'.");
+
+        let tokens = Scanner::new("const Y;".to_string(), None).scan_tokens();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_power() {
+        let interpreter = run_source(
+            "var a = 2 ** 10; var b = 2 ** 3 ** 2; var c = -2 ** 2; var d = 2 ** -1; var e = 10 % 3;",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_num(1024.0));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_num(512.0));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_num(-4.0));
+        assert_eq!(get_value(&interpreter, "d"), LoxType::new_num(0.5));
+        assert_eq!(get_value(&interpreter, "e"), LoxType::new_num(1.0));
+
+        let error = try_run_source("var a = 2 ** nil;").unwrap_err();
+        assert_eq!(error.message, "Operands must be numbers.");
+    }
+
+    #[test]
+    fn test_list() {
+        let interpreter = run_source(
+            "var a = [1, 2, 3];
+            a[0] = 10;
+            var first = a[0];
+            var b = a;
+            b[2] = \"three\";
+            var last = a[2];
+            var empty = [];
+            var nested = [[1, 2], [3]];
+            var inner = nested[1][0];",
+        );
+        assert_eq!(get_value(&interpreter, "first"), LoxType::new_num(10.0));
+        assert_eq!(get_value(&interpreter, "last"), LoxType::new_str("three"));
+        assert_eq!(get_value(&interpreter, "empty").to_string(), "[]");
+        assert_eq!(get_value(&interpreter, "inner"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "a").to_string(), "[10, 2, three]");
+
+        let error = try_run_source("var a = [1, 2, 3]; print a[3];").unwrap_err();
+        assert_eq!(error.message, "List index out of bounds.");
+        let error = try_run_source("var a = [1]; a[-1] = 2;").unwrap_err();
+        assert_eq!(error.message, "List index must be a non-negative integer.");
+        let error = try_run_source("var a = [1]; print a[0.5];").unwrap_err();
+        assert_eq!(error.message, "List index must be a non-negative integer.");
+        let error = try_run_source("var a = 1; print a[0];").unwrap_err();
+        assert_eq!(error.message, "Only lists and maps can be indexed.");
+    }
+
+    #[test]
+    fn test_list_natives() {
+        let interpreter = run_source(
+            "var l = [];
+            push(l, 1);
+            var alias = l;
+            push(alias, 2);
+            var size = len(l);
+            var second = list_get(l, 1);
+            var popped = pop(l);
+            var remaining = len(alias);
+            pop(l);
+            var empty = pop(l);",
+        );
+        assert_eq!(get_value(&interpreter, "size"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "second"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "popped"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "remaining"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "empty"), LoxType::Nil);
+
+        let error = try_run_source("push(1, 2);").unwrap_err();
+        assert_eq!(error.message, "First argument to 'push' must be a list.");
+        let error = try_run_source("pop(\"abc\");").unwrap_err();
+        assert_eq!(error.message, "Argument to 'pop' must be a list.");
+        let error = try_run_source("list_get([1], 1);").unwrap_err();
+        assert_eq!(error.message, "List index out of bounds.");
+    }
+
+    #[test]
+    fn test_map() {
+        let interpreter = run_source(
+            "var m = {\"x\": 1, y: 2};
+            m[\"z\"] = 3;
+            var has_z = map_has(m, \"z\");
+            var x = m[\"x\"];
+            var y = map_get(m, \"y\");
+            map_set(m, \"w\", 4);
+            var deleted = map_delete(m, \"x\");
+            var has_x = map_has(m, \"x\");
+            var empty = {};
+            {}",
+        );
+        assert_eq!(get_value(&interpreter, "has_z"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "x"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "y"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "deleted"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "has_x"), LoxType::new_bool(false));
+        assert_eq!(
+            get_value(&interpreter, "m").to_string(),
+            "{w: 4, y: 2, z: 3}"
+        );
+        assert_eq!(get_value(&interpreter, "empty").to_string(), "{}");
+
+        let error = try_run_source("var m = {}; print m[\"a\"];").unwrap_err();
+        assert_eq!(error.message, "Undefined key 'a'.");
+        let error = try_run_source("var m = {}; m[1] = 2;").unwrap_err();
+        assert_eq!(error.message, "Map key must be a string.");
+        let error = try_run_source("map_has([], \"a\");").unwrap_err();
+        assert_eq!(error.message, "First argument to 'map_has' must be a map.");
+    }
+
+    #[test]
+    fn test_for_in() {
+        let interpreter = run_source(
+            "var seen = [];
+            for x in [1, 2, 3] { push(seen, x); }
+            var sum = 0;
+            for (var x in [1, 2, 3, 4, 5]) {
+                if (x == 2) continue;
+                if (x == 4) break;
+                sum = sum + x;
+            }
+            var items = [1, 2];
+            for item in items { push(items, item); }",
+        );
+        assert_eq!(get_value(&interpreter, "seen").to_string(), "[1, 2, 3]");
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(4.0));
+        assert_eq!(get_value(&interpreter, "items").to_string(), "[1, 2, 1, 2]");
+
+        let error = try_run_source("for x in 1 { print x; }").unwrap_err();
+        assert_eq!(error.message, "Can only iterate over a list.");
+    }
+
+    #[test]
+    fn test_var_list() {
+        let interpreter = run_source(
+            "var [x, y] = [1, 2];
+            var sum = x + y;
+            var [a] = [];
+            var [first, ...rest] = [1, 2, 3];
+            var [only, ...empty] = [1];
+            fun f() {
+                var [p, q] = [3, 4];
+                return p * q;
+            }
+            var product = f();",
+        );
+        assert_eq!(get_value(&interpreter, "sum"), LoxType::new_num(3.0));
+        assert_eq!(get_value(&interpreter, "a"), LoxType::Nil);
+        assert_eq!(get_value(&interpreter, "first"), LoxType::new_num(1.0));
+        assert_eq!(get_value(&interpreter, "rest").to_string(), "[2, 3]");
+        assert_eq!(get_value(&interpreter, "empty").to_string(), "[]");
+        assert_eq!(get_value(&interpreter, "product"), LoxType::new_num(12.0));
+
+        let error = try_run_source("var [a, b] = \"ab\";").unwrap_err();
+        assert_eq!(error.message, "Can only destructure a list.");
+        assert_eq!(error.token.lexeme, "[");
+    }
+
+    #[test]
+    fn test_switch() {
+        let interpreter = run_source(
+            "fun describe(x) {
+                var result = \"none\";
+                switch (x) {
+                    case 1:
+                        result = \"one\";
+                    case 1 + 1:
+                        var local = \"two\";
+                        result = local;
+                    case \"a\" + \"b\":
+                        result = \"ab\";
+                    default:
+                        result = \"other\";
+                }
+                return result;
+            }
+            var one = describe(1);
+            var two = describe(2);
+            var ab = describe(\"ab\");
+            var other = describe(nil);
+            var count = 0;
+            switch (count = count + 1) {
+                case 3:
+                    count = 10;
+            }",
+        );
+        assert_eq!(get_value(&interpreter, "one").to_string(), "one");
+        assert_eq!(get_value(&interpreter, "two").to_string(), "two");
+        assert_eq!(get_value(&interpreter, "ab").to_string(), "ab");
+        assert_eq!(get_value(&interpreter, "other").to_string(), "other");
+        // 没有匹配且没有 default 时不执行任何分支，被比较的值只求值一次
+        assert_eq!(get_value(&interpreter, "count"), LoxType::new_num(1.0));
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let interpreter = run_source(
+            "fun fail(value) {
+                throw value;
+            }
+            var caught;
+            try {
+                fail([1, 2]);
+                caught = \"unreachable\";
+            } catch (e) {
+                caught = e;
+            }
+            var message;
+            try {
+                nil + 1;
+            } catch (e) {
+                message = e;
+            }
+            var outer;
+            try {
+                try {
+                    throw 1;
+                } catch (e) {
+                    throw e + 1;
+                }
+            } catch (e) {
+                outer = e;
+            }
+            var untouched = \"kept\";
+            try {
+                var local = 1;
+            } catch (e) {
+                untouched = e;
+            }",
+        );
+        assert_eq!(get_value(&interpreter, "caught").to_string(), "[1, 2]");
+        assert_eq!(
+            get_value(&interpreter, "message").to_string(),
+            "Operand must not be nil."
+        );
+        assert_eq!(get_value(&interpreter, "outer"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "untouched").to_string(), "kept");
+        // 捕获后调用栈恢复，之后的调用不受影响
+        assert_eq!(interpreter.call_stack(), ["<script>"]);
+
+        let error = try_run_source("throw \"oops\";").unwrap_err();
+        assert_eq!(error.message, "oops");
+        assert_eq!(error.token.lexeme, "throw");
+    }
+
+    #[test]
+    fn test_assign_global_from_nested_function() {
+        let interpreter = run_source(
+            "var count = 0;
+            {
+                {
+                    fun increment() {
+                        { count = count + 1; }
+                    }
+                    increment();
+                    increment();
+                }
+            }",
+        );
+        assert_eq!(get_value(&interpreter, "count"), LoxType::new_num(2.0));
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        // 测试线程默认的栈较小，在更大的栈上运行以达到默认的调用深度上限
+        let handle = std::thread::Builder::new()
+            .stack_size(64 * 1024 * 1024)
+            .spawn(|| {
+                let interpreter = run_source(
+                    "fun depth(n) { if (n == 0) return 0; return 1 + depth(n - 1); }
+                    var result = depth(200);",
+                );
+                assert_eq!(get_value(&interpreter, "result"), LoxType::new_num(200.0));
+
+                let source = "fun forever(n) { return 1 + forever(n + 1); } forever(0);";
+                let error = try_run_source(source).unwrap_err();
+                assert_eq!(
+                    error.message,
+                    "Stack overflow: maximum call depth of 256 exceeded."
+                );
+            })
+            .unwrap();
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tail_call() {
+        // 超过默认的调用深度上限，只有尾调用不增加调用深度时才能完成
+        let interpreter = run_source(
+            "fun count_down(n) { if (n == 0) return \"done\"; return count_down(n - 1); }
+            var result = count_down(3000);
+            fun is_even(n) { if (n == 0) return true; return is_odd(n - 1); }
+            fun is_odd(n) { if (n == 0) return false; return is_even(n - 1); }
+            var even = is_even(1001);
+            fun length(s) { return len(s); }
+            var size = length(\"abc\");",
+        );
+        assert_eq!(get_value(&interpreter, "result"), LoxType::new_str("done"));
+        assert_eq!(get_value(&interpreter, "even"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "size"), LoxType::new_num(3.0));
+
+        let error = try_run_source("fun f() { return g(1); } fun g() {} f();").unwrap_err();
+        assert_eq!(error.message, "Expected 0 arguments but got 1.");
+    }
+
+    #[test]
+    fn test_compare() {
+        let interpreter = run_source(
+            "var a = 1 < 2; var b = \"apple\" < \"banana\"; var c = \"b\" <= \"a\"; var d = 2 >= 2;",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "d"), LoxType::new_bool(true));
+
+        let error = try_run_source("var a = 1 < \"2\";").unwrap_err();
+        assert_eq!(
+            error.message,
+            "Operands must be two numbers or two strings."
+        );
+        let error = try_run_source("var a = nil > nil;").unwrap_err();
+        assert_eq!(error.token.token_type, TokenType::Greater);
+    }
+
+    #[test]
+    fn test_logical_short_circuit() {
+        let interpreter = run_source(
+            "var a = true or false; var b = false and true; var c = nil or \"x\"; var d = 1 and 2;
+            var calls = 0;
+            fun touch() { calls = calls + 1; return true; }
+            var e = true or touch(); var f = false and touch(); var g = false or touch();",
+        );
+        assert_eq!(get_value(&interpreter, "a"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "b"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "c"), LoxType::new_str("x"));
+        assert_eq!(get_value(&interpreter, "d"), LoxType::new_num(2.0));
+        assert_eq!(get_value(&interpreter, "e"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "f"), LoxType::new_bool(false));
+        assert_eq!(get_value(&interpreter, "g"), LoxType::new_bool(true));
+        // 只有最后一次调用没有被短路
+        assert_eq!(get_value(&interpreter, "calls"), LoxType::new_num(1.0));
+
+        // 被短路的右侧即使会出错也不会被求值
+        run_source("var h = false and -\"a\"; var i = true or undefined();");
+
+        // 被短路的赋值不会生效
+        let interpreter = run_source("var x = 0; false and (x = 1); var y = x; true or (x = 2);");
+        assert_eq!(get_value(&interpreter, "y"), LoxType::new_num(0.0));
+        assert_eq!(get_value(&interpreter, "x"), LoxType::new_num(0.0));
+
+        // and 的优先级高于 or
+        let interpreter = run_source("var j = true or false and false; var k = nil and 1 or 2;");
+        assert_eq!(get_value(&interpreter, "j"), LoxType::new_bool(true));
+        assert_eq!(get_value(&interpreter, "k"), LoxType::new_num(2.0));
+    }
+
+    #[test]
+    fn test_step_callback() {
+        let run = |source: &str| {
+            let steps = Rc::new(std::cell::Cell::new(0));
+            let mut interpreter = Interpreter::new();
+            let counter = Rc::clone(&steps);
+            interpreter.set_step_callback(move |_| counter.set(counter.get() + 1));
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            let statements = Parser::new(tokens).parse().unwrap();
+            Resolver::new(&mut interpreter).resolve(&statements);
+            interpreter.interpret(&statements).unwrap();
+            steps.get()
+        };
+        assert_eq!(run("var a = 1; var b = 2; a = a + b;"), 3);
+        // 代码块本身与其中的两条语句各算一步
+        assert_eq!(run("{ var a = 1; a = 2; }"), 3);
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let error = try_run_source(
+            "fun a() { b(); }
+            fun b() { var f = fun () { c(); }; f(); }
+            fun c() { len(1); }
+            a();",
+        )
+        .unwrap_err();
+        assert_eq!(error.call_stack, ["<script>", "a", "b", "<anonymous>", "c"]);
+
+        // 尾调用复用调用帧
+        let error = try_run_source(
+            "fun a() { return b(); }
+            fun b() { nil + 1; }
+            a();",
+        )
+        .unwrap_err();
+        assert_eq!(error.call_stack, ["<script>", "b"]);
+
+        // 调用结束后调用帧全部弹出
+        let interpreter = run_source("fun f() { return 1; } f();");
+        assert_eq!(interpreter.call_stack(), ["<script>"]);
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut interpreter = run_source("var a = 1; fun f() { return a; }");
+        assert_eq!(interpreter.get_global("a"), Some(LoxType::new_num(1.0)));
+        interpreter.reset();
+        assert_eq!(interpreter.get_global("a"), None);
+        assert!(interpreter.get_global("clock").is_some());
+        assert!(interpreter.get_global("len").is_some());
+
+        let tokens = Scanner::new("print a;".to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let error = interpreter.interpret(&statements).unwrap_err();
+        assert_eq!(error.message, "Undefined variable 'a'.");
+    }
+
+    #[test]
+    fn test_unary_visit() {
+        let cases = [
+            ("!true", LoxType::new_bool(false)),
+            ("!false", LoxType::new_bool(true)),
+            ("!nil", LoxType::new_bool(true)),
+            ("!3", LoxType::new_bool(false)),
+            ("!\"\"", LoxType::new_bool(false)),
+            ("-5", LoxType::new_num(-5.0)),
+            ("-(-3)", LoxType::new_num(3.0)),
+            ("--3", LoxType::new_num(3.0)),
+            ("!!nil", LoxType::new_bool(false)),
+        ];
+        for (source, expected) in cases {
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            let expr = Parser::new(tokens).expression().unwrap();
+            assert!(expr.as_any().is::<Unary>(), "{}", source);
+            let mut interpreter = Interpreter::new();
+            assert_eq!(
+                interpreter.evaluate(expr.as_ref()).unwrap(),
+                expected,
+                "{}",
+                source
+            );
+        }
     }
 }
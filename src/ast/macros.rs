@@ -1,10 +1,8 @@
-use paste::paste;
-
 #[macro_export]
 macro_rules! generate_ast {
     (
         $(
-            $ast_name:ident {
+            $ast_name:ident -> $ret:ty {
                 $(
                     $struct_name:ident($visitor_fn:ident) {
                         $($field_name:ident : $field_type:ty),* $(,)?
@@ -25,12 +23,12 @@ macro_rules! generate_ast {
 
                 pub trait [<$ast_name Visitor>] {
                     $(
-                        fn $visitor_fn(&mut self, [<$ast_name:lower>]: &$struct_name) -> Result<Option<LoxType>, LoxReturn>;
+                        fn $visitor_fn(&mut self, [<$ast_name:lower>]: &$struct_name) -> Result<$ret, LoxInterrupt>;
                     )*
                 }
 
-                pub trait $ast_name:Debug + Send + Sync {
-                    fn accept(&self, visitor: &mut dyn [<$ast_name Visitor>]) -> Result<Option<LoxType>, LoxReturn>;
+                pub trait $ast_name: Debug {
+                    fn accept(&self, visitor: &mut dyn [<$ast_name Visitor>]) -> Result<$ret, LoxInterrupt>;
                     fn get_type(&self) -> [<$ast_name Type>];
                     fn as_any(&self) -> &dyn std::any::Any;
                     fn box_clone(&self) -> Box<dyn $ast_name>;
@@ -51,7 +49,7 @@ macro_rules! generate_ast {
 
                     impl $ast_name for $struct_name {
 
-                        fn accept(&self, visitor: &mut dyn [<$ast_name Visitor>]) -> Result<Option<LoxType>, LoxReturn> {
+                        fn accept(&self, visitor: &mut dyn [<$ast_name Visitor>]) -> Result<$ret, LoxInterrupt> {
                             visitor.$visitor_fn(self)
                         }
 
@@ -0,0 +1,454 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::scanner::LoxType;
+use crate::scanner::token::{LoxInterrupt, Token};
+use std::mem;
+
+// 以 S 表达式的形式输出语法树，语句每行一条，子语句按嵌套层数缩进两个空格
+#[derive(Debug, Default)]
+pub struct AstPrinter {
+    output: String,
+    indent: usize,
+}
+
+impl AstPrinter {
+    #[allow(dead_code)]
+    pub fn print_expr(expr: &dyn Expr) -> String {
+        AstPrinter::default().expr(expr)
+    }
+
+    pub fn print_stmt(stmt: &dyn Stmt) -> String {
+        let mut printer = AstPrinter::default();
+        printer.stmt(stmt);
+        printer.output
+    }
+
+    // 表达式输出在同一行内，先写入独立的缓冲区再取出
+    fn expr(&mut self, expr: &dyn Expr) -> String {
+        let output = mem::take(&mut self.output);
+        expr.accept(self).expect("Printing never interrupts");
+        mem::replace(&mut self.output, output)
+    }
+
+    fn stmt(&mut self, stmt: &dyn Stmt) {
+        stmt.accept(self).expect("Printing never interrupts");
+    }
+
+    // 输出一个没有子语句的节点
+    fn leaf(&mut self, text: &str) {
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+        self.output.push_str(&"  ".repeat(self.indent));
+        self.output.push_str(text);
+    }
+
+    // 输出节点的开头，之后的子语句缩进一层，直到 close 为止
+    fn open(&mut self, text: &str) {
+        self.leaf(text);
+        self.indent += 1;
+    }
+
+    fn close(&mut self) {
+        self.indent -= 1;
+        self.output.push(')');
+    }
+
+    fn parenthesize(&mut self, name: &str, exprs: &[&dyn Expr]) -> String {
+        let mut text = format!("({}", name);
+        for expr in exprs {
+            text.push(' ');
+            text.push_str(&self.expr(*expr));
+        }
+        text.push(')');
+        text
+    }
+
+    fn function(&mut self, kind: &str, function: &Function) {
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect::<Vec<_>>();
+        self.open(&format!("({} ({})", kind, params.join(" ")));
+        for stmt in function.body.iter() {
+            self.stmt(stmt.as_ref());
+        }
+        self.close();
+    }
+}
+
+// 普通赋值输出 =，复合赋值输出 += 等
+fn compound_operator(operator: &Option<Token>) -> String {
+    match operator {
+        Some(operator) => format!("{}=", operator.lexeme),
+        None => String::from("="),
+    }
+}
+
+impl ExprVisitor for AstPrinter {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        let text = self.parenthesize(&format!("= {}", expr.name.lexeme), &[expr.value.as_ref()]);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        let exprs = [expr.left.as_ref(), expr.right.as_ref()];
+        let text = self.parenthesize(&expr.operator.lexeme, &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        let text = self.parenthesize("group", &[expr.expression.as_ref()]);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        match &expr.value {
+            LoxType::Str(s) => self.output.push_str(&format!("\"{}\"", s)),
+            value => self.output.push_str(&value.to_string()),
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        let exprs = [expr.left.as_ref(), expr.right.as_ref()];
+        let text = self.parenthesize(&expr.operator.lexeme, &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        let text = self.parenthesize(&expr.operator.lexeme, &[expr.right.as_ref()]);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        self.output.push_str(&expr.name.lexeme);
+        Ok(LoxType::Nil)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        let mut exprs = vec![expr.callee.as_ref()];
+        exprs.extend(expr.arguments.iter().map(|argument| argument.as_ref()));
+        let text = self.parenthesize("call", &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        self.output
+            .push_str(&format!("(. {} {})", object, expr.name.lexeme));
+        Ok(LoxType::Nil)
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let value = self.expr(expr.value.as_ref());
+        let operator = compound_operator(&expr.operator);
+        self.output.push_str(&format!(
+            "(.{} {} {} {})",
+            operator, object, expr.name.lexeme, value
+        ));
+        Ok(LoxType::Nil)
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        self.output
+            .push_str(&format!("(super {})", expr.method.lexeme));
+        Ok(LoxType::Nil)
+    }
+
+    fn this_visit(&mut self, _expr: &This) -> Result<LoxType, LoxInterrupt> {
+        self.output.push_str("this");
+        Ok(LoxType::Nil)
+    }
+
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        let exprs = [
+            expr.condition.as_ref(),
+            expr.then_branch.as_ref(),
+            expr.else_branch.as_ref(),
+        ];
+        let text = self.parenthesize("?:", &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        // 匿名函数的函数体同样逐行输出，缩进与所在的语句保持一致
+        let output = mem::take(&mut self.output);
+        self.function("fun", &expr.function);
+        let text = mem::replace(&mut self.output, output);
+        self.output.push_str(text.trim_start());
+        Ok(LoxType::Nil)
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        let exprs = expr
+            .elements
+            .iter()
+            .map(|element| element.as_ref())
+            .collect::<Vec<_>>();
+        let text = self.parenthesize("list", &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        let text = self.parenthesize("[]", &[expr.object.as_ref(), expr.index.as_ref()]);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        let exprs = [
+            expr.object.as_ref(),
+            expr.index.as_ref(),
+            expr.value.as_ref(),
+        ];
+        let name = format!("[]{}", compound_operator(&expr.operator));
+        let text = self.parenthesize(&name, &exprs);
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        let mut text = String::from("(map");
+        for (key, value) in expr.keys.iter().zip(expr.values.iter()) {
+            let value = self.expr(value.as_ref());
+            text.push_str(&format!(" (\"{}\" {})", key, value));
+        }
+        text.push(')');
+        self.output.push_str(&text);
+        Ok(LoxType::Nil)
+    }
+}
+
+impl StmtVisitor for AstPrinter {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        let name = if stmt.newline { "print" } else { "write" };
+        let text = self.parenthesize(name, &[stmt.expression.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        let condition = self.expr(stmt.condition.as_ref());
+        self.open(&format!("(if {}", condition));
+        self.stmt(stmt.then_branch.as_ref());
+        if let Some(else_branch) = stmt.else_branch.as_ref() {
+            self.stmt(else_branch.as_ref());
+        }
+        self.close();
+        Ok(None)
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.open("(block");
+        for statement in stmt.statements.iter() {
+            self.stmt(statement.as_ref());
+        }
+        self.close();
+        Ok(None)
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        let text = self.parenthesize(";", &[stmt.expression.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        let name = format!("var {}", stmt.name.lexeme);
+        let text = self.parenthesize(&name, &[stmt.initializer.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut names = stmt
+            .names
+            .iter()
+            .map(|name| name.lexeme.clone())
+            .collect::<Vec<_>>();
+        if let Some(rest) = &stmt.rest {
+            names.push(format!("...{}", rest.lexeme));
+        }
+        let name = format!("var [{}]", names.join(" "));
+        let text = self.parenthesize(&name, &[stmt.initializer.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        let name = format!("const {}", stmt.name.lexeme);
+        let text = self.parenthesize(&name, &[stmt.initializer.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        let condition = self.expr(stmt.condition.as_ref());
+        self.open(&format!("(while {}", condition));
+        self.stmt(stmt.body.as_ref());
+        if let Some(increment) = stmt.increment.as_ref() {
+            let increment = self.expr(increment.as_ref());
+            self.leaf(&increment);
+        }
+        self.close();
+        Ok(None)
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        let iterable = self.expr(stmt.iterable.as_ref());
+        self.open(&format!("(for {} in {}", stmt.name.lexeme, iterable));
+        self.stmt(stmt.body.as_ref());
+        self.close();
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.function(&format!("fun {}", stmt.name.lexeme), stmt);
+        Ok(None)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        let text = match stmt.value.as_ref() {
+            Some(value) => self.parenthesize("return", &[value.as_ref()]),
+            None => String::from("(return)"),
+        };
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn break_visit(&mut self, _stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.leaf("(break)");
+        Ok(None)
+    }
+
+    fn continue_visit(&mut self, _stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.leaf("(continue)");
+        Ok(None)
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        let scrutinee = self.expr(stmt.scrutinee.as_ref());
+        self.open(&format!("(switch {}", scrutinee));
+        for (case, body) in stmt.cases.iter() {
+            let case = self.expr(case.as_ref());
+            self.open(&format!("(case {}", case));
+            for statement in body.iter() {
+                self.stmt(statement.as_ref());
+            }
+            self.close();
+        }
+        if let Some(body) = &stmt.default_case {
+            self.open("(default");
+            for statement in body.iter() {
+                self.stmt(statement.as_ref());
+            }
+            self.close();
+        }
+        self.close();
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.open("(try");
+        for statement in stmt.body.iter() {
+            self.stmt(statement.as_ref());
+        }
+        self.open(&format!("(catch {}", stmt.name.lexeme));
+        for statement in stmt.handler.iter() {
+            self.stmt(statement.as_ref());
+        }
+        self.close();
+        self.close();
+        Ok(None)
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        let text = self.parenthesize("throw", &[stmt.value.as_ref()]);
+        self.leaf(&text);
+        Ok(None)
+    }
+
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.leaf(&format!("(import \"{}\")", stmt.path));
+        Ok(None)
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut text = format!("(class {}", stmt.name.lexeme);
+        if let Some(superclass) = stmt.superclass.as_ref() {
+            text.push_str(&format!(" < {}", self.expr(superclass.as_ref())));
+        }
+        self.open(&text);
+        for method in stmt.methods.iter() {
+            self.function(&method.name.lexeme, method);
+        }
+        self.close();
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::{Scanner, Token, TokenType};
+
+    fn print_source(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        statements
+            .iter()
+            .map(|stmt| AstPrinter::print_stmt(stmt.as_ref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_print_expr() {
+        let plus = Token::new(TokenType::Plus, "+".to_string(), 1, 2, 3, None, None);
+        let binary = Binary::new(
+            Box::new(Literal::new(LoxType::new_num(1.0))),
+            plus,
+            Box::new(Literal::new(LoxType::new_num(2.0))),
+        );
+        assert_eq!(AstPrinter::print_expr(&binary), "(+ 1 2)");
+
+        let printed = print_source("a = -(b.c + \"s\") * f(1, nil);");
+        assert_eq!(
+            printed[0],
+            "(; (= a (* (- (group (+ (. b c) \"s\"))) (call f 1 nil))))"
+        );
+    }
+
+    #[test]
+    fn test_print_stmt() {
+        let printed = print_source("{ var a = 1; print a; }");
+        assert_eq!(printed[0], "(block\n  (var a 1)\n  (print a))");
+
+        let printed = print_source("fun f(a, b) { if (a) return b; else { return; } }");
+        assert_eq!(
+            printed[0],
+            "(fun f (a b)\n  (if a\n    (return b)\n    (block\n      (return))))"
+        );
+
+        let printed = print_source("class B < A { init() { this.x = super.y; } }");
+        assert_eq!(
+            printed[0],
+            "(class B < A\n  (init ()\n    (; (.= this x (super y)))))"
+        );
+    }
+}
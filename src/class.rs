@@ -0,0 +1,119 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::interpreter::Interpreter;
+use crate::function::LoxFunction;
+use crate::scanner::token::Callable;
+use crate::scanner::{LoxType, Token};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[derive(Debug, Clone)]
+pub struct LoxClass {
+    pub name: String,
+    superclass: Option<Box<LoxClass>>,
+    methods: HashMap<String, LoxFunction>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Box<LoxClass>>,
+        methods: HashMap<String, LoxFunction>,
+    ) -> Self {
+        LoxClass {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<LoxFunction> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(method.clone());
+        }
+        // 当前类中没有找到时，沿着继承链向上查找
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+}
+
+impl Callable for LoxClass {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let instance = LoxInstance::new(self.clone());
+        // 存在 init 方法时，创建实例后立即调用进行初始化
+        if let Some(initializer) = self.find_method("init") {
+            initializer
+                .bind(instance.clone())
+                .call(interpreter, paren, arguments)?;
+        }
+        Ok(LoxType::Instance(Box::new(instance)))
+    }
+
+    fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        if let Some(other_class) = other.as_any().downcast_ref::<LoxClass>() {
+            return self.name == other_class.name;
+        }
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoxInstance {
+    pub class: LoxClass,
+    // 实例的字段在所有引用之间共享，保证通过任意引用修改字段后都能被看到
+    fields: Rc<RefCell<HashMap<String, LoxType>>>,
+}
+
+impl LoxInstance {
+    pub fn new(class: LoxClass) -> Self {
+        LoxInstance {
+            class,
+            fields: Rc::new(RefCell::new(HashMap::new())),
+        }
+    }
+
+    pub fn get(&self, name: &Token) -> Result<LoxType, LoxRuntimeError> {
+        if let Some(value) = self.fields.borrow().get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        if let Some(method) = self.class.find_method(&name.lexeme) {
+            let method = method.bind(self.clone());
+            return Ok(LoxType::new_function(Box::new(method)));
+        }
+        let message = format!("Undefined property '{}'.", name.lexeme);
+        Err(LoxRuntimeError::new(name.clone(), &message))
+    }
+
+    pub fn set(&self, name: &Token, value: LoxType) {
+        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+    }
+}
+
+impl PartialEq for LoxInstance {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.fields, &other.fields)
+    }
+}
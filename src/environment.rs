@@ -3,10 +3,17 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
 
+// 环境中保存的变量，constant 为 true 时不允许重新赋值
+#[derive(Debug, Clone)]
+pub struct ConstValue {
+    value: LoxType,
+    constant: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Environment {
     enclosing: Option<Rc<RefCell<Environment>>>,
-    values: HashMap<String, Option<LoxType>>,
+    values: HashMap<String, ConstValue>,
 }
 
 impl Environment {
@@ -24,37 +31,221 @@ impl Environment {
         }
     }
 
-    pub fn new_with_values(values: HashMap<String, Option<LoxType>>) -> Self {
-        Environment {
-            enclosing: None,
-            values,
-        }
+    pub fn define(&mut self, name: String, value: LoxType) {
+        println!("define environment name: {}; value: {:?}", name, value);
+        let entry = ConstValue {
+            value,
+            constant: false,
+        };
+        self.values.insert(name, entry);
     }
 
-    pub fn define(&mut self, name: String, value: Option<LoxType>) {
-        println!("define environment name: {}; value: {:?}", name, value);
-        self.values.insert(name, value);
+    pub fn define_constant(&mut self, name: String, value: LoxType) {
+        let entry = ConstValue {
+            value,
+            constant: true,
+        };
+        self.values.insert(name, entry);
     }
 
-    pub fn get(&self, name: &str) -> Option<LoxType> {
-        if let Some(value) = self.values.get(name) {
-            return value.clone();
+    pub fn get(&self, name: &str) -> Result<LoxType, String> {
+        if let Some(entry) = self.values.get(name) {
+            return Ok(entry.value.clone());
         }
         if let Some(enclosing) = &self.enclosing {
             return enclosing.borrow().get(name);
         }
-        panic!("Undefined variable '{}'.", name);
+        Err(format!("Undefined variable '{}'.", name))
     }
 
-    pub fn assign(&mut self, name: String, value: Option<LoxType>) -> Result<(), String> {
-        if self.values.contains_key(&name) {
-            self.values.insert(name.clone(), value);
-            return Ok(());
+    pub fn get_at(&self, depth: usize, name: &str) -> Result<LoxType, String> {
+        if depth == 0 {
+            return match self.values.get(name) {
+                Some(entry) => Ok(entry.value.clone()),
+                None => Err(format!("Undefined variable '{}'.", name)),
+            };
         }
-        if let Some(enclosing) = &self.enclosing {
-            return enclosing.borrow_mut().assign(name.clone(), value);
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get_at(depth - 1, name),
+            None => Err(Environment::depth_error(depth, name)),
+        }
+    }
+
+    pub fn assign_at(&mut self, depth: usize, name: &str, value: LoxType) -> Result<(), String> {
+        if depth == 0 {
+            return match self.values.get_mut(name) {
+                Some(entry) => Environment::assign_entry(entry, name, value),
+                None => Err(format!("Undefined variable '{}'.", name)),
+            };
+        }
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign_at(depth - 1, name, value),
+            None => Err(Environment::depth_error(depth, name)),
+        }
+    }
+
+    fn depth_error(remaining_depth: usize, name: &str) -> String {
+        format!(
+            "Scope chain is {} level(s) too short to reach variable '{}'.",
+            remaining_depth, name
+        )
+    }
+
+    // 逐层向外查找变量，每次只借用当前一层环境，取出外层环境后立即释放借用
+    pub fn assign(&mut self, name: String, value: LoxType) -> Result<(), String> {
+        if let Some(entry) = self.values.get_mut(&name) {
+            return Environment::assign_entry(entry, &name, value);
+        }
+        let mut current = self.enclosing.clone();
+        while let Some(environment) = current {
+            let mut environment = environment.borrow_mut();
+            if let Some(entry) = environment.values.get_mut(&name) {
+                return Environment::assign_entry(entry, &name, value);
+            }
+            current = environment.enclosing.clone();
         }
 
         Err(format!("Undefined variable '{}'.", name))
     }
+
+    // 当前这一层环境中定义的变量，顺序不固定
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &LoxType)> {
+        self.values
+            .iter()
+            .map(|(name, entry)| (name.as_str(), &entry.value))
+    }
+
+    // 由内向外遍历整条作用域链，被内层同名变量遮蔽的外层变量不会出现
+    pub fn all_iter(&self) -> impl Iterator<Item = (String, LoxType)> {
+        let mut values: HashMap<String, LoxType> = HashMap::new();
+        let mut collect = |environment: &Environment| {
+            for (name, value) in environment.iter() {
+                if !values.contains_key(name) {
+                    values.insert(name.to_string(), value.clone());
+                }
+            }
+        };
+        collect(self);
+        let mut current = self.enclosing.clone();
+        while let Some(environment) = current {
+            let environment = environment.borrow();
+            collect(&environment);
+            current = environment.enclosing.clone();
+        }
+        values.into_iter()
+    }
+
+    fn assign_entry(entry: &mut ConstValue, name: &str, value: LoxType) -> Result<(), String> {
+        if entry.constant {
+            return Err(format!("Can't assign to constant '{}'.", name));
+        }
+        entry.value = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_at_assign_at() {
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global
+            .borrow_mut()
+            .define("a".to_string(), LoxType::new_num(1.0));
+        let middle = Rc::new(RefCell::new(Environment::new_with_enclosing(
+            global.clone(),
+        )));
+        middle
+            .borrow_mut()
+            .define("a".to_string(), LoxType::new_num(2.0));
+        let mut inner = Environment::new_with_enclosing(middle.clone());
+        inner.define("a".to_string(), LoxType::new_num(3.0));
+
+        assert_eq!(inner.get_at(0, "a"), Ok(LoxType::new_num(3.0)));
+        assert_eq!(inner.get_at(1, "a"), Ok(LoxType::new_num(2.0)));
+        assert_eq!(inner.get_at(2, "a"), Ok(LoxType::new_num(1.0)));
+
+        inner
+            .assign_at(2, "a", LoxType::new_str("changed"))
+            .unwrap();
+        assert_eq!(global.borrow().get("a"), Ok(LoxType::new_str("changed")));
+        assert_eq!(inner.get_at(1, "a"), Ok(LoxType::new_num(2.0)));
+
+        assert_eq!(
+            inner.get_at(3, "a"),
+            Err("Scope chain is 1 level(s) too short to reach variable 'a'.".to_string())
+        );
+        assert!(inner.assign_at(5, "a", LoxType::Nil).is_err());
+        assert!(inner.get_at(1, "missing").is_err());
+    }
+
+    #[test]
+    fn test_assign_constant() {
+        let mut environment = Environment::new();
+        environment.define_constant("x".to_string(), LoxType::new_num(5.0));
+        assert_eq!(
+            environment.assign("x".to_string(), LoxType::new_num(6.0)),
+            Err("Can't assign to constant 'x'.".to_string())
+        );
+        assert!(environment.assign_at(0, "x", LoxType::Nil).is_err());
+        assert_eq!(environment.get("x"), Ok(LoxType::new_num(5.0)));
+    }
+
+    #[test]
+    fn test_assign_through_enclosing_chain() {
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global
+            .borrow_mut()
+            .define("a".to_string(), LoxType::new_num(1.0));
+        let mut current = global.clone();
+        for _ in 0..3 {
+            let environment = Environment::new_with_enclosing(current.clone());
+            current = Rc::new(RefCell::new(environment));
+        }
+        current
+            .borrow_mut()
+            .assign("a".to_string(), LoxType::new_num(2.0))
+            .unwrap();
+        assert_eq!(global.borrow().get("a"), Ok(LoxType::new_num(2.0)));
+        assert!(
+            current
+                .borrow_mut()
+                .assign("missing".to_string(), LoxType::Nil)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_iter() {
+        let global = Rc::new(RefCell::new(Environment::new()));
+        global
+            .borrow_mut()
+            .define("a".to_string(), LoxType::new_num(0.0));
+        let mut local = Environment::new_with_enclosing(global.clone());
+        local.define("a".to_string(), LoxType::new_num(1.0));
+        local.define("b".to_string(), LoxType::new_num(2.0));
+        local.define_constant("c".to_string(), LoxType::new_num(3.0));
+
+        let mut values = local.iter().collect::<Vec<_>>();
+        values.sort_by_key(|(name, _)| *name);
+        assert_eq!(
+            values,
+            vec![
+                ("a", &LoxType::new_num(1.0)),
+                ("b", &LoxType::new_num(2.0)),
+                ("c", &LoxType::new_num(3.0)),
+            ]
+        );
+
+        global
+            .borrow_mut()
+            .define("d".to_string(), LoxType::new_num(4.0));
+        let mut values = local.all_iter().collect::<Vec<_>>();
+        values.sort_by(|(left, _), (right, _)| left.cmp(right));
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[0], ("a".to_string(), LoxType::new_num(1.0)));
+        assert_eq!(values[3], ("d".to_string(), LoxType::new_num(4.0)));
+    }
 }
@@ -0,0 +1,50 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::parser::error::ParseError;
+use crate::resolver::error::ResolveError;
+use crate::scanner::error::ScanError;
+use crate::vm::error::CompileError;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+
+// 执行一段源码过程中各个阶段可能产生的错误
+#[derive(Debug)]
+pub enum LoxError {
+    Scan(ScanError),
+    Parse(ParseError),
+    Resolve(ResolveError),
+    Runtime(LoxRuntimeError),
+    Compile(CompileError),
+    Io(io::Error),
+    // 执行多个文件时标注错误所在的文件
+    File { path: String, error: Box<LoxError> },
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxError::Scan(error) => write!(f, "{}", error),
+            LoxError::Parse(error) => write!(f, "{}", error),
+            LoxError::Resolve(error) => write!(f, "{}", error),
+            LoxError::Runtime(error) => write!(f, "{}", error),
+            LoxError::Compile(error) => write!(f, "{}", error),
+            LoxError::Io(error) => write!(f, "{}", error),
+            LoxError::File { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl Error for LoxError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoxError::Scan(error) => Some(error),
+            LoxError::Parse(error) => Some(error),
+            LoxError::Resolve(error) => Some(error),
+            LoxError::Runtime(error) => Some(error),
+            LoxError::Compile(error) => Some(error),
+            LoxError::Io(error) => Some(error),
+            LoxError::File { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
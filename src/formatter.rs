@@ -0,0 +1,508 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::scanner::LoxType;
+use crate::scanner::token::{LoxInterrupt, Token, TokenType};
+use std::mem;
+
+const INDENT: &str = "  ";
+
+// 将语法树重新输出为统一格式的 Lox 源码
+// 块使用两个空格缩进，二元运算符两侧各留一个空格，行尾不留空白
+#[derive(Debug, Default)]
+pub struct Formatter {
+    output: String,
+    indent: usize,
+}
+
+impl Formatter {
+    pub fn format(statements: &[Box<dyn Stmt>]) -> String {
+        let mut formatter = Formatter::default();
+        for stmt in statements {
+            formatter.stmt(stmt.as_ref());
+        }
+        formatter.output
+    }
+
+    // 表达式输出在同一行内，先写入独立的缓冲区再取出
+    fn expr(&mut self, expr: &dyn Expr) -> String {
+        let output = mem::take(&mut self.output);
+        expr.accept(self).expect("Formatting never interrupts");
+        mem::replace(&mut self.output, output)
+    }
+
+    fn stmt(&mut self, stmt: &dyn Stmt) {
+        stmt.accept(self).expect("Formatting never interrupts");
+    }
+
+    // 不带缩进与换行地输出一条语句，用于 for 循环的初始化部分
+    fn inline_stmt(&mut self, stmt: &dyn Stmt) -> String {
+        let output = mem::take(&mut self.output);
+        let indent = mem::replace(&mut self.indent, 0);
+        self.stmt(stmt);
+        self.indent = indent;
+        mem::replace(&mut self.output, output)
+            .trim_end()
+            .to_string()
+    }
+
+    fn line(&mut self, text: &str) {
+        self.output.push_str(&INDENT.repeat(self.indent));
+        self.output.push_str(text);
+        self.output.push('\n');
+    }
+
+    fn statements(&mut self, statements: &[Box<dyn Stmt>]) {
+        self.indent += 1;
+        for stmt in statements {
+            self.stmt(stmt.as_ref());
+        }
+        self.indent -= 1;
+    }
+
+    // 输出带有语句体的结构，语句体为块时花括号与头部在同一行，返回语句体是否为块
+    fn header_body(&mut self, header: &str, body: &dyn Stmt) -> bool {
+        if let Some(block) = body.as_any().downcast_ref::<Block>() {
+            self.line(&format!("{} {{", header));
+            self.statements(&block.statements);
+            self.line("}");
+            true
+        } else {
+            self.line(header);
+            self.indent += 1;
+            self.stmt(body);
+            self.indent -= 1;
+            false
+        }
+    }
+
+    // else if 链保持在同一层缩进，prefix 为连接上一个分支的部分
+    fn if_chain(&mut self, prefix: &str, stmt: &If) {
+        let condition = self.expr(stmt.condition.as_ref());
+        let header = format!("{}if ({})", prefix, condition);
+        let then_block = self.header_body(&header, stmt.then_branch.as_ref());
+        let Some(else_branch) = stmt.else_branch.as_ref() else {
+            return;
+        };
+        let prefix = if then_block {
+            // 将上一行的 "}" 与 else 合并为一行
+            let closing = INDENT.repeat(self.indent).len() + "}\n".len();
+            self.output.truncate(self.output.len() - closing);
+            "} else "
+        } else {
+            "else "
+        };
+        match else_branch.as_any().downcast_ref::<If>() {
+            Some(else_if) => self.if_chain(prefix, else_if),
+            None => {
+                self.header_body(prefix.trim_end(), else_branch.as_ref());
+            }
+        }
+    }
+
+    fn function(&mut self, name: &str, function: &Function) {
+        let params = function
+            .params
+            .iter()
+            .map(|param| param.lexeme.as_str())
+            .collect::<Vec<_>>();
+        if function.body.is_empty() {
+            self.line(&format!("{}({}) {{}}", name, params.join(", ")));
+            return;
+        }
+        self.line(&format!("{}({}) {{", name, params.join(", ")));
+        self.statements(&function.body);
+        self.line("}");
+    }
+
+    // 由 for 循环脱糖得到的 While 带有自增部分，还原为 for 循环输出
+    fn for_loop(&mut self, initializer: &str, stmt: &While) {
+        let condition = self.expr(stmt.condition.as_ref());
+        let increment = match stmt.increment.as_ref() {
+            Some(increment) => self.expr(increment.as_ref()),
+            None => String::new(),
+        };
+        let initializer = if initializer.is_empty() {
+            ";".to_string()
+        } else {
+            initializer.to_string()
+        };
+        let header = format!("for ({} {}; {})", initializer, condition, increment);
+        self.header_body(header.trim_end_matches(' '), stmt.body.as_ref());
+    }
+
+    fn arguments(&mut self, arguments: &[Box<dyn Expr>]) -> String {
+        arguments
+            .iter()
+            .map(|argument| self.expr(argument.as_ref()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // 复合赋值脱糖后右操作数可能是优先级更低的二元表达式，需要补上括号
+    fn operand(&mut self, expr: &dyn Expr, operator: &TokenType) -> String {
+        let text = self.expr(expr);
+        let needs_paren = if let Some(binary) = expr.as_any().downcast_ref::<Binary>() {
+            let inner = precedence(&binary.operator.token_type);
+            let outer = precedence(operator);
+            inner < outer || (inner == outer && *operator != TokenType::StarStar)
+        } else {
+            expr.as_any().is::<Logical>()
+                || expr.as_any().is::<Ternary>()
+                || expr.as_any().is::<Assign>()
+        };
+        if needs_paren {
+            format!("({})", text)
+        } else {
+            text
+        }
+    }
+}
+
+fn precedence(token_type: &TokenType) -> u8 {
+    match token_type {
+        TokenType::EqualEqual | TokenType::BangEqual => 1,
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => 2,
+        TokenType::Plus | TokenType::Minus => 3,
+        TokenType::Star | TokenType::Slash | TokenType::Percent => 4,
+        _ => 5,
+    }
+}
+
+// 字符串中的转义字符还原为源码形式
+fn quote(value: &str) -> String {
+    let mut text = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            '\0' => text.push_str("\\0"),
+            '\\' => text.push_str("\\\\"),
+            '"' => text.push_str("\\\""),
+            _ => text.push(c),
+        }
+    }
+    text.push('"');
+    text
+}
+
+fn assign_operator(operator: &Option<Token>) -> String {
+    match operator {
+        Some(operator) => format!("{}=", operator.lexeme),
+        None => String::from("="),
+    }
+}
+
+impl ExprVisitor for Formatter {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        let value = self.expr(expr.value.as_ref());
+        self.output
+            .push_str(&format!("{} = {}", expr.name.lexeme, value));
+        Ok(LoxType::Nil)
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        let left = self.expr(expr.left.as_ref());
+        let right = self.operand(expr.right.as_ref(), &expr.operator.token_type);
+        self.output
+            .push_str(&format!("{} {} {}", left, expr.operator.lexeme, right));
+        Ok(LoxType::Nil)
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        let expression = self.expr(expr.expression.as_ref());
+        self.output.push_str(&format!("({})", expression));
+        Ok(LoxType::Nil)
+    }
+
+    fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        match &expr.value {
+            LoxType::Str(s) => self.output.push_str(&quote(s)),
+            value => self.output.push_str(&value.to_string()),
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        let left = self.expr(expr.left.as_ref());
+        let right = self.expr(expr.right.as_ref());
+        self.output
+            .push_str(&format!("{} {} {}", left, expr.operator.lexeme, right));
+        Ok(LoxType::Nil)
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        let right = self.expr(expr.right.as_ref());
+        self.output
+            .push_str(&format!("{}{}", expr.operator.lexeme, right));
+        Ok(LoxType::Nil)
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        self.output.push_str(&expr.name.lexeme);
+        Ok(LoxType::Nil)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        let callee = self.expr(expr.callee.as_ref());
+        let arguments = self.arguments(&expr.arguments);
+        self.output.push_str(&format!("{}({})", callee, arguments));
+        Ok(LoxType::Nil)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        self.output
+            .push_str(&format!("{}.{}", object, expr.name.lexeme));
+        Ok(LoxType::Nil)
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let value = self.expr(expr.value.as_ref());
+        let operator = assign_operator(&expr.operator);
+        self.output.push_str(&format!(
+            "{}.{} {} {}",
+            object, expr.name.lexeme, operator, value
+        ));
+        Ok(LoxType::Nil)
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        self.output
+            .push_str(&format!("super.{}", expr.method.lexeme));
+        Ok(LoxType::Nil)
+    }
+
+    fn this_visit(&mut self, _expr: &This) -> Result<LoxType, LoxInterrupt> {
+        self.output.push_str("this");
+        Ok(LoxType::Nil)
+    }
+
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        let condition = self.expr(expr.condition.as_ref());
+        let then_branch = self.expr(expr.then_branch.as_ref());
+        let else_branch = self.expr(expr.else_branch.as_ref());
+        self.output.push_str(&format!(
+            "{} ? {} : {}",
+            condition, then_branch, else_branch
+        ));
+        Ok(LoxType::Nil)
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        // 函数体逐行输出，结尾的花括号与所在语句对齐
+        let output = mem::take(&mut self.output);
+        self.function("fun ", &expr.function);
+        let text = mem::replace(&mut self.output, output);
+        self.output.push_str(text.trim());
+        Ok(LoxType::Nil)
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        let elements = self.arguments(&expr.elements);
+        self.output.push_str(&format!("[{}]", elements));
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let index = self.expr(expr.index.as_ref());
+        self.output.push_str(&format!("{}[{}]", object, index));
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let index = self.expr(expr.index.as_ref());
+        let value = self.expr(expr.value.as_ref());
+        let operator = assign_operator(&expr.operator);
+        self.output
+            .push_str(&format!("{}[{}] {} {}", object, index, operator, value));
+        Ok(LoxType::Nil)
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        let entries = expr
+            .keys
+            .iter()
+            .zip(expr.values.iter())
+            .map(|(key, value)| format!("{}: {}", quote(key), self.expr(value.as_ref())))
+            .collect::<Vec<_>>();
+        self.output.push_str(&format!("{{{}}}", entries.join(", ")));
+        Ok(LoxType::Nil)
+    }
+}
+
+impl StmtVisitor for Formatter {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        let keyword = if stmt.newline { "print" } else { "write" };
+        let expression = self.expr(stmt.expression.as_ref());
+        self.line(&format!("{} {};", keyword, expression));
+        Ok(None)
+    }
+
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.if_chain("", stmt);
+        Ok(None)
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        if let [initializer, body] = stmt.statements.as_slice() {
+            let is_initializer =
+                initializer.as_any().is::<Var>() || initializer.as_any().is::<Expression>();
+            if let Some(body) = body.as_any().downcast_ref::<While>()
+                && is_initializer
+                && body.increment.is_some()
+            {
+                let initializer = self.inline_stmt(initializer.as_ref());
+                self.for_loop(&initializer, body);
+                return Ok(None);
+            }
+        }
+        if stmt.statements.is_empty() {
+            self.line("{}");
+            return Ok(None);
+        }
+        self.line("{");
+        self.statements(&stmt.statements);
+        self.line("}");
+        Ok(None)
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        let expression = self.expr(stmt.expression.as_ref());
+        self.line(&format!("{};", expression));
+        Ok(None)
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.line(&format!("var {} = {};", stmt.name.lexeme, initializer));
+        Ok(None)
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut names = stmt
+            .names
+            .iter()
+            .map(|name| name.lexeme.clone())
+            .collect::<Vec<_>>();
+        if let Some(rest) = &stmt.rest {
+            names.push(format!("...{}", rest.lexeme));
+        }
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.line(&format!("var [{}] = {};", names.join(", "), initializer));
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.line(&format!("const {} = {};", stmt.name.lexeme, initializer));
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        if stmt.increment.is_some() {
+            self.for_loop("", stmt);
+            return Ok(None);
+        }
+        let condition = self.expr(stmt.condition.as_ref());
+        self.header_body(&format!("while ({})", condition), stmt.body.as_ref());
+        Ok(None)
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        let iterable = self.expr(stmt.iterable.as_ref());
+        let header = format!("for (var {} in {})", stmt.name.lexeme, iterable);
+        self.header_body(&header, stmt.body.as_ref());
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.function(&format!("fun {}", stmt.name.lexeme), stmt);
+        Ok(None)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        match stmt.value.as_ref() {
+            Some(value) => {
+                let value = self.expr(value.as_ref());
+                self.line(&format!("return {};", value));
+            }
+            None => self.line("return;"),
+        }
+        Ok(None)
+    }
+
+    fn break_visit(&mut self, _stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.line("break;");
+        Ok(None)
+    }
+
+    fn continue_visit(&mut self, _stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.line("continue;");
+        Ok(None)
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        let scrutinee = self.expr(stmt.scrutinee.as_ref());
+        self.line(&format!("switch ({}) {{", scrutinee));
+        self.indent += 1;
+        for (case, body) in stmt.cases.iter() {
+            let case = self.expr(case.as_ref());
+            self.line(&format!("case {}:", case));
+            self.statements(body);
+        }
+        if let Some(body) = &stmt.default_case {
+            self.line("default:");
+            self.statements(body);
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.line("try {");
+        self.statements(&stmt.body);
+        self.line(&format!("}} catch ({}) {{", stmt.name.lexeme));
+        self.statements(&stmt.handler);
+        self.line("}");
+        Ok(None)
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = self.expr(stmt.value.as_ref());
+        self.line(&format!("throw {};", value));
+        Ok(None)
+    }
+
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.line(&format!("import \"{}\";", stmt.path));
+        Ok(None)
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        let mut header = format!("class {}", stmt.name.lexeme);
+        if let Some(superclass) = stmt.superclass.as_ref() {
+            header.push_str(&format!(" < {}", self.expr(superclass.as_ref())));
+        }
+        if stmt.methods.is_empty() {
+            self.line(&format!("{} {{}}", header));
+            return Ok(None);
+        }
+        self.line(&format!("{} {{", header));
+        self.indent += 1;
+        for method in stmt.methods.iter() {
+            self.function(&method.name.lexeme, method);
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(None)
+    }
+}
@@ -1,42 +1,130 @@
 use crate::ast::Function;
+use crate::ast::error::LoxRuntimeError;
 use crate::ast::interpreter::Interpreter;
+use crate::class::LoxInstance;
 use crate::environment::Environment;
-use crate::log::Log;
 use crate::log_info;
-use crate::scanner::LoxType;
-use crate::scanner::token::Callable;
+use crate::scanner::token::{Callable, LoxInterrupt, LoxTailCall};
+use crate::scanner::{LoxType, Token, TokenType};
 use std::any::Any;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Formatter;
+use std::rc::Rc;
 
 pub mod native;
 
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct LoxFunction {
     declaration: Function,
+    // 函数定义时所在的环境
+    closure: Rc<RefCell<Environment>>,
+    // 作为方法被访问时绑定的实例，调用时以 this 的名义定义在闭包之上
+    this: Option<Box<LoxInstance>>,
 }
 
 impl LoxFunction {
-    pub fn new(declaration: Function) -> Self {
-        LoxFunction { declaration }
+    pub fn new(declaration: Function, closure: Rc<RefCell<Environment>>) -> Self {
+        LoxFunction {
+            declaration,
+            closure,
+            this: None,
+        }
+    }
+
+    // 匿名函数以 fun 关键字作为名称 token
+    fn name(&self) -> String {
+        match self.declaration.name.token_type {
+            TokenType::Fun => "<anonymous>".to_string(),
+            _ => self.declaration.name.lexeme.clone(),
+        }
+    }
+
+    pub fn bind(&self, instance: LoxInstance) -> LoxFunction {
+        LoxFunction {
+            declaration: self.declaration.clone(),
+            closure: self.closure.clone(),
+            this: Some(Box::new(instance)),
+        }
     }
 }
 
-impl Callable for LoxFunction {
-    fn call(
-        &mut self,
+// 闭包环境与绑定的实例中都可能包含函数自身，Debug 输出时跳过它们避免无限递归
+impl fmt::Debug for LoxFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoxFunction")
+            .field("declaration", &self.declaration)
+            .finish_non_exhaustive()
+    }
+}
+
+impl LoxFunction {
+    // 执行一次函数体，return 的值作为结果返回，尾调用以 LoxInterrupt::TailCall 的形式交给调用方处理
+    fn execute(
+        &self,
         interpreter: &mut Interpreter,
-        arguments: &Vec<Option<LoxType>>,
-    ) -> Option<LoxType> {
-        let mut environment = Environment::new_with_enclosing(interpreter.environment.clone());
-        for index in 0..self.declaration.params.len() {
-            let declaration_param = self.declaration.params.get(index).expect("param exist");
-            let argument = arguments.get(index).expect("argument exist");
-            environment.define(declaration_param.lexeme.clone(), argument.clone())
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxInterrupt> {
+        let mut enclosing = self.closure.clone();
+        if let Some(instance) = &self.this {
+            let mut this_environment = Environment::new_with_enclosing(enclosing);
+            this_environment.define("this".to_string(), LoxType::Instance(instance.clone()));
+            enclosing = Rc::new(RefCell::new(this_environment));
+        }
+        let mut environment = Environment::new_with_enclosing(enclosing);
+        for (param, argument) in self.declaration.params.iter().zip(arguments) {
+            environment.define(param.lexeme.clone(), argument.clone())
         }
         match interpreter.execute_block(&self.declaration.body, environment) {
-            Ok(_) => None,
-            Err(lox_return) => {
+            Ok(_) => Ok(LoxType::Nil),
+            Err(LoxInterrupt::Return(lox_return)) => {
                 log_info!("Function returned with value: {:?}", lox_return.value);
-                return lox_return.value;
+                Ok(lox_return.value)
+            }
+            Err(interrupt) => Err(interrupt),
+        }
+    }
+}
+
+impl Callable for LoxFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let guard = interpreter.enter_call(paren, self.name())?;
+        let mut function = self.clone();
+        let mut arguments = arguments.to_vec();
+        // 尾调用在循环中依次执行，不增加 Rust 栈的深度与调用深度
+        loop {
+            match function.execute(interpreter, &arguments) {
+                Ok(value) => return Ok(value),
+                Err(LoxInterrupt::TailCall(tail_call)) => {
+                    let LoxTailCall {
+                        mut callee,
+                        paren,
+                        arguments: next_arguments,
+                    } = *tail_call;
+                    match callee.as_any().downcast_ref::<LoxFunction>() {
+                        Some(next_function) => {
+                            function = next_function.clone();
+                            arguments = next_arguments;
+                            guard.rename(function.name());
+                        }
+                        // 原生函数与类的调用不会继续产生尾调用，直接执行即可
+                        None => return callee.call(interpreter, &paren, &next_arguments),
+                    }
+                }
+                Err(LoxInterrupt::Error(mut error)) => {
+                    // 在最内层的函数中记录调用栈，外层函数不再覆盖
+                    if error.call_stack.is_empty() {
+                        error.call_stack = interpreter.call_stack();
+                    }
+                    return Err(error);
+                }
+                // break 与 continue 在解析阶段已被限制在循环内部，不会越过函数边界
+                Err(_) => return Ok(LoxType::Nil),
             }
         }
     }
@@ -1,41 +1,699 @@
+use crate::ast::error::LoxRuntimeError;
 use crate::ast::interpreter::Interpreter;
-use crate::scanner::LoxType;
 use crate::scanner::token::Callable;
+use crate::scanner::{LoxType, Token};
 use std::any::Any;
+use std::fmt;
+use std::fmt::Formatter;
+use std::rc::Rc;
+use std::str::FromStr;
 use std::time::SystemTime;
 
+pub mod math;
+pub mod random;
+pub mod string_fns;
+
+// 原生函数的实现，参数数量在调用前已经检查过
+type NativeBody = dyn Fn(&mut Interpreter, &[LoxType]) -> LoxType;
+
+// 由 Rust 闭包包装而成的原生函数
+#[derive(Clone)]
+pub struct NativeFn {
+    name: String,
+    arity: usize,
+    func: Rc<NativeBody>,
+}
+
+impl NativeFn {
+    pub fn new<F>(name: String, arity: usize, func: F) -> Self
+    where
+        F: Fn(&mut Interpreter, &[LoxType]) -> LoxType + 'static,
+    {
+        NativeFn {
+            name,
+            arity,
+            func: Rc::new(func),
+        }
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFn")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Callable for NativeFn {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        Ok((self.func)(interpreter, arguments))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        if let Some(other_native) = other.as_any().downcast_ref::<NativeFn>() {
+            return Rc::ptr_eq(&self.func, &other_native.func);
+        }
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 返回当前的 Unix 时间戳，单位为秒
+pub fn clock(_interpreter: &mut Interpreter, _arguments: &[LoxType]) -> LoxType {
+    let current_timestamp = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Time went backwards")
+        .as_secs_f64();
+    LoxType::from(current_timestamp)
+}
+
+// 返回字符串中 Unicode 标量值的个数或列表的元素个数
+#[derive(Debug, Clone)]
+pub struct LenNativeFunction;
+
+impl Callable for LenNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Str(s) => Ok(LoxType::from(s.chars().count() as f64)),
+            LoxType::List(elements) => Ok(LoxType::from(elements.borrow().len() as f64)),
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "Argument to 'len' must be a string or a list.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<LenNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 将任意值转换为其在 Lox 中的字符串表示
+#[derive(Debug, Clone)]
+pub struct StrNativeFunction;
+
+impl Callable for StrNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        Ok(LoxType::from(arguments[0].to_string()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<StrNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 将字符串或布尔值转换为数字，true 为 1，false 为 0
+#[derive(Debug, Clone)]
+pub struct NumNativeFunction;
+
+impl Callable for NumNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Num(n) => Ok(LoxType::from(*n)),
+            LoxType::Bool(b) => Ok(LoxType::from(if *b { 1.0 } else { 0.0 })),
+            LoxType::Str(s) => match f64::from_str(s) {
+                Ok(n) => Ok(LoxType::from(n)),
+                Err(_) => {
+                    let message = format!("Can't convert '{}' to a number.", s);
+                    Err(LoxRuntimeError::new(paren.clone(), &message))
+                }
+            },
+            value => {
+                let message = format!("Can't convert '{}' to a number.", value);
+                Err(LoxRuntimeError::new(paren.clone(), &message))
+            }
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<NumNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 按照 Lox 的真值规则将任意值转换为布尔值
 #[derive(Debug, Clone)]
-pub struct ClockNativeFunction;
+pub struct BoolNativeFunction;
+
+impl Callable for BoolNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        Ok(LoxType::from(interpreter.is_truthy(&arguments[0])))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<BoolNativeFunction>()
+    }
 
-impl ClockNativeFunction {
-    pub fn new() -> Self {
-        ClockNativeFunction
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
-impl Callable for ClockNativeFunction {
+// 返回参数的类型名称，便于调试
+#[derive(Debug, Clone)]
+pub struct TypeOfNativeFunction;
+
+impl Callable for TypeOfNativeFunction {
     fn call(
         &mut self,
         _interpreter: &mut Interpreter,
-        _arguments: &Vec<Option<LoxType>>,
-    ) -> Option<LoxType> {
-        let current_timestamp = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .expect("Time went backwards")
-            .as_secs_f64();
-        Some(LoxType::new_num(current_timestamp))
+        _paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        Ok(LoxType::from(arguments[0].type_name()))
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<TypeOfNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 在列表末尾追加元素并返回追加的值，所有引用该列表的变量都能看到修改
+#[derive(Debug, Clone)]
+pub struct PushNativeFunction;
+
+impl Callable for PushNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::List(elements) => {
+                elements.borrow_mut().push(arguments[1].clone());
+                Ok(arguments[1].clone())
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'push' must be a list.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<PushNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 移除并返回列表的最后一个元素，列表为空时返回 nil
+#[derive(Debug, Clone)]
+pub struct PopNativeFunction;
+
+impl Callable for PopNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::List(elements) => Ok(elements.borrow_mut().pop().unwrap_or(LoxType::Nil)),
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "Argument to 'pop' must be a list.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<PopNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 按下标读取列表元素，与 list[index] 的检查规则一致
+#[derive(Debug, Clone)]
+pub struct ListGetNativeFunction;
+
+impl Callable for ListGetNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::List(elements) => {
+                let elements = elements.borrow();
+                let index = interpreter.list_index(paren, elements.len(), &arguments[1])?;
+                Ok(elements[index].clone())
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'list_get' must be a list.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<ListGetNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 按键读取字典中的值，与 map[key] 的检查规则一致
+#[derive(Debug, Clone)]
+pub struct MapGetNativeFunction;
+
+impl Callable for MapGetNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Map(entries) => {
+                let key = interpreter.map_key(paren, &arguments[1])?;
+                match entries.borrow().get(&key) {
+                    Some(value) => Ok(value.clone()),
+                    None => {
+                        let message = format!("Undefined key '{}'.", key);
+                        Err(LoxRuntimeError::new(paren.clone(), &message))
+                    }
+                }
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'map_get' must be a map.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<MapGetNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 设置字典中键对应的值并返回该值
+#[derive(Debug, Clone)]
+pub struct MapSetNativeFunction;
+
+impl Callable for MapSetNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Map(entries) => {
+                let key = interpreter.map_key(paren, &arguments[1])?;
+                entries.borrow_mut().insert(key, arguments[2].clone());
+                Ok(arguments[2].clone())
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'map_set' must be a map.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<MapSetNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 判断字典中是否存在指定的键
+#[derive(Debug, Clone)]
+pub struct MapHasNativeFunction;
+
+impl Callable for MapHasNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Map(entries) => {
+                let key = interpreter.map_key(paren, &arguments[1])?;
+                Ok(LoxType::from(entries.borrow().contains_key(&key)))
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'map_has' must be a map.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<MapHasNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 删除字典中的键并返回原来的值，键不存在时返回 nil
+#[derive(Debug, Clone)]
+pub struct MapDeleteNativeFunction;
+
+impl Callable for MapDeleteNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match &arguments[0] {
+            LoxType::Map(entries) => {
+                let key = interpreter.map_key(paren, &arguments[1])?;
+                Ok(entries.borrow_mut().remove(&key).unwrap_or(LoxType::Nil))
+            }
+            _ => Err(LoxRuntimeError::new(
+                paren.clone(),
+                "First argument to 'map_delete' must be a map.",
+            )),
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<MapDeleteNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 输出提示后从输入中读取一行，不包含行尾的换行符
+#[derive(Debug, Clone)]
+pub struct InputNativeFunction;
+
+impl Callable for InputNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let LoxType::Str(prompt) = &arguments[0] else {
+            let message = "Argument to 'input' must be a string.";
+            return Err(LoxRuntimeError::new(paren.clone(), message));
+        };
+        match interpreter.read_line(prompt) {
+            Ok(line) => Ok(LoxType::from(line)),
+            Err(error) => {
+                let message = format!("Failed to read input: {}.", error);
+                Err(LoxRuntimeError::new(paren.clone(), &message))
+            }
+        }
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<InputNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 以给定的退出码立即结束整个进程，不会执行之后的任何语句
+// 参数会截断为 i32，参数不是数字时以 1 退出
+#[derive(Debug, Clone)]
+pub struct ExitNativeFunction;
+
+impl Callable for ExitNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let code = match arguments[0] {
+            LoxType::Num(code) => code as i32,
+            _ => 1,
+        };
+        // 进程退出时不会再有机会写出缓冲区中的输出
+        let _ = interpreter.flush_output();
+        std::process::exit(code)
+    }
+
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<ExitNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 条件为假值时以给定的信息抛出运行时错误，否则返回 nil
+#[derive(Debug, Clone)]
+pub struct AssertNativeFunction;
+
+impl Callable for AssertNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let LoxType::Str(message) = &arguments[1] else {
+            let message = "Second argument to 'assert' must be a string.";
+            return Err(LoxRuntimeError::new(paren.clone(), message));
+        };
+        if interpreter.is_truthy(&arguments[0]) {
+            Ok(LoxType::Nil)
+        } else {
+            Err(LoxRuntimeError::new(paren.clone(), message))
+        }
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<AssertNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 与 print 语句相同，输出值并换行，便于以函数调用的形式使用
+#[derive(Debug, Clone)]
+pub struct PrintlnNativeFunction;
+
+impl Callable for PrintlnNativeFunction {
+    fn call(
+        &mut self,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        match interpreter.write_line(&arguments[0]) {
+            Ok(()) => Ok(LoxType::Nil),
+            Err(error) => {
+                let message = format!("Failed to write output: {}.", error);
+                Err(LoxRuntimeError::new(paren.clone(), &message))
+            }
+        }
     }
 
     fn arity(&self) -> usize {
-        0
+        1
     }
 
     fn clone_box(&self) -> Box<dyn Callable> {
-        Box::new(ClockNativeFunction)
+        Box::new(self.clone())
     }
 
     fn eq_callable(&self, other: &dyn Callable) -> bool {
-        other.as_any().is::<ClockNativeFunction>()
+        other.as_any().is::<PrintlnNativeFunction>()
     }
 
     fn as_any(&self) -> &dyn Any {
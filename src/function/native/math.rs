@@ -0,0 +1,79 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::interpreter::Interpreter;
+use crate::scanner::token::Callable;
+use crate::scanner::{LoxType, Token};
+use std::any::Any;
+
+// 参数全部为数字的数学函数，直接委托给 f64 的同名方法
+#[derive(Debug, Clone)]
+pub struct MathNativeFunction {
+    name: &'static str,
+    arity: usize,
+    op: fn(&[f64]) -> f64,
+}
+
+impl MathNativeFunction {
+    pub fn new(name: &'static str, arity: usize, op: fn(&[f64]) -> f64) -> Self {
+        MathNativeFunction { name, arity, op }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Callable for MathNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let mut numbers = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            let LoxType::Num(number) = argument else {
+                let message = if self.arity == 1 {
+                    format!("Argument to '{}' must be a number.", self.name)
+                } else {
+                    format!("Arguments to '{}' must be numbers.", self.name)
+                };
+                return Err(LoxRuntimeError::new(paren.clone(), &message));
+            };
+            numbers.push(*number);
+        }
+        // 定义域之外的参数按 IEEE 754 返回 NaN，不视为运行时错误
+        Ok(LoxType::from((self.op)(&numbers)))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<MathNativeFunction>()
+            .is_some_and(|other| self.name == other.name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 所有需要注册到全局环境的数学函数
+pub fn functions() -> Vec<MathNativeFunction> {
+    vec![
+        MathNativeFunction::new("sqrt", 1, |args| args[0].sqrt()),
+        MathNativeFunction::new("floor", 1, |args| args[0].floor()),
+        MathNativeFunction::new("ceil", 1, |args| args[0].ceil()),
+        MathNativeFunction::new("abs", 1, |args| args[0].abs()),
+        MathNativeFunction::new("round", 1, |args| args[0].round()),
+        MathNativeFunction::new("pow", 2, |args| args[0].powf(args[1])),
+        MathNativeFunction::new("log", 1, |args| args[0].ln()),
+    ]
+}
@@ -0,0 +1,109 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::interpreter::Interpreter;
+use crate::scanner::token::Callable;
+use crate::scanner::{LoxType, Token};
+use std::any::Any;
+use std::cell::Cell;
+use std::time::SystemTime;
+
+thread_local! {
+    // xorshift64* 的内部状态，首次使用时以当前时间作为种子，状态不能为 0
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default();
+    nanos | 1
+}
+
+// 返回 [0, 1) 之间均匀分布的浮点数，不适用于密码学场景
+fn next_f64() -> f64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        // 取高 53 位作为尾数，保证结果严格小于 1
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+// 返回 [0, 1) 之间的随机数
+#[derive(Debug, Clone)]
+pub struct RandomNativeFunction;
+
+impl Callable for RandomNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        _paren: &Token,
+        _arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        Ok(LoxType::from(next_f64()))
+    }
+
+    fn arity(&self) -> usize {
+        0
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<RandomNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// 返回 [min, max) 之间的随机数，要求 min < max
+#[derive(Debug, Clone)]
+pub struct RandomRangeNativeFunction;
+
+impl Callable for RandomRangeNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let (LoxType::Num(min), LoxType::Num(max)) = (&arguments[0], &arguments[1]) else {
+            let message = "Arguments to 'random_range' must be numbers.";
+            return Err(LoxRuntimeError::new(paren.clone(), message));
+        };
+        if min >= max {
+            let message = format!(
+                "Minimum {} must be less than maximum {} in 'random_range'.",
+                LoxType::from(*min),
+                LoxType::from(*max)
+            );
+            return Err(LoxRuntimeError::new(paren.clone(), &message));
+        }
+        // 舍入可能使结果恰好等于 max，此时退回到 min
+        let value = min + (max - min) * next_f64();
+        Ok(LoxType::from(if value < *max { value } else { *min }))
+    }
+
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other.as_any().is::<RandomRangeNativeFunction>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,125 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::interpreter::Interpreter;
+use crate::scanner::token::Callable;
+use crate::scanner::{LoxType, Token};
+use std::any::Any;
+
+// 第一个参数为字符串的函数，其余参数交给具体实现校验，校验失败时返回错误信息
+type StringOp = fn(&str, &[LoxType]) -> Result<LoxType, String>;
+
+// 字符串处理函数，长度与下标都按 Unicode 标量值计算，与 len 保持一致
+#[derive(Debug, Clone)]
+pub struct StringNativeFunction {
+    name: &'static str,
+    arity: usize,
+    op: StringOp,
+}
+
+impl StringNativeFunction {
+    pub fn new(name: &'static str, arity: usize, op: StringOp) -> Self {
+        StringNativeFunction { name, arity, op }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl Callable for StringNativeFunction {
+    fn call(
+        &mut self,
+        _interpreter: &mut Interpreter,
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError> {
+        let LoxType::Str(string) = &arguments[0] else {
+            let message = if self.arity == 1 {
+                format!("Argument to '{}' must be a string.", self.name)
+            } else {
+                format!("First argument to '{}' must be a string.", self.name)
+            };
+            return Err(LoxRuntimeError::new(paren.clone(), &message));
+        };
+        (self.op)(string, &arguments[1..])
+            .map_err(|message| LoxRuntimeError::new(paren.clone(), &message))
+    }
+
+    fn arity(&self) -> usize {
+        self.arity
+    }
+
+    fn clone_box(&self) -> Box<dyn Callable> {
+        Box::new(self.clone())
+    }
+
+    fn eq_callable(&self, other: &dyn Callable) -> bool {
+        other
+            .as_any()
+            .downcast_ref::<StringNativeFunction>()
+            .is_some_and(|other| self.name == other.name)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn pattern<'a>(name: &str, value: &'a LoxType) -> Result<&'a str, String> {
+    match value {
+        LoxType::Str(pattern) => Ok(pattern),
+        _ => Err(format!("Second argument to '{}' must be a string.", name)),
+    }
+}
+
+// 截取下标必须是非负整数
+fn index(value: &LoxType) -> Result<usize, String> {
+    match value {
+        LoxType::Num(n) if n.fract() == 0.0 && *n >= 0.0 => Ok(*n as usize),
+        _ => Err("Substring start and length must be non-negative integers.".to_string()),
+    }
+}
+
+fn substr(string: &str, arguments: &[LoxType]) -> Result<LoxType, String> {
+    let start = index(&arguments[0])?;
+    let len = index(&arguments[1])?;
+    let count = string.chars().count();
+    if start.checked_add(len).is_none_or(|end| end > count) {
+        return Err(format!(
+            "Substring range {}..{} out of bounds for length {}.",
+            start,
+            start.saturating_add(len),
+            count
+        ));
+    }
+    Ok(LoxType::from(
+        string.chars().skip(start).take(len).collect::<String>(),
+    ))
+}
+
+// 所有需要注册到全局环境的字符串函数
+pub fn functions() -> Vec<StringNativeFunction> {
+    vec![
+        StringNativeFunction::new("str_len", 1, |s, _| {
+            Ok(LoxType::from(s.chars().count() as f64))
+        }),
+        StringNativeFunction::new("str_upper", 1, |s, _| Ok(LoxType::from(s.to_uppercase()))),
+        StringNativeFunction::new("str_lower", 1, |s, _| Ok(LoxType::from(s.to_lowercase()))),
+        StringNativeFunction::new("str_trim", 1, |s, _| Ok(LoxType::from(s.trim()))),
+        StringNativeFunction::new("str_contains", 2, |s, args| {
+            Ok(LoxType::from(
+                s.contains(pattern("str_contains", &args[0])?),
+            ))
+        }),
+        StringNativeFunction::new("str_starts_with", 2, |s, args| {
+            Ok(LoxType::from(
+                s.starts_with(pattern("str_starts_with", &args[0])?),
+            ))
+        }),
+        StringNativeFunction::new("str_ends_with", 2, |s, args| {
+            Ok(LoxType::from(
+                s.ends_with(pattern("str_ends_with", &args[0])?),
+            ))
+        }),
+        StringNativeFunction::new("str_substr", 3, substr),
+    ]
+}
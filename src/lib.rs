@@ -1,63 +1,551 @@
+use crate::ast::Stmt;
 use crate::ast::interpreter::Interpreter;
+use crate::ast::printer::AstPrinter;
+use crate::lint::Linter;
+use crate::optimizer::Optimizer;
 use crate::parser::Parser;
+use crate::prompt::Prompt;
+use crate::resolver::Resolver;
+use crate::scanner::token::{KEYWORDS, LoxInterrupt};
+use crate::vm::compiler::Compiler;
+use crate::vm::disassembler::disassemble_chunk;
+use crate::vm::vm::VM;
+use std::cell::RefCell;
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::io::{BufRead, Write};
+use std::mem;
+use std::rc::Rc;
+use std::slice;
 
 mod ast;
+mod class;
 mod environment;
+mod error;
+mod formatter;
+mod function;
+mod lint;
 mod log;
+mod optimizer;
 mod parser;
 mod prompt;
+mod resolver;
 mod scanner;
-mod function;
+mod vm;
+
+pub use crate::ast::error::LoxRuntimeError;
+pub use crate::error::LoxError;
+pub use crate::lint::{LintSeverity, LintWarning};
+pub use crate::parser::error::ParseError;
+pub use crate::resolver::error::ResolveError;
+pub use crate::scanner::error::{LoxTypeError, ScanError, TypeError};
+pub use crate::scanner::{LoxType, Token, TokenType};
+pub use crate::vm::error::CompileError;
+pub use crate::vm::{Chunk, OpCode};
 
-#[derive(Debug)]
 pub struct Lox {
     inerpreter: Interpreter,
+    // 与解释器共享，print 语句与交互提示符都写入这里
+    stdout: Rc<RefCell<dyn Write>>,
+    stderr: Box<dyn Write>,
+    // 与解释器共享，交互提示符与 input 函数都从这里读取
+    stdin: Rc<RefCell<dyn BufRead>>,
+    // 交互模式下输入过的非空行
+    history: Vec<String>,
+    // 决定错误信息是否带有颜色
+    prompt: Prompt,
 }
 
 impl Lox {
     pub fn new() -> Self {
-        Lox {
-            inerpreter: Interpreter::new(),
-        }
+        LoxBuilder::new().build()
     }
 
-    fn run(&mut self, content: String) {
-        let mut scanner = scanner::Scanner::new(content);
-        let tokens = scanner.scan_tokens();
+    pub fn builder() -> LoxBuilder {
+        LoxBuilder::new()
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    // 供宿主程序在脚本运行前后读写全局变量
+    pub fn get_global(&self, name: &str) -> Option<LoxType> {
+        self.inerpreter.get_global(name)
+    }
+
+    // 补全 position 之前输入了一部分的标识符，候选为全局变量、函数与关键字，按字母顺序返回
+    // position 为字节偏移，超出源码长度时视为源码末尾
+    pub fn complete(&self, source: &str, position: usize) -> Vec<String> {
+        let Some(before) = source.get(..position.min(source.len())) else {
+            return Vec::new();
+        };
+        let prefix_len = before
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .map(char::len_utf8)
+            .sum::<usize>();
+        let prefix = &before[before.len() - prefix_len..];
+        let globals = self.inerpreter.globals.borrow();
+        let names = globals.iter().map(|(name, _)| name);
+        let mut candidates = names
+            .chain(KEYWORDS)
+            .filter(|name| name.starts_with(prefix))
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    pub fn set_global(&mut self, name: &str, value: LoxType) {
+        self.inerpreter.set_global(name, value);
+    }
+
+    // 清空全局环境，之后运行的脚本看不到之前定义的变量
+    pub fn reset(&mut self) {
+        self.inerpreter.reset();
+    }
+
+    pub fn set_max_call_depth(&mut self, limit: usize) {
+        self.inerpreter.set_max_call_depth(limit);
+    }
+
+    // 执行一段源码，任一阶段出错时停止执行并返回该阶段收集到的全部错误
+    pub fn run(&mut self, content: String) -> Result<(), Vec<LoxError>> {
+        self.run_source(content, None)
+    }
+
+    // file_path 会记录在每个 token 上，用于在错误信息中指出出错的文件
+    fn run_source(
+        &mut self,
+        content: String,
+        file_path: Option<&str>,
+    ) -> Result<(), Vec<LoxError>> {
+        let statements = Self::parse_and_resolve(content, file_path, &mut self.inerpreter)?;
+        self.inerpreter
+            .interpret(&statements)
+            .map_err(|error| vec![LoxError::Runtime(error)])
+    }
+
+    // 编译为字节码后在虚拟机中执行，目前只支持表达式、print、块与变量
+    pub fn run_bytecode(&mut self, source: String) -> Result<(), Vec<LoxError>> {
+        VM::interpret(&source, self.stdout.clone()).map_err(|error| vec![error])
+    }
+
+    // 只扫描、解析并检查变量作用域，不执行程序，便于在运行之前验证脚本
+    pub fn check(source: &str) -> Result<(), Vec<LoxError>> {
+        Self::parse_and_resolve(source.to_string(), None, &mut Interpreter::new()).map(|_| ())
+    }
+
+    fn parse_and_resolve(
+        content: String,
+        file_path: Option<&str>,
+        interpreter: &mut Interpreter,
+    ) -> Result<Vec<Box<dyn Stmt>>, Vec<LoxError>> {
+        let tokens = Self::scan(content, file_path)?;
         for token in tokens.iter() {
             log_info!("{:?}", token);
         }
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| errors.into_iter().map(LoxError::Parse).collect::<Vec<_>>())?;
+        let statements = Optimizer::optimize(&statements);
+        let mut resolver = Resolver::new(interpreter);
+        resolver.resolve(&statements);
+        if !resolver.errors().is_empty() {
+            return Err(resolver
+                .errors()
+                .iter()
+                .cloned()
+                .map(LoxError::Resolve)
+                .collect());
+        }
+        Ok(statements)
+    }
+
+    // 对单个表达式求值并返回结果，表达式之后不能有多余的内容
+    pub fn eval_expr(&mut self, source: &str) -> Result<LoxType, LoxError> {
+        let mut scanner = scanner::Scanner::new(source.to_string(), None);
+        let tokens = scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(LoxError::Scan(error.clone()));
+        }
         let mut parser = Parser::new(tokens);
-        let statements = parser.parse();
-        self.inerpreter.interpret(&statements);
+        let expr = parser.expression().map_err(LoxError::Parse)?;
+        if !parser.is_at_end() {
+            let message = "Expect end of expression.";
+            return Err(LoxError::Parse(ParseError::new(parser.peek(), message)));
+        }
+        let mut resolver = Resolver::new(&mut self.inerpreter);
+        resolver.resolve_expr(expr.as_ref());
+        if let Some(error) = resolver.errors().first() {
+            return Err(LoxError::Resolve(error.clone()));
+        }
+        match self.inerpreter.evaluate(expr.as_ref()) {
+            Ok(value) => Ok(value),
+            Err(LoxInterrupt::Error(error)) => Err(LoxError::Runtime(error)),
+            // 表达式中不包含语句，return / break / continue 不会传递到这里
+            Err(interrupt) => unreachable!("Unexpected interrupt: {:?}", interrupt),
+        }
+    }
+
+    // 将源码格式化为统一风格，注释不会保留在输出中
+    pub fn format(source: &str) -> Result<String, LoxError> {
+        let mut scanner = scanner::Scanner::new(source.to_string(), None);
+        let tokens = scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(LoxError::Scan(error.clone()));
+        }
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|mut errors| LoxError::Parse(errors.remove(0)))?;
+        Ok(formatter::Formatter::format(&statements))
+    }
+
+    // 不执行程序，对源码做静态检查，结果按行号排序
+    // 扫描、解析与变量解析阶段的错误同样作为 Error 级别的结果返回
+    pub fn lint(source: &str) -> Vec<LintWarning> {
+        let lint_error = |line, col, message: &str| {
+            LintWarning::new(line, col + 1, message, LintSeverity::Error)
+        };
+        let tokens = match Self::scan(source.to_string(), None) {
+            Ok(tokens) => tokens,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .filter_map(|error| match error {
+                        LoxError::Scan(e) => Some(lint_error(e.line, e.column, &e.message)),
+                        _ => None,
+                    })
+                    .collect();
+            }
+        };
+        let statements = match Parser::new(tokens).parse() {
+            Ok(statements) => statements,
+            Err(errors) => {
+                return errors
+                    .iter()
+                    .map(|e| lint_error(e.line(), e.column(), &e.message))
+                    .collect();
+            }
+        };
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(&statements);
+        let mut warnings = resolver
+            .errors()
+            .iter()
+            .map(|e| lint_error(e.token.line, e.token.col_start, &e.message))
+            .collect::<Vec<_>>();
+        warnings.extend(Linter::lint(&statements));
+        warnings.extend(Optimizer::optimize_with_warnings(&statements).1);
+        warnings.sort_by_key(|warning| (warning.line, warning.col));
+        warnings
+    }
+
+    // 扫描源码并逐行输出每个 token，不进行解析与执行
+    pub fn dump_tokens(source: &str) -> Result<String, Vec<LoxError>> {
+        let tokens = Self::scan(source.to_string(), None)?;
+        Ok(tokens
+            .iter()
+            .map(|token| format!("{}\n", token.format_debug()))
+            .collect())
+    }
+
+    // 解析源码并以 S 表达式的形式逐条输出语句，不进行执行
+    pub fn dump_ast(source: &str) -> Result<String, Vec<LoxError>> {
+        let tokens = Self::scan(source.to_string(), None)?;
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| errors.into_iter().map(LoxError::Parse).collect::<Vec<_>>())?;
+        Ok(statements
+            .iter()
+            .map(|stmt| format!("{}\n", AstPrinter::print_stmt(stmt.as_ref())))
+            .collect())
+    }
+
+    // 将源码编译为字节码并输出反汇编结果，不进行执行
+    pub fn dump_bytecode(source: &str) -> Result<String, Vec<LoxError>> {
+        let tokens = Self::scan(source.to_string(), None)?;
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|errors| errors.into_iter().map(LoxError::Parse).collect::<Vec<_>>())?;
+        let chunk = Compiler::compile(&statements).map_err(|error| vec![error])?;
+        Ok(disassemble_chunk(&chunk, "<script>"))
     }
 
-    fn error(line: usize, message: &str) {
-        eprintln!("[line {}] Error: {}", line, message);
+    fn scan(source: String, file_path: Option<&str>) -> Result<Vec<Token>, Vec<LoxError>> {
+        let mut scanner = scanner::Scanner::new(source, file_path);
+        let tokens = scanner.scan_tokens();
+        if scanner.has_errors() {
+            return Err(scanner
+                .errors()
+                .iter()
+                .cloned()
+                .map(LoxError::Scan)
+                .collect());
+        }
+        Ok(tokens)
+    }
+
+    fn report(&mut self, source: &str, errors: &[LoxError]) -> io::Result<()> {
+        for error in errors {
+            let output = self.stderr.as_mut();
+            match error {
+                LoxError::Scan(error) => self.prompt.error_by_line(
+                    output,
+                    error.line,
+                    Prompt::source_line(source, error.line),
+                    error.column,
+                    &error.message,
+                )?,
+                LoxError::Parse(error) => self.prompt.error_with_underline(
+                    output,
+                    error.line(),
+                    Prompt::source_line(source, error.line()),
+                    error.column(),
+                    error.column_end(),
+                    &error.message,
+                )?,
+                LoxError::Resolve(error) => {
+                    self.prompt
+                        .error(output, source, &error.token, &error.message)?
+                }
+                LoxError::Runtime(error) => {
+                    self.prompt
+                        .error(output, source, &error.token, &error.message)?;
+                    if !error.call_stack.is_empty() {
+                        self.prompt.call_stack(output, &error.call_stack)?;
+                    }
+                }
+                LoxError::Compile(error) => self.prompt.error_by_line(
+                    output,
+                    error.line,
+                    Prompt::source_line(source, error.line),
+                    error.column,
+                    &error.message,
+                )?,
+                LoxError::Io(error) => writeln!(output, "Error: {}", error)?,
+                LoxError::File { path, error } => {
+                    // 错误来自另一个文件，重新读取该文件以显示出错的源码行
+                    let source = std::fs::read_to_string(path).unwrap_or_default();
+                    self.report(&source, slice::from_ref(error))?
+                }
+            }
+        }
+        let scan_errors = errors
+            .iter()
+            .filter(|error| matches!(error, LoxError::Scan(_)))
+            .count();
+        if scan_errors > 0 {
+            writeln!(self.stderr, "Scanner found {} error(s).", scan_errors)?;
+        }
+        Ok(())
     }
 
     pub fn run_file(&mut self, path: &str) {
         let file_content_string = std::fs::read_to_string(path).expect("Reader File Error");
-        self.run(file_content_string);
+        if let Err(errors) = self.run_source(file_content_string.clone(), Some(path)) {
+            self.report(&file_content_string, &errors)
+                .expect("Failed to write errors");
+        }
+    }
+
+    // 按顺序在同一个解释器中执行多个文件，前面文件中的定义在后面的文件中可见
+    // 某个文件出错后继续执行其余文件，返回的每个错误都标注了所在的文件
+    pub fn run_files(&mut self, paths: &[&str]) -> Result<(), Vec<LoxError>> {
+        let mut errors = Vec::new();
+        for &path in paths {
+            let result = match std::fs::read_to_string(path) {
+                Ok(source) => self.run_source(source, Some(path)),
+                Err(error) => Err(vec![LoxError::Io(error)]),
+            };
+            if let Err(file_errors) = result {
+                errors.extend(file_errors.into_iter().map(|error| LoxError::File {
+                    path: path.to_string(),
+                    error: Box::new(error),
+                }));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 
+    // 行尾为 \\ 或括号尚未闭合时继续读取下一行，拼接后作为一个整体执行
     pub fn run_prompt(&mut self) {
-        let stdin = std::io::stdin();
+        let mut buffer = String::new();
         loop {
-            print!("> ");
-            std::io::Write::flush(&mut std::io::stdout()).unwrap();
+            {
+                let mut stdout = self.stdout.borrow_mut();
+                let prompt = if buffer.is_empty() { "> " } else { "… " };
+                write!(stdout, "{}", prompt).unwrap();
+                stdout.flush().unwrap();
+            }
             let mut line = String::new();
-            match stdin.read_line(&mut line) {
+            // 读取完成后立即释放借用，执行代码时 input 函数还需要读取
+            let result = self.stdin.borrow_mut().read_line(&mut line);
+            match result {
                 Ok(0) => break, // EOF
                 Ok(_) => {
-                    self.run(line);
+                    let line = line.trim_end();
+                    if let Some(line) = line.strip_suffix('\\') {
+                        buffer.push_str(line);
+                        buffer.push('\n');
+                        continue;
+                    }
+                    // 续行时输入空行，直接执行已输入的内容
+                    if line.is_empty() && !buffer.is_empty() {
+                        let input = mem::take(&mut buffer);
+                        self.run_prompt_line(input.trim());
+                        continue;
+                    }
+                    buffer.push_str(line);
+                    if Prompt::bracket_depth(&buffer) > 0 {
+                        buffer.push('\n');
+                        continue;
+                    }
+                    let input = mem::take(&mut buffer);
+                    self.run_prompt_line(input.trim());
                 }
                 Err(error) => {
-                    eprintln!("Error reading line: {}", error);
+                    writeln!(self.stderr, "Error reading line: {}", error).unwrap();
                     break;
                 }
             }
         }
+        if !buffer.is_empty() {
+            self.run_prompt_line(buffer.trim());
+        }
+    }
+
+    // 除了 Lox 代码外，还支持 .history 列出历史记录，.vars 列出已定义的变量，以及 !n 重新执行第 n 条记录
+    fn run_prompt_line(&mut self, line: &str) {
+        if line.is_empty() {
+            return;
+        }
+        if line == ".history" {
+            let mut stdout = self.stdout.borrow_mut();
+            for (index, entry) in self.history.iter().enumerate() {
+                writeln!(stdout, "{:>4}  {}", index + 1, entry).unwrap();
+            }
+            return;
+        }
+        if line == ".vars" {
+            let mut values = self
+                .inerpreter
+                .environment
+                .borrow()
+                .all_iter()
+                .collect::<Vec<_>>();
+            values.sort_by(|(left, _), (right, _)| left.cmp(right));
+            let mut stdout = self.stdout.borrow_mut();
+            for (name, value) in values {
+                writeln!(stdout, "{} = {}", name, value).unwrap();
+            }
+            return;
+        }
+        let line = match line.strip_prefix('!').and_then(|n| n.parse::<usize>().ok()) {
+            Some(index) => match index.checked_sub(1).and_then(|i| self.history.get(i)) {
+                Some(entry) => entry.clone(),
+                None => {
+                    writeln!(self.stderr, "No history entry {}.", index).unwrap();
+                    return;
+                }
+            },
+            None => line.to_string(),
+        };
+        self.history.push(line.clone());
+        if let Err(errors) = self.run(line.clone()) {
+            self.report(&line, &errors).expect("Failed to write errors");
+        }
+    }
+}
+
+// 输出目标没有实现 Debug，输出时跳过
+impl fmt::Debug for Lox {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lox")
+            .field("inerpreter", &self.inerpreter)
+            .field("history", &self.history)
+            .field("prompt", &self.prompt)
+            .finish_non_exhaustive()
+    }
+}
+
+// 用于配置 Lox 的输入输出，未配置时使用标准输入、标准输出与标准错误
+pub struct LoxBuilder {
+    stdout: Rc<RefCell<dyn Write>>,
+    stderr: Box<dyn Write>,
+    stdin: Rc<RefCell<dyn BufRead>>,
+    colors: Option<bool>,
+    // 替换标准错误后默认不再输出颜色
+    stderr_is_default: bool,
+}
+
+impl LoxBuilder {
+    pub fn new() -> Self {
+        LoxBuilder {
+            stdout: Rc::new(RefCell::new(io::stdout())),
+            stderr: Box::new(io::stderr()),
+            stdin: Rc::new(RefCell::new(io::BufReader::new(io::stdin()))),
+            colors: None,
+            stderr_is_default: true,
+        }
+    }
+
+    pub fn stdout(mut self, stdout: impl Write + 'static) -> Self {
+        self.stdout = Rc::new(RefCell::new(stdout));
+        self
+    }
+
+    pub fn stderr(mut self, stderr: impl Write + 'static) -> Self {
+        self.stderr = Box::new(stderr);
+        self.stderr_is_default = false;
+        self
+    }
+
+    // 错误信息是否使用 ANSI 颜色，未配置时仅在标准错误为终端且没有设置 NO_COLOR 时启用
+    pub fn colors(mut self, colors: bool) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    // 交互模式与 input 函数读取输入的来源
+    pub fn stdin(mut self, stdin: impl BufRead + 'static) -> Self {
+        self.stdin = Rc::new(RefCell::new(stdin));
+        self
+    }
+
+    pub fn build(self) -> Lox {
+        Lox {
+            inerpreter: Interpreter::new_with_io(self.stdout.clone(), self.stdin.clone()),
+            stdout: self.stdout,
+            stderr: self.stderr,
+            stdin: self.stdin,
+            history: Vec::new(),
+            prompt: Prompt::with_colors(
+                self.colors
+                    .unwrap_or(self.stderr_is_default && Prompt::colors_supported()),
+            ),
+        }
+    }
+}
+
+impl Default for LoxBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -65,9 +553,600 @@ impl Lox {
 mod test {
     use super::*;
 
+    // 与测试代码共享底层缓冲区，便于在 Lox 运行后检查写入的内容
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.borrow().clone()).unwrap()
+        }
+    }
+
     #[test]
     fn test_loxr() {
         let mut lox = Lox::new();
         lox.run_file("lox/main.lox");
     }
+
+    #[test]
+    fn test_run_returns_errors() {
+        let mut lox = Lox::new();
+        assert!(lox.run("var a = 1;".to_string()).is_ok());
+
+        let errors = lox.run("var a = @; var b = #;".to_string()).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], LoxError::Scan(_)));
+
+        let errors = lox.run("print 1 +; print (2;".to_string()).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], LoxError::Parse(_)));
+
+        let errors = lox.run("{ var b = b; }".to_string()).unwrap_err();
+        assert!(matches!(errors[0], LoxError::Resolve(_)));
+
+        let errors = lox.run("print 1; return;".to_string()).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Can't return from top-level code.");
+
+        let errors = lox.run("print -\"a\";".to_string()).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], LoxError::Runtime(_)));
+        assert_eq!(errors[0].to_string(), "Operand must be a number.");
+    }
+
+    #[test]
+    fn test_builder_writers() {
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build();
+        lox.run("print \"hello\";".to_string()).unwrap();
+        assert_eq!(stdout.0.borrow().as_slice(), b"hello\n");
+        assert_eq!(stderr.contents(), "");
+
+        let errors = lox.run("print -nil;".to_string()).unwrap_err();
+        lox.report("print -nil;", &errors).unwrap();
+        assert!(stderr.contents().contains("Operand must be a number."));
+        assert_eq!(stdout.contents(), "hello\n");
+    }
+
+    #[test]
+    fn test_run_files_errors() {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("lox-rs-first-{}.lox", std::process::id()));
+        let second = dir.join(format!("lox-rs-second-{}.lox", std::process::id()));
+        std::fs::write(&first, "var a = 1;\nprint -nil;").unwrap();
+        std::fs::write(&second, "print a;").unwrap();
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .build();
+        let (first, second) = (first.to_str().unwrap(), second.to_str().unwrap());
+        let errors = lox.run_files(&[first, "missing.lox", second]).unwrap_err();
+        // 出错之后的文件仍然执行
+        assert_eq!(stdout.contents(), "1\n");
+        assert_eq!(errors.len(), 2);
+        let LoxError::File { path, error } = &errors[0] else {
+            panic!("expected a file error");
+        };
+        assert_eq!(path, first);
+        assert!(matches!(error.as_ref(), LoxError::Runtime(_)));
+        assert!(matches!(&errors[1], LoxError::File { path, error }
+            if path == "missing.lox" && matches!(error.as_ref(), LoxError::Io(_))));
+        lox.report("", &errors).unwrap();
+        std::fs::remove_file(first).unwrap();
+        std::fs::remove_file(second).unwrap();
+        assert!(stderr.contents().contains("print -nil;"));
+        assert!(stderr.contents().contains("Operand must be a number."));
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = std::env::temp_dir().join(format!("lox-rs-import-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        std::fs::write(
+            dir.join("lib/math.lox"),
+            "import \"util.lox\";
+            fun add(a, b) { return a + b; }
+            fun twice(a) { return add(a, a); }
+            var loaded = twice(2);
+            fun quadruple(a) { return double(double(a)); }",
+        )
+        .unwrap();
+        std::fs::write(dir.join("lib/util.lox"), "fun double(a) { return a * 2; }").unwrap();
+        std::fs::write(
+            dir.join("main.lox"),
+            "import \"lib/math.lox\";\nprint add(1, 2);\nprint loaded;\nprint quadruple(1);",
+        )
+        .unwrap();
+        std::fs::write(dir.join("a.lox"), "import \"b.lox\";").unwrap();
+        std::fs::write(dir.join("b.lox"), "import \"a.lox\";").unwrap();
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        let main = dir.join("main.lox");
+        let result = lox.run_files(&[main.to_str().unwrap()]);
+        let a = dir.join("a.lox");
+        let errors = lox.run_files(&[a.to_str().unwrap()]).unwrap_err();
+        let missing = lox.run("import \"missing.lox\";".to_string()).unwrap_err();
+        // import 定义的是全局变量，不能出现在块或函数中
+        let nested = dir.join("nested.lox");
+        std::fs::write(
+            &nested,
+            "var x = \"global\"; { import \"lib/util.lox\"; print x; }",
+        )
+        .unwrap();
+        let nested = lox.run_files(&[nested.to_str().unwrap()]).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert_eq!(stdout.contents(), "3\n4\n4\n");
+        assert!(
+            nested[0]
+                .to_string()
+                .contains("Can't import outside of top-level code.")
+        );
+        let LoxError::File { error, .. } = &errors[0] else {
+            panic!("expected a file error");
+        };
+        assert!(error.to_string().contains("Circular import of 'a.lox'."));
+        assert!(
+            missing[0]
+                .to_string()
+                .contains("Failed to import 'missing.lox'")
+        );
+    }
+
+    #[test]
+    fn test_eval_expr() {
+        let mut lox = Lox::new();
+        assert_eq!(lox.eval_expr("1 + 2").unwrap(), LoxType::new_num(3.0));
+        assert_eq!(
+            lox.eval_expr("\"hello\"").unwrap(),
+            LoxType::new_str("hello")
+        );
+        lox.run("var a = 10;".to_string()).unwrap();
+        assert_eq!(lox.eval_expr("a * 2").unwrap(), LoxType::new_num(20.0));
+
+        assert!(matches!(lox.eval_expr("1 +"), Err(LoxError::Parse(_))));
+        assert!(matches!(lox.eval_expr("1 2"), Err(LoxError::Parse(_))));
+        assert!(matches!(lox.eval_expr("-\"a\""), Err(LoxError::Runtime(_))));
+    }
+
+    #[test]
+    fn test_print_newline() {
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        lox.run("print \"hello\"; print \"world\";".to_string())
+            .unwrap();
+        assert_eq!(stdout.contents(), "hello\nworld\n");
+
+        lox.run("write 1; write 2; print 3;".to_string()).unwrap();
+        assert_eq!(stdout.contents(), "hello\nworld\n123\n");
+
+        // println 函数与 print 语句一样总是换行
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        lox.run("println(\"hello\"); write 1; println(\"world\");".to_string())
+            .unwrap();
+        assert_eq!(stdout.contents(), "hello\n1world\n");
+    }
+
+    #[test]
+    fn test_set_max_call_depth() {
+        let mut lox = Lox::new();
+        lox.set_max_call_depth(10);
+        let source = "fun f(n) { if (n > 0) f(n - 1); }";
+        lox.run(format!("{} f(9);", source)).unwrap();
+        let errors = lox.run(format!("{} f(10);", source)).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Stack overflow: maximum call depth of 10 exceeded."
+        );
+        // 出错后调用深度应当恢复，后续调用不受影响
+        lox.run("f(5);".to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_format() {
+        let source = "var   a=1;fun add(x,y){return x+y;}\n\
+            if(a>0){print add(a,2);}else if (a<0) print \"neg\\n\"; else{write nil;}\n\
+            for(var i=0;i<3;i=i+1){a-=i-1;}\n\
+            class B<A{init(){this.x=[1,2][0];} get(){return fun(k){return {\"k\":k};};}}\n\
+            for x in [1] print x;";
+        let formatted = Lox::format(source).unwrap();
+        assert_eq!(
+            formatted,
+            "var a = 1;\n\
+            fun add(x, y) {\n  return x + y;\n}\n\
+            if (a > 0) {\n  print add(a, 2);\n} else if (a < 0)\n  print \"neg\\n\";\nelse {\n  write nil;\n}\n\
+            for (var i = 0; i < 3; i = i + 1) {\n  a = a - (i - 1);\n}\n\
+            class B < A {\n  init() {\n    this.x = [1, 2][0];\n  }\n  \
+            get() {\n    return fun (k) {\n      return {\"k\": k};\n    };\n  }\n}\n\
+            for (var x in [1])\n  print x;\n"
+        );
+        assert_eq!(Lox::format(&formatted).unwrap(), formatted);
+
+        assert!(matches!(Lox::format("print ;"), Err(LoxError::Parse(_))));
+
+        let formatted = Lox::format("o.x+=1+2;l[0]*=2;").unwrap();
+        assert_eq!(formatted, "o.x += 1 + 2;\nl[0] *= 2;\n");
+
+        let formatted = Lox::format("var [a,b,...rest]=[1];").unwrap();
+        assert_eq!(formatted, "var [a, b, ...rest] = [1];\n");
+
+        let formatted = Lox::format("switch(x){case 1:print 1;print 2; default:{}}").unwrap();
+        assert_eq!(
+            formatted,
+            "switch (x) {\n  case 1:\n    print 1;\n    print 2;\n  default:\n    {}\n}\n"
+        );
+        assert_eq!(Lox::format(&formatted).unwrap(), formatted);
+
+        let formatted = Lox::format("try{throw 1;}catch(e){print e;}").unwrap();
+        assert_eq!(
+            formatted,
+            "try {\n  throw 1;\n} catch (e) {\n  print e;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_prompt_history() {
+        let stdout = SharedBuffer::default();
+        let stderr = SharedBuffer::default();
+        let stdin = io::Cursor::new("var a = 1;\n\n  a = a + 1;  \nprint a;\n!2\n!9\n.history\n");
+        let mut lox = Lox::builder()
+            .stdout(stdout.clone())
+            .stderr(stderr.clone())
+            .stdin(stdin)
+            .build();
+        lox.run_prompt();
+        assert_eq!(
+            lox.history(),
+            ["var a = 1;", "a = a + 1;", "print a;", "a = a + 1;"]
+        );
+        assert_eq!(lox.eval_expr("a").unwrap(), LoxType::new_num(3.0));
+        assert_eq!(stderr.contents(), "No history entry 9.\n");
+        assert!(stdout.contents().contains("   1  var a = 1;\n"));
+        assert!(stdout.contents().contains("   4  a = a + 1;\n"));
+    }
+
+    #[test]
+    fn test_prompt_multiline() {
+        let stdout = SharedBuffer::default();
+        let stdin = io::Cursor::new(
+            "var i = 0;\nwhile (i < 3) {\n  write i;\n  i = i + 1;\n}\nprint 1 + \\\n2;\nprint (1\n\nprint 4;\n",
+        );
+        let mut lox = Lox::builder()
+            .stdout(stdout.clone())
+            .stderr(SharedBuffer::default())
+            .stdin(stdin)
+            .build();
+        lox.run_prompt();
+        assert_eq!(
+            lox.history(),
+            [
+                "var i = 0;",
+                "while (i < 3) {\n  write i;\n  i = i + 1;\n}",
+                "print 1 + \n2;",
+                "print (1",
+                "print 4;"
+            ]
+        );
+        assert_eq!(stdout.contents(), "> > … … … 012> … 3\n> … > 4\n> ");
+    }
+
+    #[test]
+    fn test_report_scan_error_count() {
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let source = "var a = @;\nvar b = #;";
+        let errors = lox.run(source.to_string()).unwrap_err();
+        lox.report(source, &errors).unwrap();
+        assert!(stderr.contents().ends_with("Scanner found 2 error(s).\n"));
+    }
+
+    #[test]
+    fn test_prompt_vars() {
+        let stdout = SharedBuffer::default();
+        let stdin = io::Cursor::new("var b = \"x\";\nvar a = [1];\n.vars\n");
+        let mut lox = Lox::builder().stdout(stdout.clone()).stdin(stdin).build();
+        lox.run_prompt();
+        // 按名称排序输出，原生函数也包含在内
+        let contents = stdout.contents();
+        let a = contents.find("a = [1]\n").unwrap();
+        let b = contents.find("b = x\n").unwrap();
+        assert!(a < b);
+        assert!(contents.contains("bool = <function>\n"));
+        assert_eq!(lox.history(), ["var b = \"x\";", "var a = [1];"]);
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut lox = Lox::new();
+        assert_eq!(lox.get_global("result"), None);
+        lox.set_global("input_value", LoxType::new_num(20.0));
+        lox.run("var result = input_value * 2 + 2; { var local = 1; }".to_string())
+            .unwrap();
+        assert_eq!(lox.get_global("result"), Some(LoxType::new_num(42.0)));
+        assert_eq!(lox.get_global("local"), None);
+
+        lox.set_global("input_value", LoxType::new_str("replaced"));
+        lox.run("result = input_value;".to_string()).unwrap();
+        assert_eq!(lox.get_global("result"), Some(LoxType::new_str("replaced")));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut lox = Lox::new();
+        lox.run("var a = 1;".to_string()).unwrap();
+        lox.reset();
+        let errors = lox.run("print a;".to_string()).unwrap_err();
+        assert_eq!(errors[0].to_string(), "Undefined variable 'a'.");
+        lox.run("print len(\"abc\");".to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_native_input() {
+        let stdout = SharedBuffer::default();
+        let stdin = io::Cursor::new("var name = input(\"name? \");\nAda\nprint \"hi \" + name;\n");
+        let mut lox = Lox::builder().stdout(stdout.clone()).stdin(stdin).build();
+        lox.run_prompt();
+        assert_eq!(stdout.contents(), "> name? > hi Ada\n> ");
+
+        // 输入结束后返回空字符串
+        let mut lox = Lox::builder()
+            .stdout(SharedBuffer::default())
+            .stdin(io::Cursor::new("last"))
+            .build();
+        lox.run("var a = input(\"\"); var b = input(\"\");".to_string())
+            .unwrap();
+        assert_eq!(lox.get_global("a"), Some(LoxType::new_str("last")));
+        assert_eq!(lox.get_global("b"), Some(LoxType::new_str("")));
+
+        let errors = lox.run("input(1);".to_string()).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "Argument to 'input' must be a string."
+        );
+    }
+
+    #[test]
+    fn test_report_colors() {
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).colors(true).build();
+        let source = "print @;";
+        let errors = lox.run(source.to_string()).unwrap_err();
+        lox.report(source, &errors).unwrap();
+        assert!(stderr.contents().contains("\x1b[31m^\x1b[0m"));
+
+        // 替换标准错误后默认不输出颜色
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let errors = lox.run(source.to_string()).unwrap_err();
+        lox.report(source, &errors).unwrap();
+        assert!(!stderr.contents().contains('\x1b'));
+    }
+
+    #[test]
+    fn test_report_call_stack() {
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let source = "fun a() { b(); }\nfun b() { c(); }\nfun c() { nil + 1; }\na();";
+        let errors = lox.run(source.to_string()).unwrap_err();
+        let LoxError::Runtime(error) = &errors[0] else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(error.call_stack, ["<script>", "a", "b", "c"]);
+        lox.report(source, &errors).unwrap();
+        assert!(stderr.contents().ends_with(
+            "Call stack (most recent call first):\n  in c\n  in b\n  in a\n  in <script>\n"
+        ));
+
+        // 顶层代码中的错误不输出调用栈
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let errors = lox.run("nil + 1;".to_string()).unwrap_err();
+        lox.report("nil + 1;", &errors).unwrap();
+        assert!(!stderr.contents().contains("Call stack"));
+    }
+
+    #[test]
+    fn test_reference_semantics() {
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        lox.run("var a = [1, 2]; var b = a; b[0] = 99; print a[0];".to_string())
+            .unwrap();
+        lox.run(
+            "fun set(m) { m[\"k\"] = 1; }
+            var m = {}; set(m); print m[\"k\"];"
+                .to_string(),
+        )
+        .unwrap();
+        // 数字、字符串与布尔值按值复制
+        lox.run(
+            "var n = 1; var n2 = n; n2 = 2; print n;
+            var s = \"a\"; var s2 = s; s2 = s2 + \"b\"; print s;
+            var t = true; var t2 = t; t2 = false; print t;"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(stdout.contents(), "99\n1\n1\na\ntrue\n");
+
+        // 包含自身的列表可以输出与比较，不会无限递归
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        lox.run("var a = [1]; push(a, a); print a; print a == a; print [1] == [1];".to_string())
+            .unwrap();
+        assert_eq!(stdout.contents(), "[1, [...]]\ntrue\nfalse\n");
+    }
+
+    #[test]
+    fn test_try_catch() {
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        lox.run("try { throw \"oops\"; } catch (e) { print e; }".to_string())
+            .unwrap();
+        assert_eq!(stdout.contents(), "oops\n");
+
+        // try 中 return 的调用在 catch 的范围内执行
+        lox.run(
+            "fun g() { throw \"x\"; }
+            fun f() { try { return g(); } catch (e) { return \"caught \" + e; } }
+            print f();
+            fun h() { try { return len(\"ab\"); } catch (e) { return e; } }
+            print h();"
+                .to_string(),
+        )
+        .unwrap();
+        assert_eq!(stdout.contents(), "oops\ncaught x\n2\n");
+
+        // 没有被捕获的值作为运行时错误报告
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let source = "print 1;\nthrow \"uncaught\";";
+        let errors = lox.run(source.to_string()).unwrap_err();
+        let LoxError::Runtime(error) = &errors[0] else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(error.value, Some(LoxType::new_str("uncaught")));
+        assert_eq!(error.token.line, 2);
+        lox.report(source, &errors).unwrap();
+        assert!(stderr.contents().contains("uncaught"));
+    }
+
+    #[test]
+    fn test_run_folds_constants() {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let mut lox = Lox::builder().stdout(SharedBuffer::default()).build();
+        let steps = Rc::clone(&printed);
+        lox.inerpreter
+            .set_step_callback(move |stmt| steps.borrow_mut().push(AstPrinter::print_stmt(stmt)));
+        lox.run("print (1 + 2) * (3 - 1);".to_string()).unwrap();
+        assert_eq!(*printed.borrow(), ["(print 6)"]);
+
+        // 被导入的文件同样经过优化
+        let path = std::env::temp_dir().join(format!("lox-rs-fold-{}.lox", std::process::id()));
+        std::fs::write(&path, "print 2 * 3;").unwrap();
+        printed.borrow_mut().clear();
+        let source = format!("import \"{}\";", path.display());
+        let result = lox.run(source.clone());
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert_eq!(
+            *printed.borrow(),
+            [
+                format!("(import \"{}\")", path.display()),
+                String::from("(print 6)")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_bytecode() {
+        let stdout = SharedBuffer::default();
+        let mut lox = Lox::builder().stdout(stdout.clone()).build();
+        let source = "var a = 1;\na = a * 10 + 2;\nprint a;\nprint \"s\" + \"t\";";
+        lox.run_bytecode(source.to_string()).unwrap();
+        lox.run(source.to_string()).unwrap();
+        assert_eq!(stdout.contents(), "12\nst\n12\nst\n");
+
+        let stderr = SharedBuffer::default();
+        let mut lox = Lox::builder().stderr(stderr.clone()).build();
+        let errors = lox.run_bytecode("print nil + 1;".to_string()).unwrap_err();
+        lox.report("print nil + 1;", &errors).unwrap();
+        assert!(
+            stderr
+                .contents()
+                .contains("Error: Operand must not be nil.")
+        );
+    }
+
+    #[test]
+    fn test_lint_dead_code() {
+        let warnings = Lox::lint("fun f() {\n  return 1;\n  print 2;\n}\nprint f();");
+        assert_eq!(
+            warnings,
+            [LintWarning::new(
+                3,
+                3,
+                "Unreachable statement 'print 2;' after return.",
+                LintSeverity::Warning
+            )]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let source = "fun add(a, b) {\n  var unused = 1;\n  return a + b;\n}\nprint add(1);";
+        let warnings = Lox::lint(source);
+        assert_eq!(
+            warnings,
+            [
+                LintWarning::new(
+                    2,
+                    7,
+                    "Variable 'unused' is declared but never used.",
+                    LintSeverity::Warning
+                ),
+                LintWarning::new(
+                    5,
+                    12,
+                    "Function 'add' expects 2 arguments but got 1.",
+                    LintSeverity::Error
+                ),
+            ]
+        );
+        // 程序不会被执行
+        assert!(Lox::lint("exit(1);").is_empty());
+
+        let warnings = Lox::lint("print 1;\nprint (;");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            (warnings[0].line, warnings[0].severity),
+            (2, LintSeverity::Error)
+        );
+        let warnings = Lox::lint("return 1;");
+        assert_eq!(warnings[0].message, "Can't return from top-level code.");
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut lox = Lox::new();
+        lox.run("var foobar = 1; fun format_name() {}".to_string())
+            .unwrap();
+        assert_eq!(lox.complete("fo", 2), ["foobar", "for", "format_name"]);
+        assert_eq!(lox.complete("foob", 4), ["foobar"]);
+        assert_eq!(lox.complete("wh", 2), ["while"]);
+        // 只取光标之前的标识符
+        assert_eq!(lox.complete("print foob + 1;", 10), ["foobar"]);
+        assert_eq!(lox.complete("print x.wh", 10), ["while"]);
+        assert!(lox.complete("zz", 2).is_empty());
+        // 不在字符边界上的位置没有候选
+        assert!(lox.complete("日本", 1).is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        // 程序不会被执行
+        assert!(Lox::check("print 1; exit(3);").is_ok());
+
+        let errors = Lox::check("print (1;").unwrap_err();
+        assert!(matches!(errors[0], LoxError::Parse(_)));
+        let errors = Lox::check("{ var a = a; }").unwrap_err();
+        assert!(matches!(errors[0], LoxError::Resolve(_)));
+        // 运行时错误只有执行时才会出现
+        assert!(Lox::check("nil + 1;").is_ok());
+    }
 }
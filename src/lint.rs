@@ -0,0 +1,497 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::scanner::token::LoxInterrupt;
+use crate::scanner::{LoxType, Token};
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintSeverity {
+    // 代码可以运行，但很可能不是作者的本意
+    Warning,
+    // 代码无法运行，或运行到此处时必然出错
+    Error,
+}
+
+impl fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LintSeverity::Warning => write!(f, "warning"),
+            LintSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+// 静态检查发现的问题，行号与列号均从 1 开始
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+    pub severity: LintSeverity,
+}
+
+impl LintWarning {
+    pub fn new(line: usize, col: usize, message: &str, severity: LintSeverity) -> Self {
+        LintWarning {
+            line,
+            col,
+            message: message.to_string(),
+            severity,
+        }
+    }
+
+    pub(crate) fn at(token: &Token, message: &str, severity: LintSeverity) -> Self {
+        LintWarning::new(token.line, token.col_start + 1, message, severity)
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.line, self.col, self.severity, self.message
+        )
+    }
+}
+
+// 作用域中的一个名称
+struct Binding {
+    token: Token,
+    // 用于提示信息，例如 Variable、Function
+    kind: &'static str,
+    used: bool,
+    // 没有初始值的变量在第一次赋值之前读取只能得到 nil
+    assigned: bool,
+    // 以 fun 声明且之后没有被重新赋值时记录参数个数，用于检查调用
+    arity: Option<usize>,
+    // 声明所在的函数嵌套层数，只有同一个函数内的读取才能按源码顺序判断是否已赋值
+    function_depth: usize,
+}
+
+// 不执行程序，遍历语法树检查常见的错误写法
+// 第一层作用域为全局作用域，全局名称可能被宿主程序使用，不检查是否未使用
+pub struct Linter {
+    scopes: Vec<HashMap<String, Binding>>,
+    function_depth: usize,
+    warnings: Vec<LintWarning>,
+}
+
+impl Linter {
+    pub fn lint(statements: &[Box<dyn Stmt>]) -> Vec<LintWarning> {
+        let mut linter = Linter {
+            scopes: vec![HashMap::new()],
+            function_depth: 0,
+            warnings: Vec::new(),
+        };
+        linter.stmts(statements);
+        linter.warnings
+    }
+
+    fn stmts(&mut self, statements: &[Box<dyn Stmt>]) {
+        for stmt in statements {
+            self.stmt(stmt.as_ref());
+        }
+    }
+
+    fn stmt(&mut self, stmt: &dyn Stmt) {
+        let _ = stmt.accept(self);
+    }
+
+    fn expr(&mut self, expr: &dyn Expr) {
+        let _ = expr.accept(self);
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        let Some(scope) = self.scopes.pop() else {
+            return;
+        };
+        for (name, binding) in scope {
+            // 以下划线开头的名称表示有意不使用
+            if !binding.used && !name.starts_with('_') {
+                let message = format!("{} '{}' is declared but never used.", binding.kind, name);
+                self.warn(&binding.token, &message, LintSeverity::Warning);
+            }
+        }
+    }
+
+    fn declare(&mut self, name: &Token, kind: &'static str, assigned: bool, arity: Option<usize>) {
+        let binding = Binding {
+            token: name.clone(),
+            kind,
+            used: false,
+            assigned,
+            arity,
+            function_depth: self.function_depth,
+        };
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), binding);
+        }
+    }
+
+    fn lookup(&mut self, name: &Token) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(&name.lexeme))
+    }
+
+    fn warn(&mut self, token: &Token, message: &str, severity: LintSeverity) {
+        self.warnings
+            .push(LintWarning::at(token, message, severity));
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.function_depth += 1;
+        self.begin_scope();
+        for param in function.params.iter() {
+            // 参数由调用方传入，不要求一定使用
+            self.declare(param, "Parameter", true, None);
+            if let Some(binding) = self.lookup(param) {
+                binding.used = true;
+            }
+        }
+        self.stmts(&function.body);
+        self.end_scope();
+        self.function_depth -= 1;
+    }
+}
+
+// 没有写初始值的变量声明由解析器补上 nil 字面量
+fn is_nil_literal(expr: &dyn Expr) -> bool {
+    expr.as_any()
+        .downcast_ref::<Literal>()
+        .is_some_and(|literal| literal.value == LoxType::Nil)
+}
+
+impl ExprVisitor for Linter {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.value.as_ref());
+        if let Some(binding) = self.lookup(&expr.name) {
+            binding.assigned = true;
+            binding.arity = None;
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.left.as_ref());
+        self.expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.expression.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn literal_visit(&mut self, _expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        Ok(LoxType::Nil)
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.left.as_ref());
+        self.expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.condition.as_ref());
+        self.expr(expr.then_branch.as_ref());
+        self.expr(expr.else_branch.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        self.function(&expr.function);
+        Ok(LoxType::Nil)
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        for element in expr.elements.iter() {
+            self.expr(element.as_ref());
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.object.as_ref());
+        self.expr(expr.index.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.value.as_ref());
+        self.expr(expr.object.as_ref());
+        self.expr(expr.index.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        for value in expr.values.iter() {
+            self.expr(value.as_ref());
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        let function_depth = self.function_depth;
+        let Some(binding) = self.lookup(&expr.name) else {
+            return Ok(LoxType::Nil);
+        };
+        binding.used = true;
+        if !binding.assigned && binding.function_depth == function_depth {
+            // 只提示一次
+            binding.assigned = true;
+            let message = format!(
+                "Variable '{}' is used before being assigned.",
+                expr.name.lexeme
+            );
+            self.warn(&expr.name, &message, LintSeverity::Warning);
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.callee.as_ref());
+        for argument in expr.arguments.iter() {
+            self.expr(argument.as_ref());
+        }
+        let Some(variable) = expr.callee.as_any().downcast_ref::<Variable>() else {
+            return Ok(LoxType::Nil);
+        };
+        if let Some(arity) = self
+            .lookup(&variable.name)
+            .and_then(|binding| binding.arity)
+            && arity != expr.arguments.len()
+        {
+            let message = format!(
+                "Function '{}' expects {} arguments but got {}.",
+                variable.name.lexeme,
+                arity,
+                expr.arguments.len()
+            );
+            self.warn(&expr.paren, &message, LintSeverity::Error);
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.object.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.value.as_ref());
+        self.expr(expr.object.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn super_visit(&mut self, _expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        Ok(LoxType::Nil)
+    }
+
+    fn this_visit(&mut self, _expr: &This) -> Result<LoxType, LoxInterrupt> {
+        Ok(LoxType::Nil)
+    }
+}
+
+impl StmtVisitor for Linter {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.expression.as_ref());
+        Ok(None)
+    }
+
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.condition.as_ref());
+        self.stmt(stmt.then_branch.as_ref());
+        if let Some(else_branch) = stmt.else_branch.as_ref() {
+            self.stmt(else_branch.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.begin_scope();
+        self.stmts(&stmt.statements);
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.expression.as_ref());
+        Ok(None)
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.initializer.as_ref());
+        let assigned = !is_nil_literal(stmt.initializer.as_ref());
+        self.declare(&stmt.name, "Variable", assigned, None);
+        Ok(None)
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.initializer.as_ref());
+        for name in stmt.names.iter().chain(stmt.rest.as_ref()) {
+            self.declare(name, "Variable", true, None);
+        }
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.initializer.as_ref());
+        self.declare(&stmt.name, "Constant", true, None);
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.condition.as_ref());
+        self.stmt(stmt.body.as_ref());
+        if let Some(increment) = stmt.increment.as_ref() {
+            self.expr(increment.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.iterable.as_ref());
+        self.begin_scope();
+        self.declare(&stmt.name, "Variable", true, None);
+        self.stmt(stmt.body.as_ref());
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.declare(&stmt.name, "Function", true, Some(stmt.params.len()));
+        self.function(stmt);
+        Ok(None)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        if let Some(value) = stmt.value.as_ref() {
+            self.expr(value.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn break_visit(&mut self, _stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        Ok(None)
+    }
+
+    fn continue_visit(&mut self, _stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        Ok(None)
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.scrutinee.as_ref());
+        for (case, body) in stmt.cases.iter() {
+            self.expr(case.as_ref());
+            self.begin_scope();
+            self.stmts(body);
+            self.end_scope();
+        }
+        if let Some(body) = &stmt.default_case {
+            self.begin_scope();
+            self.stmts(body);
+            self.end_scope();
+        }
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.begin_scope();
+        self.stmts(&stmt.body);
+        self.end_scope();
+        self.begin_scope();
+        self.declare(&stmt.name, "Variable", true, None);
+        self.stmts(&stmt.handler);
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.value.as_ref());
+        Ok(None)
+    }
+
+    fn import_visit(&mut self, _stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        Ok(None)
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.declare(&stmt.name, "Class", true, None);
+        if let Some(superclass) = &stmt.superclass {
+            self.expr(superclass.as_ref());
+        }
+        for method in stmt.methods.iter() {
+            self.function(method);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn lint(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut warnings = Linter::lint(&statements);
+        warnings.sort_by_key(|warning| (warning.line, warning.col));
+        warnings.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_unused_variable() {
+        assert_eq!(
+            lint("fun f(unused) {\n  var a = 1;\n  var _b = 2;\n  var c = 3;\n  return c;\n}"),
+            ["2:7: warning: Variable 'a' is declared but never used."]
+        );
+        // 全局变量可能被宿主程序使用，不做检查
+        assert!(lint("var a = 1;").is_empty());
+        assert_eq!(
+            lint("{\n  fun g() {}\n}"),
+            ["2:7: warning: Function 'g' is declared but never used."]
+        );
+    }
+
+    #[test]
+    fn test_used_before_assignment() {
+        assert_eq!(
+            lint("var a;\nprint a;\na = 1;\nprint a;"),
+            ["2:7: warning: Variable 'a' is used before being assigned."]
+        );
+        assert!(lint("var a;\na = 1;\nprint a;").is_empty());
+        // 函数体可能在赋值之后才被调用
+        assert!(lint("var a;\nfun f() { return a; }\na = 1;\nf();").is_empty());
+    }
+
+    #[test]
+    fn test_wrong_arity() {
+        assert_eq!(
+            lint("fun f(a, b) { return a + b; }\nf(1);\nf(1, 2);"),
+            ["2:4: error: Function 'f' expects 2 arguments but got 1."]
+        );
+        // 重新赋值后无法确定参数个数
+        assert!(lint("fun f(a) { return a; }\nf = clock;\nf();").is_empty());
+    }
+}
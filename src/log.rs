@@ -1,5 +1,6 @@
 pub struct Log;
 
+#[allow(dead_code)]
 impl Log {
     pub fn info(message: &str) {
         println!("LOX: [INFO]: {}", message);
@@ -1,7 +1,15 @@
-use lox_rs::Lox;
+use lox_rs::{Lox, LoxError};
+use std::io::Read;
 
 fn main() {
     let args = std::env::args().collect::<Vec<String>>();
+    match args.get(1).map(String::as_str) {
+        Some("--tokens") => return dump(&args, Lox::dump_tokens),
+        Some("--ast") => return dump(&args, Lox::dump_ast),
+        Some("--bytecode") => return dump(&args, Lox::dump_bytecode),
+        Some("--check") => return dump(&args, |source| Lox::check(source).map(|()| String::new())),
+        _ => {}
+    }
     println!("Arguments: {:?}", args);
     let mut lox = Lox::new();
     if args.len() > 2 {
@@ -13,3 +21,29 @@ fn main() {
         lox.run_prompt();
     }
 }
+
+// lox-rs --tokens|--ast|--bytecode|--check <file>，file 为 - 时从标准输入读取
+fn dump(args: &[String], dump: fn(&str) -> Result<String, Vec<LoxError>>) {
+    if args.len() != 3 {
+        println!("Usage: lox-rs {} <script|->", args[1]);
+        std::process::exit(64);
+    }
+    let source = if args[2] == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .expect("Reader Stdin Error");
+        source
+    } else {
+        std::fs::read_to_string(&args[2]).expect("Reader File Error")
+    };
+    match dump(&source) {
+        Ok(output) => print!("{}", output),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}", error);
+            }
+            std::process::exit(65);
+        }
+    }
+}
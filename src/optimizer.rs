@@ -0,0 +1,488 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::lint::LintWarning;
+use crate::scanner::token::LoxInterrupt;
+use crate::scanner::{LoxType, TokenType};
+use dead_code::DeadCodeEliminator;
+use std::rc::Rc;
+
+mod dead_code;
+
+// 在解释执行之前重建语法树，把操作数都是数字字面量的运算折叠为一个字面量，
+// 并删除函数中 return 之后不可能执行到的语句
+// 必须在 Resolver 之前运行，Resolver 按节点地址记录局部变量
+pub struct Optimizer {
+    // 访问方法把重建后的节点放在这里，由 expr / stmt 取出
+    expr: Option<Box<dyn Expr>>,
+    stmt: Option<Box<dyn Stmt>>,
+    dead_code: DeadCodeEliminator,
+}
+
+impl Optimizer {
+    pub fn optimize(statements: &[Box<dyn Stmt>]) -> Vec<Box<dyn Stmt>> {
+        Self::optimize_with_warnings(statements).0
+    }
+
+    // 同时返回被删除的不可达语句对应的警告
+    pub fn optimize_with_warnings(
+        statements: &[Box<dyn Stmt>],
+    ) -> (Vec<Box<dyn Stmt>>, Vec<LintWarning>) {
+        let mut optimizer = Optimizer {
+            expr: None,
+            stmt: None,
+            dead_code: DeadCodeEliminator::default(),
+        };
+        let statements = optimizer.stmts(statements);
+        (statements, optimizer.dead_code.into_warnings())
+    }
+
+    fn expr(&mut self, expr: &dyn Expr) -> Box<dyn Expr> {
+        expr.accept(self).expect("Optimizing never interrupts");
+        self.expr.take().expect("Every expression is rebuilt")
+    }
+
+    fn stmt(&mut self, stmt: &dyn Stmt) -> Box<dyn Stmt> {
+        stmt.accept(self).expect("Optimizing never interrupts");
+        self.stmt.take().expect("Every statement is rebuilt")
+    }
+
+    fn stmts(&mut self, statements: &[Box<dyn Stmt>]) -> Vec<Box<dyn Stmt>> {
+        statements
+            .iter()
+            .map(|stmt| self.stmt(stmt.as_ref()))
+            .collect()
+    }
+
+    fn exprs(&mut self, exprs: &[Box<dyn Expr>]) -> Vec<Box<dyn Expr>> {
+        exprs.iter().map(|expr| self.expr(expr.as_ref())).collect()
+    }
+
+    fn function(&mut self, function: &Function) -> Function {
+        let body = self.stmts(&function.body);
+        let body = self.dead_code.eliminate(body);
+        Function::new(
+            function.name.clone(),
+            function.params.clone(),
+            Rc::new(body),
+        )
+    }
+
+    fn rebuilt_expr(&mut self, expr: impl Expr + 'static) -> Result<LoxType, LoxInterrupt> {
+        self.expr = Some(Box::new(expr));
+        Ok(LoxType::Nil)
+    }
+
+    fn rebuilt_stmt(&mut self, stmt: impl Stmt + 'static) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.stmt = Some(Box::new(stmt));
+        Ok(None)
+    }
+}
+
+fn number(expr: &dyn Expr) -> Option<f64> {
+    match expr.as_any().downcast_ref::<Literal>()?.value {
+        LoxType::Num(number) => Some(number),
+        _ => None,
+    }
+}
+
+// 运行时会报错的运算（例如除以零）不折叠，留给解释器报告错误位置
+fn fold_binary(operator: &TokenType, left: f64, right: f64) -> Option<LoxType> {
+    let value = match operator {
+        TokenType::Plus => LoxType::new_num(left + right),
+        TokenType::Minus => LoxType::new_num(left - right),
+        TokenType::Star => LoxType::new_num(left * right),
+        TokenType::Slash if right != 0.0 => LoxType::new_num(left / right),
+        TokenType::Percent if right != 0.0 => LoxType::new_num(left % right),
+        TokenType::StarStar => LoxType::new_num(left.powf(right)),
+        TokenType::Greater => LoxType::new_bool(left > right),
+        TokenType::GreaterEqual => LoxType::new_bool(left >= right),
+        TokenType::Less => LoxType::new_bool(left < right),
+        TokenType::LessEqual => LoxType::new_bool(left <= right),
+        TokenType::EqualEqual => LoxType::new_bool(left == right),
+        TokenType::BangEqual => LoxType::new_bool(left != right),
+        _ => return None,
+    };
+    Some(value)
+}
+
+impl ExprVisitor for Optimizer {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        let value = self.expr(expr.value.as_ref());
+        self.rebuilt_expr(Assign::new(expr.name.clone(), value))
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        let left = self.expr(expr.left.as_ref());
+        let right = self.expr(expr.right.as_ref());
+        if let (Some(a), Some(b)) = (number(left.as_ref()), number(right.as_ref()))
+            && let Some(value) = fold_binary(&expr.operator.token_type, a, b)
+        {
+            return self.rebuilt_expr(Literal::new(value));
+        }
+        self.rebuilt_expr(Binary::new(left, expr.operator.clone(), right))
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        let expression = self.expr(expr.expression.as_ref());
+        // 括号中只剩一个字面量时去掉括号，使外层运算也能继续折叠
+        if expression.as_any().is::<Literal>() {
+            self.expr = Some(expression);
+            return Ok(LoxType::Nil);
+        }
+        self.rebuilt_expr(Grouping::new(expression))
+    }
+
+    fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        self.rebuilt_expr(expr.clone())
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        let left = self.expr(expr.left.as_ref());
+        let right = self.expr(expr.right.as_ref());
+        self.rebuilt_expr(Logical::new(left, expr.operator.clone(), right))
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        let right = self.expr(expr.right.as_ref());
+        if expr.operator.token_type == TokenType::Minus
+            && let Some(number) = number(right.as_ref())
+        {
+            return self.rebuilt_expr(Literal::new(LoxType::new_num(-number)));
+        }
+        self.rebuilt_expr(Unary::new(expr.operator.clone(), right))
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        self.rebuilt_expr(expr.clone())
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        let callee = self.expr(expr.callee.as_ref());
+        let arguments = self.exprs(&expr.arguments);
+        self.rebuilt_expr(Call::new(callee, expr.paren.clone(), arguments))
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        self.rebuilt_expr(Get::new(object, expr.name.clone()))
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let value = self.expr(expr.value.as_ref());
+        self.rebuilt_expr(Set::new(
+            object,
+            expr.name.clone(),
+            expr.operator.clone(),
+            value,
+        ))
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        self.rebuilt_expr(expr.clone())
+    }
+
+    fn this_visit(&mut self, expr: &This) -> Result<LoxType, LoxInterrupt> {
+        self.rebuilt_expr(expr.clone())
+    }
+
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        let condition = self.expr(expr.condition.as_ref());
+        let then_branch = self.expr(expr.then_branch.as_ref());
+        let else_branch = self.expr(expr.else_branch.as_ref());
+        self.rebuilt_expr(Ternary::new(condition, then_branch, else_branch))
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        let function = self.function(&expr.function);
+        self.rebuilt_expr(Lambda::new(function))
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        let elements = self.exprs(&expr.elements);
+        self.rebuilt_expr(ListLiteral::new(expr.bracket.clone(), elements))
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let index = self.expr(expr.index.as_ref());
+        self.rebuilt_expr(Subscript::new(object, expr.bracket.clone(), index))
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        let object = self.expr(expr.object.as_ref());
+        let index = self.expr(expr.index.as_ref());
+        let value = self.expr(expr.value.as_ref());
+        self.rebuilt_expr(SubscriptAssign::new(
+            object,
+            expr.bracket.clone(),
+            index,
+            expr.operator.clone(),
+            value,
+        ))
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        let values = self.exprs(&expr.values);
+        self.rebuilt_expr(MapLiteral::new(
+            expr.brace.clone(),
+            expr.keys.clone(),
+            values,
+        ))
+    }
+}
+
+impl StmtVisitor for Optimizer {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        let expression = self.expr(stmt.expression.as_ref());
+        self.rebuilt_stmt(Print::new(stmt.keyword.clone(), expression, stmt.newline))
+    }
+
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        let condition = self.expr(stmt.condition.as_ref());
+        let then_branch = self.stmt(stmt.then_branch.as_ref());
+        let else_branch = stmt
+            .else_branch
+            .as_ref()
+            .map(|branch| self.stmt(branch.as_ref()));
+        self.rebuilt_stmt(If::new(condition, then_branch, else_branch))
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        let statements = self.stmts(&stmt.statements);
+        let statements = self.dead_code.eliminate(statements);
+        self.rebuilt_stmt(Block::new(statements))
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        let expression = self.expr(stmt.expression.as_ref());
+        self.rebuilt_stmt(Expression::new(expression))
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.rebuilt_stmt(Var::new(stmt.name.clone(), initializer))
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.rebuilt_stmt(VarList::new(
+            stmt.bracket.clone(),
+            stmt.names.clone(),
+            stmt.rest.clone(),
+            initializer,
+        ))
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        let initializer = self.expr(stmt.initializer.as_ref());
+        self.rebuilt_stmt(Const::new(stmt.name.clone(), initializer))
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        let condition = self.expr(stmt.condition.as_ref());
+        let body = self.stmt(stmt.body.as_ref());
+        let increment = stmt
+            .increment
+            .as_ref()
+            .map(|increment| self.expr(increment.as_ref()));
+        self.rebuilt_stmt(While::new(condition, body, increment))
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        let function = self.function(stmt);
+        self.rebuilt_stmt(function)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = stmt.value.as_ref().map(|value| self.expr(value.as_ref()));
+        self.rebuilt_stmt(Return::new(stmt.keyword.clone(), value))
+    }
+
+    fn break_visit(&mut self, stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.rebuilt_stmt(stmt.clone())
+    }
+
+    fn continue_visit(&mut self, stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.rebuilt_stmt(stmt.clone())
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        let iterable = self.expr(stmt.iterable.as_ref());
+        let body = self.stmt(stmt.body.as_ref());
+        self.rebuilt_stmt(ForIn::new(stmt.name.clone(), iterable, body))
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        let scrutinee = self.expr(stmt.scrutinee.as_ref());
+        let cases = stmt
+            .cases
+            .iter()
+            .map(|(value, body)| (self.expr(value.as_ref()), self.stmts(body)))
+            .collect();
+        let default_case = stmt.default_case.as_ref().map(|body| self.stmts(body));
+        self.rebuilt_stmt(Switch::new(
+            stmt.keyword.clone(),
+            scrutinee,
+            cases,
+            default_case,
+        ))
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        let body = self.stmts(&stmt.body);
+        let handler = self.stmts(&stmt.handler);
+        self.rebuilt_stmt(Try::new(
+            stmt.keyword.clone(),
+            body,
+            stmt.name.clone(),
+            handler,
+        ))
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        let value = self.expr(stmt.value.as_ref());
+        self.rebuilt_stmt(Throw::new(stmt.keyword.clone(), value))
+    }
+
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.rebuilt_stmt(stmt.clone())
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        let superclass = stmt
+            .superclass
+            .as_ref()
+            .map(|superclass| self.expr(superclass.as_ref()));
+        let methods = stmt
+            .methods
+            .iter()
+            .map(|method| self.function(method))
+            .collect();
+        self.rebuilt_stmt(Class::new(stmt.name.clone(), superclass, methods))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ast::printer::AstPrinter;
+    use crate::lint::LintSeverity;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn optimize_source(source: &str) -> Vec<String> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Optimizer::optimize(&statements)
+            .iter()
+            .map(|stmt| AstPrinter::print_stmt(stmt.as_ref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fold_constants() {
+        let tokens = Scanner::new("(1 + 2) * (3 - 1);".to_string(), None).scan_tokens();
+        let statements = Optimizer::optimize(&Parser::new(tokens).parse().unwrap());
+        let expression = statements[0].as_any().downcast_ref::<Expression>().unwrap();
+        let literal = expression
+            .expression
+            .as_any()
+            .downcast_ref::<Literal>()
+            .unwrap();
+        assert_eq!(literal.value, LoxType::new_num(6.0));
+
+        assert_eq!(
+            optimize_source("print -(2 ** 3) % 5 < 0;"),
+            ["(print true)"]
+        );
+        assert_eq!(optimize_source("var a = 1 == 1.0;"), ["(var a true)"]);
+    }
+
+    #[test]
+    fn test_fold_nested_statements() {
+        let printed = optimize_source("fun f(x) { return x + 2 * 3; }");
+        assert_eq!(printed[0], "(fun f (x)\n  (return (+ x 6)))");
+        let printed = optimize_source("class A { m() { while (1 < 2) print [1 + 1]; } }");
+        assert_eq!(
+            printed[0],
+            "(class A\n  (m ()\n    (while true\n      (print (list 2)))))"
+        );
+    }
+
+    #[test]
+    fn test_eliminate_dead_code() {
+        let source =
+            "fun f() {\n  return 1;\n  print 2;\n  { print 3; }\n  fun g() {}\n  x = 1;\n}";
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let (statements, warnings) = Optimizer::optimize_with_warnings(&statements);
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(fun f ()\n  (return 1))"
+        );
+        assert_eq!(
+            warnings,
+            [
+                LintWarning::new(
+                    3,
+                    3,
+                    "Unreachable statement 'print 2;' after return.",
+                    LintSeverity::Warning
+                ),
+                LintWarning::new(
+                    4,
+                    5,
+                    "Unreachable block after return.",
+                    LintSeverity::Warning
+                ),
+                LintWarning::new(
+                    5,
+                    7,
+                    "Unreachable statement 'fun g() {}' after return.",
+                    LintSeverity::Warning
+                ),
+                LintWarning::new(
+                    6,
+                    3,
+                    "Unreachable statement 'x = 1;' after return.",
+                    LintSeverity::Warning
+                ),
+            ]
+        );
+
+        // 条件折叠为常量后，只看会执行的分支
+        let printed = optimize_source(
+            "fun g() { if (1 < 2) { return; } print \"dead\"; }
+            fun h() { if (false) return; print \"alive\"; }
+            fun k(x) { if (x) return 1; else return 2; print \"dead\"; }
+            var l = fun () { { return; print \"dead\"; } };",
+        );
+        assert_eq!(
+            printed[0],
+            "(fun g ()\n  (if true\n    (block\n      (return))))"
+        );
+        assert_eq!(
+            printed[1],
+            "(fun h ()\n  (if false\n    (return))\n  (print \"alive\"))"
+        );
+        assert_eq!(
+            printed[2],
+            "(fun k (x)\n  (if x\n    (return 1)\n    (return 2)))"
+        );
+        assert_eq!(printed[3], "(var l (fun ()\n  (block\n    (return))))");
+    }
+
+    #[test]
+    fn test_keep_runtime_errors() {
+        // 除以零与非数字的运算保留原样，由解释器在运行时报告
+        assert_eq!(optimize_source("print 1 / 0;"), ["(print (/ 1 0))"]);
+        assert_eq!(optimize_source("print 1 % 0;"), ["(print (% 1 0))"]);
+        assert_eq!(optimize_source("print \"a\" + 1;"), ["(print (+ \"a\" 1))"]);
+        assert_eq!(optimize_source("print -(nil);"), ["(print (- nil))"]);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, Expression, ForIn, Function,
+    Get, Grouping, If, Import, ListLiteral, Literal, Logical, MapLiteral, Print, Return, Set, Stmt,
+    Subscript, SubscriptAssign, Super, Switch, Ternary, This, Throw, Try, Unary, Var, VarList,
+    Variable, While,
+};
+use crate::formatter::Formatter;
+use crate::lint::{LintSeverity, LintWarning};
+use crate::scanner::{LoxType, Token};
+use std::slice;
+
+// 删除一定会 return 的语句之后的语句，并为每条被删除的语句记录一条警告
+#[derive(Default)]
+pub struct DeadCodeEliminator {
+    warnings: Vec<LintWarning>,
+}
+
+impl DeadCodeEliminator {
+    pub fn eliminate(&mut self, mut statements: Vec<Box<dyn Stmt>>) -> Vec<Box<dyn Stmt>> {
+        let Some((index, keyword)) = statements
+            .iter()
+            .enumerate()
+            .find_map(|(index, stmt)| Some((index, returning(stmt.as_ref())?.clone())))
+        else {
+            return statements;
+        };
+        for stmt in statements.drain(index + 1..) {
+            // 单行的语句直接引用源码，多行的语句只描述语句的种类
+            let text = Formatter::format(slice::from_ref(&stmt));
+            let message = match text.trim_end().lines().collect::<Vec<_>>().as_slice() {
+                [line] => format!("Unreachable statement '{}' after return.", line),
+                _ => format!("Unreachable {} after return.", describe(stmt.as_ref())),
+            };
+            // 找不到任何 token 的语句（例如空块）退而指向 return 关键字
+            let token = first_token(stmt.as_ref()).unwrap_or(&keyword);
+            self.warnings
+                .push(LintWarning::at(token, &message, LintSeverity::Warning));
+        }
+        statements
+    }
+
+    pub fn into_warnings(self) -> Vec<LintWarning> {
+        self.warnings
+    }
+}
+
+// 语句执行完之前一定会从函数返回时，返回导致返回的 return 关键字
+// 条件已经过常量折叠，条件为字面量的 if 只看会执行的那个分支
+fn returning(stmt: &dyn Stmt) -> Option<&Token> {
+    let any = stmt.as_any();
+    if let Some(stmt) = any.downcast_ref::<Return>() {
+        return Some(&stmt.keyword);
+    }
+    if let Some(block) = any.downcast_ref::<Block>() {
+        return block
+            .statements
+            .iter()
+            .find_map(|stmt| returning(stmt.as_ref()));
+    }
+    let stmt = any.downcast_ref::<If>()?;
+    let else_returning = stmt
+        .else_branch
+        .as_ref()
+        .and_then(|branch| returning(branch.as_ref()));
+    match constant_truthiness(stmt.condition.as_any().downcast_ref::<Literal>()) {
+        Some(true) => returning(stmt.then_branch.as_ref()),
+        Some(false) => else_returning,
+        None => else_returning.and(returning(stmt.then_branch.as_ref())),
+    }
+}
+
+fn constant_truthiness(literal: Option<&Literal>) -> Option<bool> {
+    Some(!matches!(
+        literal?.value,
+        LoxType::Nil | LoxType::Bool(false)
+    ))
+}
+
+fn describe(stmt: &dyn Stmt) -> String {
+    let any = stmt.as_any();
+    if let Some(function) = any.downcast_ref::<Function>() {
+        return format!("function '{}'", function.name.lexeme);
+    }
+    if let Some(class) = any.downcast_ref::<Class>() {
+        return format!("class '{}'", class.name.lexeme);
+    }
+    let kind = if any.is::<Block>() {
+        "block"
+    } else if any.is::<If>() {
+        "'if' statement"
+    } else if any.is::<While>() || any.is::<ForIn>() {
+        "loop"
+    } else if any.is::<Switch>() {
+        "'switch' statement"
+    } else if any.is::<Try>() {
+        "'try' statement"
+    } else {
+        "statement"
+    };
+    kind.to_string()
+}
+
+// 语句中位置最靠前的 token，用于定位被删除的语句
+fn first_token(stmt: &dyn Stmt) -> Option<&Token> {
+    let any = stmt.as_any();
+    if let Some(stmt) = any.downcast_ref::<Print>() {
+        return Some(&stmt.keyword);
+    }
+    if let Some(stmt) = any.downcast_ref::<Expression>() {
+        return expr_token(stmt.expression.as_ref());
+    }
+    if let Some(block) = any.downcast_ref::<Block>() {
+        return block
+            .statements
+            .iter()
+            .find_map(|stmt| first_token(stmt.as_ref()));
+    }
+    if let Some(stmt) = any.downcast_ref::<If>() {
+        return expr_token(stmt.condition.as_ref())
+            .or_else(|| first_token(stmt.then_branch.as_ref()))
+            .or_else(|| stmt.else_branch.as_deref().and_then(first_token));
+    }
+    if let Some(stmt) = any.downcast_ref::<While>() {
+        return expr_token(stmt.condition.as_ref()).or_else(|| first_token(stmt.body.as_ref()));
+    }
+    let token = if let Some(stmt) = any.downcast_ref::<Var>() {
+        &stmt.name
+    } else if let Some(stmt) = any.downcast_ref::<VarList>() {
+        &stmt.bracket
+    } else if let Some(stmt) = any.downcast_ref::<Const>() {
+        &stmt.name
+    } else if let Some(stmt) = any.downcast_ref::<Function>() {
+        &stmt.name
+    } else if let Some(stmt) = any.downcast_ref::<Class>() {
+        &stmt.name
+    } else if let Some(stmt) = any.downcast_ref::<ForIn>() {
+        &stmt.name
+    } else if let Some(stmt) = any.downcast_ref::<Return>() {
+        &stmt.keyword
+    } else if let Some(stmt) = any.downcast_ref::<Break>() {
+        &stmt.keyword
+    } else if let Some(stmt) = any.downcast_ref::<Continue>() {
+        &stmt.keyword
+    } else if let Some(stmt) = any.downcast_ref::<Switch>() {
+        &stmt.keyword
+    } else if let Some(stmt) = any.downcast_ref::<Try>() {
+        &stmt.keyword
+    } else if let Some(stmt) = any.downcast_ref::<Throw>() {
+        &stmt.keyword
+    } else {
+        &any.downcast_ref::<Import>()?.keyword
+    };
+    Some(token)
+}
+
+// 表达式中位置最靠前的 token，字面量没有 token 时返回 None
+fn expr_token(expr: &dyn Expr) -> Option<&Token> {
+    let any = expr.as_any();
+    if let Some(expr) = any.downcast_ref::<Binary>() {
+        return expr_token(expr.left.as_ref()).or(Some(&expr.operator));
+    }
+    if let Some(expr) = any.downcast_ref::<Logical>() {
+        return expr_token(expr.left.as_ref()).or(Some(&expr.operator));
+    }
+    if let Some(expr) = any.downcast_ref::<Grouping>() {
+        return expr_token(expr.expression.as_ref());
+    }
+    if let Some(expr) = any.downcast_ref::<Call>() {
+        return expr_token(expr.callee.as_ref()).or(Some(&expr.paren));
+    }
+    if let Some(expr) = any.downcast_ref::<Get>() {
+        return expr_token(expr.object.as_ref()).or(Some(&expr.name));
+    }
+    if let Some(expr) = any.downcast_ref::<Set>() {
+        return expr_token(expr.object.as_ref()).or(Some(&expr.name));
+    }
+    if let Some(expr) = any.downcast_ref::<Subscript>() {
+        return expr_token(expr.object.as_ref()).or(Some(&expr.bracket));
+    }
+    if let Some(expr) = any.downcast_ref::<SubscriptAssign>() {
+        return expr_token(expr.object.as_ref()).or(Some(&expr.bracket));
+    }
+    if let Some(expr) = any.downcast_ref::<Ternary>() {
+        return expr_token(expr.condition.as_ref())
+            .or_else(|| expr_token(expr.then_branch.as_ref()))
+            .or_else(|| expr_token(expr.else_branch.as_ref()));
+    }
+    let token = if let Some(expr) = any.downcast_ref::<Assign>() {
+        &expr.name
+    } else if let Some(expr) = any.downcast_ref::<Unary>() {
+        &expr.operator
+    } else if let Some(expr) = any.downcast_ref::<Variable>() {
+        &expr.name
+    } else if let Some(expr) = any.downcast_ref::<Super>() {
+        &expr.keyword
+    } else if let Some(expr) = any.downcast_ref::<This>() {
+        &expr.keyword
+    } else if let Some(expr) = any.downcast_ref::<ListLiteral>() {
+        &expr.bracket
+    } else {
+        &any.downcast_ref::<MapLiteral>()?.brace
+    };
+    Some(token)
+}
@@ -1,52 +1,105 @@
 use std::fmt::Debug;
-mod error;
+use std::mem;
+use std::rc::Rc;
+pub mod error;
 
 use crate::ast::{
-    Assign, Binary, Block, Call, Expr, Expression, Grouping, If, Literal, Logical, Print,
-    PrintExprVisitor, Return, Stmt, Unary, Var, Variable, While,
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, Expression, ForIn, Get,
+    Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral, Print, Return, Set,
+    Stmt, Subscript, SubscriptAssign, Super, Switch, Ternary, This, Throw, Try, Unary, Var,
+    VarList, Variable, While,
 };
-use crate::parser::error::{ParseError, create_parse_error};
-use crate::scanner::TokenType::Or;
+use crate::parser::error::ParseError;
 use crate::scanner::{LoxType, Token, TokenType};
 
 #[derive(Debug)]
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // 当前所在循环的嵌套层数，用于检查 break / continue 的位置
+    loop_depth: usize,
+    errors: Vec<ParseError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            loop_depth: 0,
+            errors: Vec::new(),
+        }
     }
 
-    pub fn parse(&mut self) -> Vec<Box<dyn Stmt>> {
+    // 解析全部语句，出错时同步到下一条语句继续解析，最终返回收集到的全部错误
+    pub fn parse(&mut self) -> Result<Vec<Box<dyn Stmt>>, Vec<ParseError>> {
         let mut statements = Vec::new();
         while !self.is_at_end() {
-            statements.push(self.declaration());
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(mem::take(&mut self.errors))
         }
-        statements
     }
 
-    fn declaration(&mut self) -> Box<dyn Stmt> {
-        let result = if self.match_types(vec![TokenType::Fun]) {
+    fn declaration(&mut self) -> Option<Box<dyn Stmt>> {
+        let result = if self.match_types(vec![TokenType::Class]) {
+            self.class_declaration()
+        } else if self.check(TokenType::Fun) && self.check_next(TokenType::Identifier) {
+            self.advance();
             self.function("function")
+                .map(|function| Box::new(function) as Box<dyn Stmt>)
         } else if self.match_types(vec![TokenType::Var]) {
             self.var_declaration()
+        } else if self.match_types(vec![TokenType::Const]) {
+            self.const_declaration()
         } else {
             self.statement()
         };
-        result.unwrap_or_else(|err| {
-            self.synchronize();
-            Box::new(Expression::new(Box::new(Literal::new(None))))
-        })
+        match result {
+            Ok(statement) => Some(statement),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+        let mut superclass: Option<Box<dyn Expr>> = None;
+        if self.match_types(vec![TokenType::Less]) {
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Box::new(Variable::new(superclass_name)));
+        }
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Box::new(Class::new(name, superclass, methods)))
     }
 
-    fn function(&mut self, kind: &str) -> Result<Box<dyn Stmt>, ParseError> {
+    fn function(&mut self, kind: &str) -> Result<crate::ast::Function, ParseError> {
         let name = self.consume(
             TokenType::Identifier,
             format!("Expect '{}' name.", kind).as_str(),
         )?;
+        self.function_body(name, kind)
+    }
+
+    // 解析参数列表与函数体，具名函数与匿名函数共用
+    fn function_body(
+        &mut self,
+        name: Token,
+        kind: &str,
+    ) -> Result<crate::ast::Function, ParseError> {
         // 解析括号部分
         self.consume(
             TokenType::LeftParen,
@@ -56,7 +109,10 @@ impl Parser {
         if !self.check(TokenType::RightParen) {
             loop {
                 if parameters.len() >= 255 {
-                    return Err(ParseError::new("Can't have more than 255 parameters."));
+                    return Err(ParseError::new(
+                        self.peek(),
+                        "Can't have more than 255 parameters.",
+                    ));
                 }
                 parameters.push(self.consume(TokenType::Identifier, "Expect parameter name")?);
                 if !self.match_types(vec![TokenType::Comma]) {
@@ -73,15 +129,22 @@ impl Parser {
             TokenType::LeftBrace,
             format!("Expect '{{' before {} body.", kind).as_str(),
         )?;
-        let body = self.block()?;
+        // 函数体内不能直接 break / continue 外层的循环
+        let enclosing_loop_depth = mem::replace(&mut self.loop_depth, 0);
+        let body = self.block();
+        self.loop_depth = enclosing_loop_depth;
+        let body = body?;
         // 拼接函数节点并返回
-        Ok(Box::new(crate::ast::Function::new(name, parameters, body)))
+        Ok(crate::ast::Function::new(name, parameters, Rc::new(body)))
     }
 
     fn var_declaration(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        if self.match_types(vec![TokenType::LeftBracket]) {
+            return self.var_list_declaration();
+        }
         let name = self.consume(TokenType::Identifier, "Expect variable name")?;
 
-        let mut initializer: Box<dyn Expr> = Box::new(Literal::new(None));
+        let mut initializer: Box<dyn Expr> = Box::new(Literal::new(LoxType::Nil));
         if self.match_types(vec![TokenType::Equal]) {
             initializer = self.expression()?;
         }
@@ -92,12 +155,53 @@ impl Parser {
         Ok(Box::new(Var::new(name, initializer)))
     }
 
-    fn expression(&mut self) -> Result<Box<dyn Expr>, ParseError> {
+    // 解构声明必须有初始值，...rest 只能出现在最后
+    fn var_list_declaration(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let bracket = self.previous();
+        let mut names = Vec::new();
+        let mut rest = None;
+        loop {
+            if self.match_types(vec![TokenType::Ellipsis]) {
+                rest =
+                    Some(self.consume(TokenType::Identifier, "Expect variable name after '...'.")?);
+                break;
+            }
+            names.push(self.consume(TokenType::Identifier, "Expect variable name.")?);
+            if !self.match_types(vec![TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(
+            TokenType::RightBracket,
+            "Expect ']' after destructuring pattern.",
+        )?;
+        self.consume(TokenType::Equal, "Expect '=' after destructuring pattern.")?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration",
+        )?;
+        Ok(Box::new(VarList::new(bracket, names, rest, initializer)))
+    }
+
+    // 常量必须在声明时初始化
+    fn const_declaration(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect constant name.")?;
+        self.consume(TokenType::Equal, "Expect '=' after constant name.")?;
+        let initializer = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after constant declaration.",
+        )?;
+        Ok(Box::new(Const::new(name, initializer)))
+    }
+
+    pub fn expression(&mut self) -> Result<Box<dyn Expr>, ParseError> {
         self.assignment()
     }
 
     fn assignment(&mut self) -> Result<Box<dyn Expr>, ParseError> {
-        let expr = self.or()?;
+        let expr = self.ternary()?;
 
         if self.match_types(vec![TokenType::Equal]) {
             let equals = self.previous();
@@ -107,14 +211,90 @@ impl Parser {
                 let name = var_expr.name.clone();
                 return Ok(Box::new(Assign::new(name, value)));
             }
+            if let Some(get_expr) = expr.as_any().downcast_ref::<Get>() {
+                let object = get_expr.object.clone();
+                let name = get_expr.name.clone();
+                return Ok(Box::new(Set::new(object, name, None, value)));
+            }
+            if let Some(subscript_expr) = expr.as_any().downcast_ref::<Subscript>() {
+                let object = subscript_expr.object.clone();
+                let bracket = subscript_expr.bracket.clone();
+                let index = subscript_expr.index.clone();
+                return Ok(Box::new(SubscriptAssign::new(
+                    object, bracket, index, None, value,
+                )));
+            }
+
+            let err_message = "Invalid assignment target.";
+            return Err(ParseError::new(&equals, err_message));
+        }
+
+        if self.match_types(vec![
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let compound = self.previous();
+            let value = self.assignment()?;
+            let mut operator = compound.clone();
+            operator.token_type = match compound.token_type {
+                TokenType::PlusEqual => TokenType::Plus,
+                TokenType::MinusEqual => TokenType::Minus,
+                TokenType::StarEqual => TokenType::Star,
+                _ => TokenType::Slash,
+            };
+            operator.lexeme = compound.lexeme[..1].to_string();
+
+            if let Some(var_expr) = expr.as_any().downcast_ref::<Variable>() {
+                // 脱糖：将 `x += e` 转换为 `x = x + e`
+                let name = var_expr.name.clone();
+                let binary = Binary::new(expr, operator, value);
+                return Ok(Box::new(Assign::new(name, Box::new(binary))));
+            }
+            // 属性与下标的复合赋值保留运算符，由解释器只求值一次对象与下标表达式
+            if let Some(get_expr) = expr.as_any().downcast_ref::<Get>() {
+                let object = get_expr.object.clone();
+                let name = get_expr.name.clone();
+                return Ok(Box::new(Set::new(object, name, Some(operator), value)));
+            }
+            if let Some(subscript_expr) = expr.as_any().downcast_ref::<Subscript>() {
+                let object = subscript_expr.object.clone();
+                let bracket = subscript_expr.bracket.clone();
+                let index = subscript_expr.index.clone();
+                return Ok(Box::new(SubscriptAssign::new(
+                    object,
+                    bracket,
+                    index,
+                    Some(operator),
+                    value,
+                )));
+            }
 
             let err_message = "Invalid assignment target.";
-            return Err(create_parse_error(&equals, err_message));
+            return Err(ParseError::new(&compound, err_message));
         }
 
         Ok(expr)
     }
 
+    // 三元表达式为右结合，else 分支继续按三元表达式解析
+    fn ternary(&mut self) -> Result<Box<dyn Expr>, ParseError> {
+        let condition = self.or()?;
+
+        if self.match_types(vec![TokenType::Question]) {
+            let then_branch = self.expression()?;
+            self.consume(
+                TokenType::Colon,
+                "Expect ':' after then branch of conditional expression.",
+            )?;
+            let else_branch = self.ternary()?;
+            return Ok(Box::new(Ternary::new(condition, then_branch, else_branch)));
+        }
+
+        Ok(condition)
+    }
+
     fn or(&mut self) -> Result<Box<dyn Expr>, ParseError> {
         let mut expr = self.and()?;
 
@@ -140,15 +320,18 @@ impl Parser {
     }
 
     fn statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        if self.match_types(vec![TokenType::LeftBrace]) {
+            return self.block_statement();
+        }
         if self.match_types(vec![TokenType::Print]) {
-            return self.print_statement();
+            return self.print_statement(true);
+        }
+        if self.match_types(vec![TokenType::Write]) {
+            return self.print_statement(false);
         }
         if self.match_types(vec![TokenType::Return]) {
             return self.return_statement();
         }
-        if self.match_types(vec![TokenType::LeftBrace]) {
-            return Ok(Box::new(Block::new(self.block()?)));
-        }
         if self.match_types(vec![TokenType::If]) {
             return self.if_statement();
         }
@@ -158,9 +341,41 @@ impl Parser {
         if self.match_types(vec![TokenType::For]) {
             return self.for_statement();
         }
+        if self.match_types(vec![TokenType::Break, TokenType::Continue]) {
+            return self.loop_control_statement();
+        }
+        if self.match_types(vec![TokenType::Switch]) {
+            return self.switch_statement();
+        }
+        if self.match_types(vec![TokenType::Try]) {
+            return self.try_statement();
+        }
+        if self.match_types(vec![TokenType::Throw]) {
+            return self.throw_statement();
+        }
+        if self.match_types(vec![TokenType::Import]) {
+            return self.import_statement();
+        }
         self.expression_statement()
     }
 
+    fn loop_control_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
+        if self.loop_depth == 0 {
+            let err_message = format!("Can't use '{}' outside of a loop.", keyword.lexeme);
+            return Err(ParseError::new(&keyword, err_message.as_str()));
+        }
+        self.consume(
+            TokenType::Semicolon,
+            format!("Expect ';' after '{}'.", keyword.lexeme).as_str(),
+        )?;
+        if keyword.token_type == TokenType::Break {
+            Ok(Box::new(Break::new(keyword)))
+        } else {
+            Ok(Box::new(Continue::new(keyword)))
+        }
+    }
+
     fn return_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
         let keyword = self.previous();
         let mut value: Option<Box<dyn Expr>> = None;
@@ -172,19 +387,31 @@ impl Parser {
     }
 
     fn for_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        // for x in list 形式可以省略括号
+        if self.check(TokenType::Identifier) && self.check_next(TokenType::In) {
+            return self.for_in_statement(false);
+        }
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+        if self.check(TokenType::Var)
+            && self
+                .tokens
+                .get(self.current + 2)
+                .is_some_and(|token| token.token_type == TokenType::In)
+        {
+            self.advance();
+            return self.for_in_statement(true);
+        }
         // 解析初始化部分
-        let mut initializer: Option<Box<dyn Stmt>> = None;
-        if (self.match_types(vec![TokenType::Semicolon])) {
+        let initializer: Option<Box<dyn Stmt>> = if self.match_types(vec![TokenType::Semicolon]) {
             // 省略初始化部分
-            initializer = None;
+            None
         } else if self.match_types(vec![TokenType::Var]) {
             // 初始化部分为变量定义
-            initializer = Some(self.var_declaration()?);
+            Some(self.var_declaration()?)
         } else {
             // 初始化部分为一个表达式
-            initializer = Some(self.expression_statement()?);
-        }
+            Some(self.expression_statement()?)
+        };
         // 解析条件表达式
         let mut condition: Option<Box<dyn Expr>> = None;
         if !self.check(TokenType::Semicolon) {
@@ -200,30 +427,26 @@ impl Parser {
         }
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
         // 获取到主要执行部分
-        let mut body = self.statement()?;
+        let mut body = self.loop_body()?;
         // 脱糖流程，将for转换为while格式
-        // 将自增后处理部分合并到body中
-        if increment.is_some() {
-            let increment_expression = Box::new(Expression::new(increment.unwrap()));
-            body = Box::new(Block::new(vec![body, increment_expression]));
-        }
-        // 将条件部分与body合并
+        // 将条件部分与body合并，自增部分由 While 在每次迭代后执行，保证 continue 时也会执行
         if condition.is_none() {
-            let true_expr = Literal::new(Some(LoxType::new_bool(true)));
+            let true_expr = Literal::new(LoxType::new_bool(true));
             condition = Some(Box::new(true_expr));
         }
-        body = Box::new(While::new(condition.unwrap(), body));
+        body = Box::new(While::new(condition.unwrap(), body, increment));
         // 将初始化部分与body合并
-        if initializer.is_some() {
-            body = Box::new(Block::new(vec![initializer.unwrap(), body]));
+        if let Some(initializer) = initializer {
+            body = Box::new(Block::new(vec![initializer, body]));
         }
         Ok(body)
     }
 
-    fn print_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+    fn print_statement(&mut self, newline: bool) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Box::new(Print::new(value)))
+        Ok(Box::new(Print::new(keyword, value, newline)))
     }
 
     fn expression_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
@@ -249,16 +472,126 @@ impl Parser {
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
-        let body = self.statement()?;
+        let body = self.loop_body()?;
+
+        Ok(Box::new(While::new(condition, body, None)))
+    }
+
+    // 遍历列表中的每个元素，parenthesized 表示是否为 for (var x in list) 形式
+    fn for_in_statement(&mut self, parenthesized: bool) -> Result<Box<dyn Stmt>, ParseError> {
+        let name = self.consume(TokenType::Identifier, "Expect loop variable name.")?;
+        self.consume(TokenType::In, "Expect 'in' after loop variable.")?;
+        let iterable = self.expression()?;
+        if parenthesized {
+            self.consume(TokenType::RightParen, "Expect ')' after for-in clause.")?;
+        }
+        let body = self.loop_body()?;
+        Ok(Box::new(ForIn::new(name, iterable, body)))
+    }
+
+    fn loop_body(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    // default 分支可以省略，存在时必须位于最后
+    fn switch_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'switch'.")?;
+        let scrutinee = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after switch value.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before switch cases.")?;
+        let mut cases = Vec::new();
+        let mut default_case = None;
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if default_case.is_some() {
+                return Err(ParseError::new(
+                    self.peek(),
+                    "Default case must be the last case in a switch.",
+                ));
+            }
+            if self.match_types(vec![TokenType::Case]) {
+                let value = self.expression()?;
+                self.consume(TokenType::Colon, "Expect ':' after case value.")?;
+                cases.push((value, self.case_body()));
+            } else if self.match_types(vec![TokenType::Default]) {
+                self.consume(TokenType::Colon, "Expect ':' after 'default'.")?;
+                default_case = Some(self.case_body());
+            } else {
+                return Err(ParseError::new(
+                    self.peek(),
+                    "Expect 'case' or 'default' in switch.",
+                ));
+            }
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after switch cases.")?;
+        Ok(Box::new(Switch::new(
+            keyword,
+            scrutinee,
+            cases,
+            default_case,
+        )))
+    }
+
+    // 分支中的语句一直持续到下一个 case、default 或 switch 的结尾
+    fn case_body(&mut self) -> Vec<Box<dyn Stmt>> {
+        let mut statements = Vec::new();
+        while ![TokenType::Case, TokenType::Default, TokenType::RightBrace]
+            .into_iter()
+            .any(|token_type| self.check(token_type))
+            && !self.is_at_end()
+        {
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
+        }
+        statements
+    }
+
+    fn try_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+        self.consume(TokenType::Catch, "Expect 'catch' after try block.")?;
+        self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+        let name = self.consume(TokenType::Identifier, "Expect catch variable name.")?;
+        self.consume(TokenType::RightParen, "Expect ')' after catch variable.")?;
+        self.consume(TokenType::LeftBrace, "Expect '{' before catch body.")?;
+        let handler = self.block()?;
+        Ok(Box::new(Try::new(keyword, body, name, handler)))
+    }
 
-        Ok(Box::new(While::new(condition, body)))
+    fn throw_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after throw value.")?;
+        Ok(Box::new(Throw::new(keyword, value)))
+    }
+
+    fn import_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        let keyword = self.previous();
+        let path = self.consume(TokenType::String, "Expect file path after 'import'.")?;
+        self.consume(TokenType::Semicolon, "Expect ';' after import path.")?;
+        let path = match path.literal {
+            Some(LoxType::Str(path)) => *path,
+            _ => path.lexeme,
+        };
+        Ok(Box::new(Import::new(keyword, path)))
+    }
+
+    fn block_statement(&mut self) -> Result<Box<dyn Stmt>, ParseError> {
+        Ok(Box::new(Block::new(self.block()?)))
     }
 
     fn block(&mut self) -> Result<Vec<Box<dyn Stmt>>, ParseError> {
         let mut statements = Vec::new();
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
-            statements.push(self.declaration());
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
         self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
@@ -308,7 +641,7 @@ impl Parser {
     fn factor(&mut self) -> Result<Box<dyn Expr>, ParseError> {
         let mut expr = self.unary()?;
 
-        while self.match_types(vec![TokenType::Slash, TokenType::Star]) {
+        while self.match_types(vec![TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous();
             let right = self.unary()?;
             expr = Box::new(Binary::new(expr, operator, right));
@@ -318,12 +651,25 @@ impl Parser {
     }
 
     fn unary(&mut self) -> Result<Box<dyn Expr>, ParseError> {
-        if self.match_types(vec![TokenType::Bang, TokenType::Minus]) {
-            let operator = self.previous();
+        if self.peek().token_type.is_unary_op() {
+            let operator = self.advance();
             let right = self.unary()?;
             return Ok(Box::new(Unary::new(operator, right)));
         }
-        self.call()
+        self.power()
+    }
+
+    // 乘方为右结合，且优先级高于一元运算符：-2 ** 2 等于 -(2 ** 2)
+    fn power(&mut self) -> Result<Box<dyn Expr>, ParseError> {
+        let expr = self.call()?;
+
+        if self.match_types(vec![TokenType::StarStar]) {
+            let operator = self.previous();
+            let right = self.unary()?;
+            return Ok(Box::new(Binary::new(expr, operator, right)));
+        }
+
+        Ok(expr)
     }
 
     fn call(&mut self) -> Result<Box<dyn Expr>, ParseError> {
@@ -331,6 +677,14 @@ impl Parser {
         loop {
             if self.match_types(vec![TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_types(vec![TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Box::new(Get::new(expr, name));
+            } else if self.match_types(vec![TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self.consume(TokenType::RightBracket, "Expect ']' after index.")?;
+                expr = Box::new(Subscript::new(expr, bracket, index));
             } else {
                 break;
             }
@@ -343,7 +697,10 @@ impl Parser {
         if !self.check(TokenType::RightParen) {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(ParseError::new("Can't have more than 255 arguments."));
+                    return Err(ParseError::new(
+                        self.peek(),
+                        "Can't have more than 255 arguments.",
+                    ));
                 }
                 arguments.push(self.expression()?);
                 if !self.match_types(vec![TokenType::Comma]) {
@@ -356,24 +713,77 @@ impl Parser {
     }
 
     fn primary(&mut self) -> Result<Box<dyn Expr>, ParseError> {
-        if self.match_types(vec![TokenType::False]) {
-            return Ok(Box::new(Literal::new(Some(LoxType::new_bool(false)))));
-        } else if self.match_types(vec![TokenType::True]) {
-            return Ok(Box::new(Literal::new(Some(LoxType::new_bool(true)))));
-        } else if self.match_types(vec![TokenType::Nil]) {
-            return Ok(Box::new(Literal::new(None)));
-        } else if self.match_types(vec![TokenType::Number, TokenType::String]) {
-            return Ok(Box::new(Literal::new(Some(
-                self.previous().literal.clone().unwrap(),
-            ))));
+        if self.peek().token_type.is_literal() {
+            let token = self.advance();
+            let value = match token.token_type {
+                TokenType::False => LoxType::new_bool(false),
+                TokenType::True => LoxType::new_bool(true),
+                TokenType::Nil => LoxType::Nil,
+                // 数字与字符串的值在扫描时已经解析好
+                _ => token.literal.unwrap(),
+            };
+            return Ok(Box::new(Literal::new(value)));
+        } else if self.match_types(vec![TokenType::This]) {
+            return Ok(Box::new(This::new(self.previous())));
+        } else if self.match_types(vec![TokenType::Super]) {
+            let keyword = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Box::new(Super::new(keyword, method)));
         } else if self.match_types(vec![TokenType::Identifier]) {
             return Ok(Box::new(Variable::new(self.previous())));
+        } else if self.match_types(vec![TokenType::Fun]) {
+            // 匿名函数以 fun 关键字作为名称 token
+            let keyword = self.previous();
+            let function = self.function_body(keyword, "function")?;
+            return Ok(Box::new(Lambda::new(function)));
+        } else if self.match_types(vec![TokenType::LeftBracket]) {
+            let mut elements = Vec::new();
+            if !self.check(TokenType::RightBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.match_types(vec![TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            let bracket =
+                self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+            return Ok(Box::new(ListLiteral::new(bracket, elements)));
+        } else if self.match_types(vec![TokenType::LeftBrace]) {
+            // 表达式位置上的 { 为字典字面量，语句位置上的 { 已在 statement 中作为代码块处理
+            return self.map_literal();
         } else if self.match_types(vec![TokenType::LeftParen]) {
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(Box::new(Grouping::new(expr)));
         }
-        Err(create_parse_error(self.peek(), "Expect expression."))
+        Err(ParseError::new(self.peek(), "Expect expression."))
+    }
+
+    fn map_literal(&mut self) -> Result<Box<dyn Expr>, ParseError> {
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        if !self.check(TokenType::RightBrace) {
+            loop {
+                let key = if self.match_types(vec![TokenType::String]) {
+                    self.previous().literal.unwrap().to_string()
+                } else if self.match_types(vec![TokenType::Identifier]) {
+                    self.previous().lexeme
+                } else {
+                    let message = "Expect string or identifier as map key.";
+                    return Err(ParseError::new(self.peek(), message));
+                };
+                self.consume(TokenType::Colon, "Expect ':' after map key.")?;
+                keys.push(key);
+                values.push(self.expression()?);
+                if !self.match_types(vec![TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let brace = self.consume(TokenType::RightBrace, "Expect '}' after map entries.")?;
+        Ok(Box::new(MapLiteral::new(brace, keys, values)))
     }
 
     fn synchronize(&mut self) {
@@ -388,10 +798,16 @@ impl Parser {
                 TokenType::Class
                 | TokenType::Fun
                 | TokenType::Var
+                | TokenType::Const
                 | TokenType::For
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
+                | TokenType::Write
+                | TokenType::Switch
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::Import
                 | TokenType::Return => {
                     return;
                 }
@@ -408,7 +824,7 @@ impl Parser {
                 return true;
             }
         }
-        return false;
+        false
     }
 
     fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ParseError> {
@@ -416,7 +832,7 @@ impl Parser {
             return Ok(self.advance());
         }
         let err_message = format!("Parser consume error: {}", message);
-        return Err(create_parse_error(self.peek(), err_message.as_str()));
+        Err(ParseError::new(self.peek(), err_message.as_str()))
     }
 
     fn check(&self, token_type: TokenType) -> bool {
@@ -426,11 +842,18 @@ impl Parser {
         self.peek().token_type == token_type
     }
 
-    fn is_at_end(&self) -> bool {
+    fn check_next(&self, token_type: TokenType) -> bool {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => token.token_type == token_type,
+            None => false,
+        }
+    }
+
+    pub fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::Eof
     }
 
-    fn peek(&self) -> &Token {
+    pub fn peek(&self) -> &Token {
         self.tokens
             .get(self.current)
             .expect("peek 没有获取到 token")
@@ -440,7 +863,7 @@ impl Parser {
         if !self.is_at_end() {
             self.current += 1;
         }
-        return self.previous();
+        self.previous()
     }
 
     fn previous(&self) -> Token {
@@ -453,7 +876,8 @@ impl Parser {
 
 #[cfg(test)]
 mod test {
-    use crate::scanner::LoxType;
+    use crate::ast::printer::AstPrinter;
+    use crate::scanner::{LoxType, Scanner};
 
     use super::*;
 
@@ -467,8 +891,9 @@ mod test {
                 1,
                 1,
                 Some(LoxType::new_str("1")),
+                None,
             ),
-            Token::new(TokenType::Plus, "+".to_string(), 1, 2, 2, None),
+            Token::new(TokenType::Plus, "+".to_string(), 1, 2, 2, None, None),
             Token::new(
                 TokenType::Number,
                 "2".to_string(),
@@ -476,9 +901,10 @@ mod test {
                 3,
                 3,
                 Some(LoxType::new_str("2")),
+                None,
             ),
-            Token::new(TokenType::Star, "*".to_string(), 1, 4, 4, None),
-            Token::new(TokenType::LeftParen, "(".to_string(), 1, 5, 5, None),
+            Token::new(TokenType::Star, "*".to_string(), 1, 4, 4, None, None),
+            Token::new(TokenType::LeftParen, "(".to_string(), 1, 5, 5, None, None),
             Token::new(
                 TokenType::Number,
                 "3".to_string(),
@@ -486,8 +912,9 @@ mod test {
                 6,
                 6,
                 Some(LoxType::new_str("3")),
+                None,
             ),
-            Token::new(TokenType::Minus, "-".to_string(), 1, 7, 7, None),
+            Token::new(TokenType::Minus, "-".to_string(), 1, 7, 7, None, None),
             Token::new(
                 TokenType::Number,
                 "4".to_string(),
@@ -495,16 +922,314 @@ mod test {
                 8,
                 8,
                 Some(LoxType::new_str("4")),
+                None,
             ),
-            Token::new(TokenType::RightParen, ")".to_string(), 1, 9, 9, None),
-            Token::new(TokenType::Eof, "".to_string(), 1, 10, 10, None),
+            Token::new(TokenType::RightParen, ")".to_string(), 1, 9, 9, None, None),
+            Token::new(TokenType::Eof, "".to_string(), 1, 10, 10, None, None),
         ];
         let mut parser = Parser::new(tokens);
-        let mut expr = parser.expression().unwrap();
+        let expr = parser.expression().unwrap();
         println!("{:?}", expr);
 
-        let mut printer = PrintExprVisitor;
-        expr.accept(&mut printer);
-        println!();
+        assert_eq!(
+            AstPrinter::print_expr(expr.as_ref()),
+            "(+ \"1\" (* \"2\" (group (- \"3\" \"4\"))))"
+        );
+    }
+
+    fn parse_source(source: &str) -> Vec<Box<dyn Stmt>> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn test_if_statement() {
+        let statements = parse_source("if (true) { print 1; }");
+        assert_eq!(statements.len(), 1);
+        let if_stmt = statements[0].as_any().downcast_ref::<If>().unwrap();
+        assert!(if_stmt.then_branch.as_any().is::<Block>());
+        assert!(if_stmt.else_branch.is_none());
+
+        let statements = parse_source("if (false) { print 1; } else { print 2; }");
+        let if_stmt = statements[0].as_any().downcast_ref::<If>().unwrap();
+        assert!(if_stmt.else_branch.as_ref().unwrap().as_any().is::<Block>());
+
+        // else if 链为嵌套的 If 节点
+        let statements = parse_source("if (a) print 1; else if (b) print 2; else print 3;");
+        let if_stmt = statements[0].as_any().downcast_ref::<If>().unwrap();
+        let else_if = if_stmt.else_branch.as_ref().unwrap();
+        let else_if = else_if.as_any().downcast_ref::<If>().unwrap();
+        assert!(else_if.then_branch.as_any().is::<Print>());
+        assert!(else_if.else_branch.as_ref().unwrap().as_any().is::<Print>());
+    }
+
+    #[test]
+    fn test_while_statement() {
+        let statements = parse_source("while (i < 10) { i = i + 1; }");
+        assert_eq!(statements.len(), 1);
+        let while_stmt = statements[0].as_any().downcast_ref::<While>().unwrap();
+        assert!(while_stmt.condition.as_any().is::<Binary>());
+        assert!(while_stmt.body.as_any().is::<Block>());
+    }
+
+    #[test]
+    fn test_block_statement() {
+        let statements = parse_source("{ var a = 1; { var a = 2; print a; } }");
+        assert_eq!(statements.len(), 1);
+        let block = statements[0].as_any().downcast_ref::<Block>().unwrap();
+        assert_eq!(block.statements.len(), 2);
+        assert!(block.statements[0].as_any().is::<Var>());
+        let inner = block.statements[1]
+            .as_any()
+            .downcast_ref::<Block>()
+            .unwrap();
+        assert_eq!(inner.statements.len(), 2);
+    }
+
+    #[test]
+    fn test_for_statement_desugar() {
+        let statements = parse_source("for (var i = 0; i < 3; i = i + 1) print i;");
+        let block = statements[0].as_any().downcast_ref::<Block>().unwrap();
+        assert!(block.statements[0].as_any().is::<Var>());
+        let while_stmt = block.statements[1]
+            .as_any()
+            .downcast_ref::<While>()
+            .unwrap();
+        assert!(while_stmt.body.as_any().is::<Print>());
+        assert!(while_stmt.increment.is_some());
+
+        // 省略全部子句时条件为 true，且不包裹额外的 Block
+        let statements = parse_source("for (;;) print 1;");
+        let while_stmt = statements[0].as_any().downcast_ref::<While>().unwrap();
+        let condition = while_stmt
+            .condition
+            .as_any()
+            .downcast_ref::<Literal>()
+            .unwrap();
+        assert_eq!(condition.value, LoxType::new_bool(true));
+        assert!(while_stmt.body.as_any().is::<Print>());
+    }
+
+    #[test]
+    fn test_function_declaration() {
+        let statements = parse_source("fun add(a, b) { return a + b; } add(1, 2);");
+        assert_eq!(statements.len(), 2);
+        let function = statements[0]
+            .as_any()
+            .downcast_ref::<crate::ast::Function>()
+            .unwrap();
+        assert_eq!(function.name.lexeme, "add");
+        assert_eq!(function.params.len(), 2);
+        assert_eq!(function.body.len(), 1);
+
+        let expression = statements[1].as_any().downcast_ref::<Expression>().unwrap();
+        let call = expression
+            .expression
+            .as_any()
+            .downcast_ref::<Call>()
+            .unwrap();
+        assert!(call.callee.as_any().is::<Variable>());
+        assert_eq!(call.arguments.len(), 2);
+    }
+
+    #[test]
+    fn test_return_statement() {
+        let statements = parse_source("fun f() { return; return 1; }");
+        let function = statements[0]
+            .as_any()
+            .downcast_ref::<crate::ast::Function>()
+            .unwrap();
+        let bare = function.body[0].as_any().downcast_ref::<Return>().unwrap();
+        assert!(bare.value.is_none());
+        let valued = function.body[1].as_any().downcast_ref::<Return>().unwrap();
+        assert!(valued.value.as_ref().unwrap().as_any().is::<Literal>());
+    }
+
+    #[test]
+    fn test_break_continue_statement() {
+        let statements = parse_source("while (true) { break; continue; }");
+        let while_stmt = statements[0].as_any().downcast_ref::<While>().unwrap();
+        let body = while_stmt.body.as_any().downcast_ref::<Block>().unwrap();
+        assert!(body.statements[0].as_any().is::<Break>());
+        assert!(body.statements[1].as_any().is::<Continue>());
+
+        let tokens = Scanner::new("break;".to_string(), None).scan_tokens();
+        assert!(Parser::new(tokens).statement().is_err());
+
+        // 循环内定义的函数同样不能跳出外层循环
+        let tokens =
+            Scanner::new("while (true) { fun f() { continue; } }".to_string(), None).scan_tokens();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+
+    #[test]
+    fn test_compound_assignment_desugar() {
+        let statements = parse_source("x -= 1 + 2;");
+        let expression = statements[0].as_any().downcast_ref::<Expression>().unwrap();
+        let assign = expression
+            .expression
+            .as_any()
+            .downcast_ref::<Assign>()
+            .unwrap();
+        assert_eq!(assign.name.lexeme, "x");
+        let binary = assign.value.as_any().downcast_ref::<Binary>().unwrap();
+        assert_eq!(binary.operator.token_type, TokenType::Minus);
+        assert_eq!(binary.operator.lexeme, "-");
+        // 左侧变量先求值，右侧表达式整体作为右操作数
+        let left = binary.left.as_any().downcast_ref::<Variable>().unwrap();
+        assert_eq!(left.name.lexeme, "x");
+        assert!(binary.right.as_any().is::<Binary>());
+
+        let statements = parse_source("obj.x += 1; list[0] *= 2;");
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(; (.+= obj x 1))"
+        );
+        assert_eq!(
+            AstPrinter::print_stmt(statements[1].as_ref()),
+            "(; ([]*= list 0 2))"
+        );
+        let tokens = Scanner::new("1 += 2;".to_string(), None).scan_tokens();
+        let error = Parser::new(tokens).parse().unwrap_err().remove(0);
+        assert!(error.message.ends_with("Invalid assignment target."));
+    }
+
+    #[test]
+    fn test_class_declaration() {
+        let statements = parse_source("class Counter { increment() { } get() { return 1; } }");
+        let class = statements[0].as_any().downcast_ref::<Class>().unwrap();
+        assert_eq!(class.name.lexeme, "Counter");
+        let method_names = class
+            .methods
+            .iter()
+            .map(|method| method.name.lexeme.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(method_names, vec!["increment", "get"]);
+
+        let statements = parse_source("counter.count = counter.count;");
+        let expression = statements[0].as_any().downcast_ref::<Expression>().unwrap();
+        let set = expression
+            .expression
+            .as_any()
+            .downcast_ref::<Set>()
+            .unwrap();
+        assert_eq!(set.name.lexeme, "count");
+        assert!(set.object.as_any().is::<Variable>());
+        assert!(set.value.as_any().is::<Get>());
+
+        let statements = parse_source("class B < A { f() { return super.f; } }");
+        let class = statements[0].as_any().downcast_ref::<Class>().unwrap();
+        let superclass = class.superclass.as_ref().unwrap();
+        let superclass = superclass.as_any().downcast_ref::<Variable>().unwrap();
+        assert_eq!(superclass.name.lexeme, "A");
+        let body = &class.methods[0].body;
+        let return_stmt = body[0].as_any().downcast_ref::<Return>().unwrap();
+        let value = return_stmt.value.as_ref().unwrap();
+        assert_eq!(
+            value
+                .as_any()
+                .downcast_ref::<Super>()
+                .unwrap()
+                .method
+                .lexeme,
+            "f"
+        );
+    }
+
+    #[test]
+    fn test_var_list_declaration() {
+        let tokens = Scanner::new("var [a, b, ...c] = list;".to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(var [a b ...c] list)"
+        );
+
+        let parse_error = |source: &str| {
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            Parser::new(tokens).parse().unwrap_err().remove(0).message
+        };
+        assert!(
+            parse_error("var [...a, b] = x;").ends_with("Expect ']' after destructuring pattern.")
+        );
+        assert!(parse_error("var [a];").ends_with("Expect '=' after destructuring pattern."));
+        assert!(parse_error("var [] = x;").ends_with("Expect variable name."));
+    }
+
+    #[test]
+    fn test_switch_statement() {
+        let tokens = Scanner::new(
+            "switch (x) { case 1: print a; print b; case \"s\": default: print c; }".to_string(),
+            None,
+        )
+        .scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(switch x\n  (case 1\n    (print a)\n    (print b))\n  (case \"s\")\n  (default\n    (print c)))"
+        );
+
+        let parse_error = |source: &str| {
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            Parser::new(tokens).parse().unwrap_err().remove(0).message
+        };
+        assert!(
+            parse_error("switch (x) { default: case 1: }")
+                .ends_with("Default case must be the last case in a switch.")
+        );
+        assert!(
+            parse_error("switch (x) { print x; }")
+                .ends_with("Expect 'case' or 'default' in switch.")
+        );
+        assert!(
+            parse_error("switch (x) { case 1 print x; }").ends_with("Expect ':' after case value.")
+        );
+    }
+
+    #[test]
+    fn test_try_statement() {
+        let tokens =
+            Scanner::new("try { throw 1; } catch (e) { print e; }".to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(try\n  (throw 1)\n  (catch e\n    (print e)))"
+        );
+
+        let parse_error = |source: &str| {
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            Parser::new(tokens).parse().unwrap_err().remove(0).message
+        };
+        assert!(parse_error("try { }").ends_with("Expect 'catch' after try block."));
+        assert!(parse_error("try { } catch { }").ends_with("Expect '(' after 'catch'."));
+        assert!(parse_error("throw 1").ends_with("Expect ';' after throw value."));
+    }
+
+    #[test]
+    fn test_import_statement() {
+        let tokens = Scanner::new("import \"lib/math.lox\";".to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        assert_eq!(
+            AstPrinter::print_stmt(statements[0].as_ref()),
+            "(import \"lib/math.lox\")"
+        );
+
+        let parse_error = |source: &str| {
+            let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+            Parser::new(tokens).parse().unwrap_err().remove(0).message
+        };
+        assert!(parse_error("import math;").ends_with("Expect file path after 'import'."));
+        assert!(parse_error("import \"a.lox\"").ends_with("Expect ';' after import path."));
+    }
+
+    #[test]
+    fn test_parse_collects_errors() {
+        let tokens = Scanner::new("var = 1;\nprint 1;\nprint (2;".to_string(), None).scan_tokens();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.ends_with("Expect variable name"));
+        assert_eq!(errors[0].line(), 1);
+        assert!(errors[1].message.ends_with("Expect ')' after expression."));
+        assert_eq!(errors[1].line(), 3);
     }
 }
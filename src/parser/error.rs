@@ -2,30 +2,70 @@ use crate::scanner::Token;
 use std::error::Error;
 use std::fmt;
 use std::fmt::Formatter;
-use crate::prompt::Prompt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ParseError {
-    message: String,
+    line: usize,
+    col_start: usize,
+    col_end: usize,
+    pub message: String,
 }
 
 impl ParseError {
-    pub fn new(message: &str) -> Self {
+    pub fn new(token: &Token, message: &str) -> Self {
         ParseError {
+            line: token.line,
+            col_start: token.col_start,
+            col_end: token.col_end,
             message: message.to_string(),
         }
     }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.col_start
+    }
+
+    pub fn column_end(&self) -> usize {
+        self.col_end
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.message)
+        write!(
+            f,
+            "[{}:{}] Error: {}",
+            self.line, self.col_start, self.message
+        )
     }
 }
 
 impl Error for ParseError {}
 
-pub fn create_parse_error(token: &Token, message: &str) -> ParseError {
-    Prompt::error(token, message);
-    ParseError::new(message)
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::TokenType;
+
+    #[test]
+    fn test_parse_error_position() {
+        let token = Token::new(
+            TokenType::Identifier,
+            "name".to_string(),
+            5,
+            10,
+            14,
+            None,
+            None,
+        );
+        let error = ParseError::new(&token, "Expect ';' after value.");
+        assert_eq!(error.line(), 5);
+        assert_eq!(error.column(), 10);
+        assert_eq!(error.column_end(), 14);
+        assert_eq!(error.to_string(), "[5:10] Error: Expect ';' after value.");
+    }
 }
@@ -1,32 +1,249 @@
 use crate::scanner::Token;
+use std::io;
+use std::io::{IsTerminal, Write};
 
-pub struct Prompt {}
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+// 负责错误信息的排版，启用颜色时行号为青色、指示符为红色、错误信息为粗体
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Prompt {
+    colors: bool,
+}
 
 impl Prompt {
-    pub fn error_by_line(line: usize, source: &str, column: usize, message: &str) {
+    pub fn with_colors(colors: bool) -> Self {
+        Prompt { colors }
+    }
+
+    // 标准错误是终端且没有设置 NO_COLOR 时才默认启用颜色
+    pub fn colors_supported() -> bool {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        !no_color && io::stderr().is_terminal()
+    }
+
+    fn paint(&self, color: &str, text: &str) -> String {
+        if self.colors {
+            format!("{}{}{}", color, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    pub fn error_by_line(
+        &self,
+        output: &mut dyn Write,
+        line: usize,
+        source: &str,
+        column: usize,
+        message: &str,
+    ) -> io::Result<()> {
+        self.error_with_underline(output, line, source, column, column + 1, message)
+    }
+
+    // 用 ^~~~ 标出 [col_start, col_end) 范围，便于看清出错 token 的完整跨度
+    pub fn error_with_underline(
+        &self,
+        output: &mut dyn Write,
+        line: usize,
+        source: &str,
+        col_start: usize,
+        col_end: usize,
+        message: &str,
+    ) -> io::Result<()> {
+        let pointer_spacing = self.excerpt(output, line, source, col_start, col_end)?;
+        let message = format!("Error: {}", message);
+        writeln!(output, "{}{}", pointer_spacing, self.paint(BOLD, &message))
+    }
+
+    // 输出源码行与指示符，返回指示符前的缩进，便于在其下方对齐输出
+    fn excerpt(
+        &self,
+        output: &mut dyn Write,
+        line: usize,
+        source: &str,
+        col_start: usize,
+        col_end: usize,
+    ) -> io::Result<String> {
         let line_indicator = format!("{} |", line);
-        eprintln!("{}{}", line_indicator, source);
+        writeln!(output, "{}{}", self.paint(CYAN, &line_indicator), source)?;
         let line_indicator_len = line_indicator.len();
-        let pointer_spacing = " ".repeat(line_indicator_len + column);
-        eprintln!("{}^", pointer_spacing);
-        eprintln!("{}Error: {}", pointer_spacing, message);
-    }
-
-    pub fn error(token: &Token, message: &str) {
-        if token.token_type == crate::scanner::TokenType::Eof {
-            Prompt::error_by_line(
-                token.line,
-                "<end of file>",
-                0,
-                &format!("at end {}", message),
-            );
+        let pointer_spacing = " ".repeat(line_indicator_len + col_start);
+        let underline = "~".repeat(col_end.saturating_sub(col_start).saturating_sub(1));
+        let pointer = format!("^{}", underline);
+        writeln!(output, "{}{}", pointer_spacing, self.paint(RED, &pointer))?;
+        Ok(pointer_spacing)
+    }
+
+    // 输出 token 所在的源码行，并指向 token 的起始列
+    // token 来自文件时先输出 file:line:col: error: message 形式的位置信息，列号从 1 开始
+    pub fn error(
+        &self,
+        output: &mut dyn Write,
+        source: &str,
+        token: &Token,
+        message: &str,
+    ) -> io::Result<()> {
+        let line_source = Prompt::source_line(source, token.line);
+        // 字节码虚拟机的错误只有行号，没有对应的 token 文本
+        let message = if token.token_type == crate::scanner::TokenType::Eof {
+            format!("at end {}", message)
+        } else if token.lexeme.is_empty() {
+            message.to_string()
         } else {
-            Prompt::error_by_line(
-                token.line,
-                &token.lexeme,
-                0,
-                &format!("at '{}' {}", token.lexeme, message),
-            );
+            format!("at '{}' {}", token.lexeme, message)
+        };
+        match &token.source_file {
+            Some(file) => {
+                let column = token.col_start + 1;
+                let location = format!("{}:{}:{}: error: {}", file, token.line, column, message);
+                writeln!(output, "{}", self.paint(BOLD, &location))?;
+                let (start, end) = (token.col_start, token.col_end);
+                self.excerpt(output, token.line, line_source, start, end)?;
+                Ok(())
+            }
+            None => self.error_by_line(output, token.line, line_source, token.col_start, &message),
+        }
+    }
+
+    // 由内向外输出调用栈，frames 按由外向内的顺序排列
+    pub fn call_stack(&self, output: &mut dyn Write, frames: &[String]) -> io::Result<()> {
+        writeln!(output, "Call stack (most recent call first):")?;
+        // 连续重复的调用帧（例如递归）合并为一行
+        let mut frames = frames.iter().rev().peekable();
+        while let Some(frame) = frames.next() {
+            let mut repeated = 1;
+            while frames.next_if_eq(&frame).is_some() {
+                repeated += 1;
+            }
+            if repeated > 1 {
+                writeln!(output, "  in {} (repeated {} times)", frame, repeated)?;
+            } else {
+                writeln!(output, "  in {}", frame)?;
+            }
         }
+        Ok(())
+    }
+
+    pub fn source_line(source: &str, line: usize) -> &str {
+        source.lines().nth(line.saturating_sub(1)).unwrap_or("")
+    }
+
+    // 统计尚未闭合的括号层数，字符串与注释中的括号不计入
+    pub fn bracket_depth(source: &str) -> isize {
+        let mut depth = 0;
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                '"' => {
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\\' => {
+                                chars.next();
+                            }
+                            '"' => break,
+                            _ => {}
+                        }
+                    }
+                }
+                '/' if chars.peek() == Some(&'/') => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        depth
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_error_with_underline() {
+        let mut output = Vec::new();
+        Prompt::default()
+            .error_with_underline(&mut output, 1, "var foo = 1", 4, 7, "Bad name.")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "1 |var foo = 1\n       ^~~\n       Error: Bad name.\n"
+        );
+
+        // 单字符或空跨度只输出 ^
+        let mut output = Vec::new();
+        Prompt::default()
+            .error_with_underline(&mut output, 2, "x", 0, 0, "At end.")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "2 |x\n   ^\n   Error: At end.\n"
+        );
+    }
+
+    #[test]
+    fn test_error_with_source_file() {
+        let token = Token::new(
+            crate::scanner::TokenType::Identifier,
+            "foo".to_string(),
+            2,
+            6,
+            9,
+            None,
+            Some("main.lox".into()),
+        );
+        let mut output = Vec::new();
+        Prompt::default()
+            .error(&mut output, "var a;\nprint foo;", &token, "Undefined.")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "main.lox:2:7: error: at 'foo' Undefined.\n2 |print foo;\n         ^~~\n"
+        );
+    }
+
+    #[test]
+    fn test_bracket_depth() {
+        assert_eq!(Prompt::bracket_depth("print 1;"), 0);
+        assert_eq!(Prompt::bracket_depth("while (i < 3) {"), 1);
+        assert_eq!(Prompt::bracket_depth("f([1, {\"a\": 2"), 3);
+        assert_eq!(Prompt::bracket_depth("print \"({[\\\"\";"), 0);
+        assert_eq!(Prompt::bracket_depth("{ // }\n"), 1);
+        assert_eq!(Prompt::bracket_depth("}"), -1);
+    }
+
+    #[test]
+    fn test_error_with_colors() {
+        let mut output = Vec::new();
+        let prompt = Prompt::with_colors(true);
+        prompt
+            .error_by_line(&mut output, 3, "print @;", 6, "Unexpected character.")
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "\x1b[36m3 |\x1b[0mprint @;\n         \x1b[31m^\x1b[0m\n         \x1b[1mError: Unexpected character.\x1b[0m\n"
+        );
+        assert!(output.contains("\x1b[31m^"));
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let frames = ["<script>", "main", "f", "f", "f"].map(String::from);
+        let mut output = Vec::new();
+        Prompt::default().call_stack(&mut output, &frames).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Call stack (most recent call first):\n  in f (repeated 3 times)\n  in main\n  in <script>\n"
+        );
     }
 }
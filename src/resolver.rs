@@ -0,0 +1,524 @@
+pub mod error;
+
+use crate::ast::interpreter::Interpreter;
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::resolver::error::ResolveError;
+use crate::scanner::token::LoxInterrupt;
+use crate::scanner::{LoxType, Token};
+use std::collections::HashMap;
+use std::mem;
+
+// 当前正在解析的函数的类型，用于检查 this 是否直接位于方法体内
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionType {
+    None,
+    Function,
+    Method,
+}
+
+// 当前正在解析的类的类型，用于检查 this 与 super 的使用位置
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassType {
+    None,
+    Class,
+    Subclass,
+}
+
+// 在解释执行之前遍历语法树，计算每个局部变量引用所在的作用域深度
+pub struct Resolver<'a> {
+    interpreter: &'a mut Interpreter,
+    // 每一层作用域中变量名到“是否已完成定义”的映射，全局作用域不在其中
+    scopes: Vec<HashMap<String, bool>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    // 顶层代码所在的作用域层数，被导入的文件的顶层位于一层模块作用域中
+    top_level_depth: usize,
+    errors: Vec<ResolveError>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(interpreter: &'a mut Interpreter) -> Self {
+        Resolver {
+            interpreter,
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            top_level_depth: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn errors(&self) -> &[ResolveError] {
+        &self.errors
+    }
+
+    pub fn resolve(&mut self, statements: &[Box<dyn Stmt>]) {
+        for statement in statements {
+            self.resolve_stmt(statement.as_ref());
+        }
+    }
+
+    // 被导入的文件在独立的环境中执行，顶层定义按局部变量解析
+    pub fn resolve_module(&mut self, statements: &[Box<dyn Stmt>]) {
+        self.begin_scope();
+        let enclosing_depth = mem::replace(&mut self.top_level_depth, self.scopes.len());
+        self.resolve(statements);
+        self.top_level_depth = enclosing_depth;
+        self.end_scope();
+    }
+
+    fn resolve_stmt(&mut self, stmt: &dyn Stmt) {
+        // Resolver 的访问方法不会产生中断，返回值可以直接忽略
+        let _ = stmt.accept(self);
+    }
+
+    pub fn resolve_expr(&mut self, expr: &dyn Expr) {
+        let _ = expr.accept(self);
+    }
+
+    fn resolve_local(&mut self, expr: &dyn Expr, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.interpreter.resolve(expr, Some(depth));
+                return;
+            }
+        }
+        // 未在任何局部作用域中找到，视为全局变量
+        self.interpreter.resolve(expr, None);
+    }
+
+    fn resolve_function(&mut self, function: &Function, function_type: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = function_type;
+        self.begin_scope();
+        for param in function.params.iter() {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(&function.body);
+        self.end_scope();
+        self.current_function = enclosing_function;
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.contains_key(&name.lexeme) {
+            let message = "Already a variable with this name in this scope.";
+            self.errors.push(ResolveError::new(name, message));
+            return;
+        }
+        scope.insert(name.lexeme.clone(), false);
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+}
+
+impl ExprVisitor for Resolver<'_> {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.value.as_ref());
+        self.resolve_local(expr, &expr.name);
+        Ok(LoxType::Nil)
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.left.as_ref());
+        self.resolve_expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.expression.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn literal_visit(&mut self, _expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        Ok(LoxType::Nil)
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.left.as_ref());
+        self.resolve_expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn ternary_visit(&mut self, expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.condition.as_ref());
+        self.resolve_expr(expr.then_branch.as_ref());
+        self.resolve_expr(expr.else_branch.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_function(&expr.function, FunctionType::Function);
+        Ok(LoxType::Nil)
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        for element in expr.elements.iter() {
+            self.resolve_expr(element.as_ref());
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.object.as_ref());
+        self.resolve_expr(expr.index.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.value.as_ref());
+        self.resolve_expr(expr.object.as_ref());
+        self.resolve_expr(expr.index.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        for value in expr.values.iter() {
+            self.resolve_expr(value.as_ref());
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.right.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        if let Some(scope) = self.scopes.last()
+            && scope.get(&expr.name.lexeme) == Some(&false)
+        {
+            let message = "Can't read local variable in its own initializer.";
+            self.errors.push(ResolveError::new(&expr.name, message));
+        }
+        self.resolve_local(expr, &expr.name);
+        Ok(LoxType::Nil)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.callee.as_ref());
+        for argument in expr.arguments.iter() {
+            self.resolve_expr(argument.as_ref());
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.object.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        self.resolve_expr(expr.value.as_ref());
+        self.resolve_expr(expr.object.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        match self.current_class {
+            ClassType::None => {
+                let message = "Can't use 'super' outside of a class.";
+                self.errors.push(ResolveError::new(&expr.keyword, message));
+            }
+            ClassType::Class => {
+                let message = "Can't use 'super' in a class with no superclass.";
+                self.errors.push(ResolveError::new(&expr.keyword, message));
+            }
+            ClassType::Subclass => {}
+        }
+        self.resolve_local(expr, &expr.keyword);
+        Ok(LoxType::Nil)
+    }
+
+    fn this_visit(&mut self, expr: &This) -> Result<LoxType, LoxInterrupt> {
+        if self.current_class == ClassType::None {
+            let message = "Can't use 'this' outside of a class.";
+            self.errors.push(ResolveError::new(&expr.keyword, message));
+        } else if self.current_function != FunctionType::Method {
+            let message = "Can't use 'this' inside a nested function.";
+            self.errors.push(ResolveError::new(&expr.keyword, message));
+        }
+        self.resolve_local(expr, &expr.keyword);
+        Ok(LoxType::Nil)
+    }
+}
+
+impl StmtVisitor for Resolver<'_> {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.expression.as_ref());
+        Ok(None)
+    }
+
+    fn if_visit(&mut self, stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.condition.as_ref());
+        self.resolve_stmt(stmt.then_branch.as_ref());
+        if let Some(else_branch) = stmt.else_branch.as_ref() {
+            self.resolve_stmt(else_branch.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.begin_scope();
+        self.resolve(&stmt.statements);
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.expression.as_ref());
+        Ok(None)
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.declare(&stmt.name);
+        self.resolve_expr(stmt.initializer.as_ref());
+        self.define(&stmt.name);
+        Ok(None)
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.initializer.as_ref());
+        for name in stmt.names.iter().chain(stmt.rest.as_ref()) {
+            self.declare(name);
+            self.define(name);
+        }
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.declare(&stmt.name);
+        self.resolve_expr(stmt.initializer.as_ref());
+        self.define(&stmt.name);
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.condition.as_ref());
+        self.resolve_stmt(stmt.body.as_ref());
+        if let Some(increment) = stmt.increment.as_ref() {
+            self.resolve_expr(increment.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        // 循环变量位于包裹循环体的独立作用域中
+        self.resolve_expr(stmt.iterable.as_ref());
+        self.begin_scope();
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_stmt(stmt.body.as_ref());
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        // 先定义函数名，使函数体内可以递归引用自身
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve_function(stmt, FunctionType::Function);
+        Ok(None)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        if self.current_function == FunctionType::None {
+            let message = "Can't return from top-level code.";
+            self.errors.push(ResolveError::new(&stmt.keyword, message));
+        }
+        if let Some(value) = stmt.value.as_ref() {
+            self.resolve_expr(value.as_ref());
+        }
+        Ok(None)
+    }
+
+    fn break_visit(&mut self, _stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        Ok(None)
+    }
+
+    fn continue_visit(&mut self, _stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        Ok(None)
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.scrutinee.as_ref());
+        for (case, body) in stmt.cases.iter() {
+            self.resolve_expr(case.as_ref());
+            self.begin_scope();
+            self.resolve(body);
+            self.end_scope();
+        }
+        if let Some(body) = &stmt.default_case {
+            self.begin_scope();
+            self.resolve(body);
+            self.end_scope();
+        }
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.begin_scope();
+        self.resolve(&stmt.body);
+        self.end_scope();
+        // 捕获变量位于包裹处理代码的独立作用域中
+        self.begin_scope();
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        self.resolve(&stmt.handler);
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.resolve_expr(stmt.value.as_ref());
+        Ok(None)
+    }
+
+    // 被导入的文件在运行时单独解析，导入的名称定义为全局变量，因此只能在顶层导入
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        if self.scopes.len() != self.top_level_depth {
+            let message = "Can't import outside of top-level code.";
+            self.errors.push(ResolveError::new(&stmt.keyword, message));
+        }
+        Ok(None)
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+        self.declare(&stmt.name);
+        self.define(&stmt.name);
+        if let Some(superclass) = &stmt.superclass {
+            if let Some(variable) = superclass.as_any().downcast_ref::<Variable>()
+                && variable.name.lexeme == stmt.name.lexeme
+            {
+                let message = "A class can't inherit from itself.";
+                self.errors.push(ResolveError::new(&variable.name, message));
+            }
+            self.current_class = ClassType::Subclass;
+            self.resolve_expr(superclass.as_ref());
+            // 与解释器保持一致，为 super 单独开一层作用域
+            self.begin_scope();
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.insert("super".to_string(), true);
+            }
+        }
+        // 方法调用时 this 定义在方法闭包与参数环境之间
+        self.begin_scope();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert("this".to_string(), true);
+        }
+        for method in stmt.methods.iter() {
+            self.resolve_function(method, FunctionType::Method);
+        }
+        self.end_scope();
+        if stmt.superclass.is_some() {
+            self.end_scope();
+        }
+        self.current_class = enclosing_class;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve_error_count(source: &str) -> usize {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(&statements);
+        resolver.errors().len()
+    }
+
+    #[test]
+    fn test_resolver_errors() {
+        let error_count = resolve_error_count("var a = 1; { var b = 2; print a + b; }");
+        assert_eq!(error_count, 0);
+
+        let error_count = resolve_error_count("{ var a = a; }");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("fun f() { var a = 1; var a = 2; }");
+        assert_eq!(error_count, 1);
+
+        // 全局作用域允许重复定义同名变量
+        let error_count = resolve_error_count("var a = 1; var a = a;");
+        assert_eq!(error_count, 0);
+    }
+
+    #[test]
+    fn test_resolver_super_errors() {
+        let error_count = resolve_error_count("class A {} class B < A { f() { return super.f; } }");
+        assert_eq!(error_count, 0);
+
+        let error_count = resolve_error_count("class A < A {}");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("fun f() { return super.f; }");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("class A { f() { return super.f; } }");
+        assert_eq!(error_count, 1);
+    }
+
+    #[test]
+    fn test_resolver_this_errors() {
+        let error_count = resolve_error_count("class A { f() { if (true) { return this; } } }");
+        assert_eq!(error_count, 0);
+
+        let error_count = resolve_error_count("print this;");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("fun f() { return this; }");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("class A { f() { fun g() { return this; } } }");
+        assert_eq!(error_count, 1);
+    }
+
+    #[test]
+    fn test_resolver_return_errors() {
+        let error_count =
+            resolve_error_count("fun f() { { return 1; } } var g = fun () { return; };");
+        assert_eq!(error_count, 0);
+
+        let error_count = resolve_error_count("return;");
+        assert_eq!(error_count, 1);
+
+        let error_count = resolve_error_count("if (true) { return 1; } while (true) return;");
+        assert_eq!(error_count, 2);
+    }
+
+    #[test]
+    fn test_resolver_import_errors() {
+        assert_eq!(resolve_error_count("import \"a.lox\";"), 0);
+        let error_count =
+            resolve_error_count("{ import \"a.lox\"; } fun f() { import \"a.lox\"; }");
+        assert_eq!(error_count, 2);
+    }
+}
@@ -0,0 +1,27 @@
+use crate::scanner::Token;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone)]
+pub struct ResolveError {
+    pub token: Token,
+    pub message: String,
+}
+
+impl ResolveError {
+    pub fn new(token: &Token, message: &str) -> Self {
+        ResolveError {
+            token: token.clone(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ResolveError {}
@@ -1,53 +1,58 @@
-mod error;
+pub mod error;
 pub mod token;
 
-use crate::prompt::Prompt;
-use error::Error;
+use error::ScanError;
+use std::collections::VecDeque;
+use std::rc::Rc;
 
 // 重导出
 pub use token::{LoxType, Token, TokenType};
 
 pub struct Scanner {
     source: String,
-    tokens: Vec<Token>,
+    // 源码所在的文件路径，交互模式下为 None
+    source_file: Option<Rc<str>>,
+    // 已扫描但尚未被取走的 token，一个字符序列可能不产生 token（如注释）
+    tokens: VecDeque<Token>,
+    // 是否已经产出 Eof，产出之后迭代结束
+    finished: bool,
     start: usize,
     current: usize,
     line: usize,
     column: usize,
-    error: Option<Error>,
+    errors: Vec<ScanError>,
 }
 
 impl Scanner {
-    pub fn new(source: String) -> Self {
+    pub fn new(source: String, file_path: Option<&str>) -> Self {
         Scanner {
             source,
-            tokens: Vec::new(),
+            source_file: file_path.map(Rc::from),
+            tokens: VecDeque::new(),
+            finished: false,
             start: 0,
             current: 0,
             line: 1,
             column: 0,
-            error: None,
+            errors: Vec::new(),
         }
     }
 
-    pub fn get_line(&self) -> usize {
-        self.line
+    pub fn errors(&self) -> &[ScanError] {
+        &self.errors
+    }
+
+    #[allow(dead_code)]
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
     }
 
     pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
-        }
-        self.tokens.push(Token::new(
-            TokenType::Eof,
-            "".to_string(),
-            self.line,
-            self.column,
-            self.column + 1,
-            None,
-        ));
-        self.tokens.clone()
+        self.by_ref().collect()
     }
 
     fn is_at_end(&self) -> bool {
@@ -62,13 +67,49 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
-            '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            '.' => {
+                if self.peek() == '.' && self.peek_next() == '.' {
+                    self.match_char('.');
+                    self.match_char('.');
+                    self.add_token(TokenType::Ellipsis)
+                } else {
+                    self.add_token(TokenType::Dot)
+                }
+            }
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '%' => self.add_token(TokenType::Percent),
+            '?' => self.add_token(TokenType::Question),
+            ':' => self.add_token(TokenType::Colon),
             // Double-character tokens.
+            '-' => {
+                let match_quote = self.match_char('=');
+                self.add_token(if match_quote {
+                    TokenType::MinusEqual
+                } else {
+                    TokenType::Minus
+                })
+            }
+            '+' => {
+                let match_quote = self.match_char('=');
+                self.add_token(if match_quote {
+                    TokenType::PlusEqual
+                } else {
+                    TokenType::Plus
+                })
+            }
+            '*' => {
+                // ** 需要先于单个 * 匹配
+                if self.match_char('*') {
+                    self.add_token(TokenType::StarStar)
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::StarEqual)
+                } else {
+                    self.add_token(TokenType::Star)
+                }
+            }
             '!' => {
                 let match_quote = self.match_char('=');
                 self.add_token(if match_quote {
@@ -108,6 +149,10 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                } else if self.match_char('*') {
+                    self.block_comment();
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -132,24 +177,19 @@ impl Scanner {
                     self.identifier();
                     return;
                 }
-                self.error = Some(Error {
+                self.errors.push(ScanError {
                     line: self.line,
-                    column: self.current - 1,
+                    column: self.column - 1,
                     message: format!("Unexpected character: {}", c),
                 });
-                Prompt::error_by_line(
-                    self.line,
-                    &self.source,
-                    self.current - 1,
-                    &format!("Unexpected character: {}", c),
-                );
             }
         }
     }
 
+    // start 与 current 均为字节偏移，按 UTF-8 编码长度前进，避免每次从头遍历字符
     fn advance(&mut self) -> char {
-        let c = self.source.chars().nth(self.current).unwrap();
-        self.current += 1;
+        let c = self.peek();
+        self.current += c.len_utf8();
         self.column += 1;
         c
     }
@@ -158,85 +198,143 @@ impl Scanner {
         if self.is_at_end() {
             return false;
         }
-        if self.source.chars().nth(self.current).unwrap() != expected {
+        if self.peek() != expected {
             return false;
         }
-        self.current += 1;
+        self.current += expected.len_utf8();
         self.column += 1;
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current).unwrap()
+        self.source[self.current..].chars().next().unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
-        }
-        self.source.chars().nth(self.current + 1).unwrap()
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
     }
 
     fn add_token(&mut self, token_type: TokenType) {
-        let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(
-            token_type,
-            text.to_string(),
-            self.line,
-            self.column,
-            self.column + text.len(),
-            None,
-        ));
+        self.add_token_with_literal(token_type, None);
     }
 
     fn add_token_with_literal(&mut self, token_type: TokenType, literal: Option<LoxType>) {
         let text = &self.source[self.start..self.current];
-        self.tokens.push(Token::new(
+        self.tokens.push_back(Token::new(
             token_type,
             text.to_string(),
             self.line,
+            // 此时 column 已经位于 token 末尾，多行字符串换行后列号会被重置
+            self.column.saturating_sub(text.chars().count()),
             self.column,
-            self.column + text.len(),
             literal,
+            self.source_file.clone(),
         ));
     }
 
+    fn block_comment(&mut self) {
+        // 块注释允许嵌套，记录当前的嵌套层数
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                self.errors.push(ScanError {
+                    line: self.line,
+                    column: self.column,
+                    message: "Unterminated block comment.".to_string(),
+                });
+                return;
+            }
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.advance();
+                self.advance();
+                depth += 1;
+            } else if self.peek() == '*' && self.peek_next() == '/' {
+                self.advance();
+                self.advance();
+                depth -= 1;
+            } else if self.advance() == '\n' {
+                self.line += 1;
+                self.column = 0;
+            }
+        }
+    }
+
     fn string(&mut self) {
+        let mut value = String::new();
         while self.peek() != '"' && !self.is_at_end() {
             if self.peek() == '\n' {
                 self.line += 1;
                 self.column = 0;
             }
-            self.advance();
+            let c = self.advance();
+            if c != '\\' {
+                value.push(c);
+            } else if let Some(escaped) = self.escape_sequence() {
+                value.push(escaped);
+            }
         }
         if self.is_at_end() {
-            self.error = Some(Error {
+            self.errors.push(ScanError {
                 line: self.line,
-                column: self.current,
+                column: self.column,
                 message: "Unterminated string.".to_string(),
             });
-            Prompt::error_by_line(
-                self.line,
-                &self.source,
-                self.current,
-                "Unterminated string.",
-            );
             return;
         }
         self.advance();
-
-        let value = self.source[self.start + 1..self.current - 1].to_string();
         self.add_token_with_literal(TokenType::String, Some(LoxType::new_str(value.as_str())));
     }
 
+    // 处理反斜杠之后的字符，返回转义后的实际字符
+    fn escape_sequence(&mut self) -> Option<char> {
+        // 换行交由 string() 处理，保证行号计数正确
+        if self.is_at_end() || self.peek() == '\n' {
+            self.errors.push(ScanError {
+                line: self.line,
+                column: self.column - 1,
+                message: "Unfinished escape sequence.".to_string(),
+            });
+            return None;
+        }
+        match self.advance() {
+            '"' => Some('"'),
+            '\\' => Some('\\'),
+            'n' => Some('\n'),
+            'r' => Some('\r'),
+            't' => Some('\t'),
+            '0' => Some('\0'),
+            c => {
+                self.errors.push(ScanError {
+                    line: self.line,
+                    column: self.column - 2,
+                    message: format!("Unknown escape sequence: \\{}", c),
+                });
+                None
+            }
+        }
+    }
+
     fn is_digit(c: char) -> bool {
-        c.is_digit(10)
+        c.is_ascii_digit()
     }
 
     fn number(&mut self) {
+        if &self.source[self.start..self.current] == "0" {
+            let radix = match self.peek() {
+                'x' | 'X' => Some(16),
+                'o' | 'O' => Some(8),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                self.advance();
+                self.radix_number(radix);
+                return;
+            }
+        }
+
         while Scanner::is_digit(self.peek()) {
             self.advance();
         }
@@ -254,6 +352,27 @@ impl Scanner {
         self.add_token_with_literal(TokenType::Number, Some(LoxType::new_num(float_value)));
     }
 
+    fn radix_number(&mut self, radix: u32) {
+        // 前缀之后的字母数字都属于该字面量，非法数字统一在解析时报错
+        while Scanner::is_alpha_numeric(self.peek()) {
+            self.advance();
+        }
+
+        let text = &self.source[self.start..self.current];
+        // 跳过两个字符的前缀，并移除用于分隔的下划线
+        let digits = text[2..].replace('_', "");
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => {
+                self.add_token_with_literal(TokenType::Number, Some(LoxType::new_num(value as f64)))
+            }
+            Err(_) => self.errors.push(ScanError {
+                line: self.line,
+                column: self.column - text.len(),
+                message: format!("Invalid number literal: {}", text),
+            }),
+        }
+    }
+
     fn is_alpha(c: char) -> bool {
         c.is_alphabetic() || c == '_'
     }
@@ -268,15 +387,41 @@ impl Scanner {
         }
 
         let text = &self.source[self.start..self.current];
-        let keywords_map = token::get_keywords_map();
-        let token_type = match keywords_map.get(text) {
-            Some(t) => t.clone(),
-            None => TokenType::Identifier,
-        };
+        let token_type = token::keyword_type(text).unwrap_or(TokenType::Identifier);
         self.add_token(token_type);
     }
 }
 
+// 按需逐个扫描 token，最后产出一个 Eof 之后结束
+impl Iterator for Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        loop {
+            if let Some(token) = self.tokens.pop_front() {
+                return Some(token);
+            }
+            if self.finished {
+                return None;
+            }
+            if self.is_at_end() {
+                self.finished = true;
+                return Some(Token::new(
+                    TokenType::Eof,
+                    "".to_string(),
+                    self.line,
+                    self.column,
+                    self.column + 1,
+                    None,
+                    self.source_file.clone(),
+                ));
+            }
+            self.start = self.current;
+            self.scan_token();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -284,10 +429,253 @@ mod tests {
     #[test]
     fn test_scanner() {
         let source = String::from("var a = \"test\";\nvar b = 123.45;");
-        let mut scanner = Scanner::new(source);
+        let mut scanner = Scanner::new(source, None);
         let tokens = scanner.scan_tokens();
         for token in tokens {
             println!("{:?}", token);
         }
     }
+
+    #[test]
+    fn test_scanner_collects_all_errors() {
+        let source = String::from("var a = @;\nvar b = #;\nvar c = $;");
+        let mut scanner = Scanner::new(source, None);
+        let tokens = scanner.scan_tokens();
+        let errors = scanner.errors();
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].message, "Unexpected character: @");
+        assert_eq!(errors[1].message, "Unexpected character: #");
+        assert_eq!(errors[2].message, "Unexpected character: $");
+        assert_eq!(
+            errors.iter().map(|error| error.line).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(errors[0].column, 8);
+        // 出错的字符被跳过，其余 token 依然正常产出
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_scanner_compound_assignment() {
+        let mut scanner = Scanner::new(String::from("+= -= *= /= + - * /"), None);
+        let token_types = scanner
+            .scan_tokens()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            token_types,
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::Plus,
+                TokenType::Minus,
+                TokenType::Star,
+                TokenType::Slash,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_block_comment() {
+        let mut scanner = Scanner::new(String::from("/* first\nsecond\n*/ var"), None);
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Var);
+        assert_eq!(tokens[0].line, 3);
+
+        let mut scanner = Scanner::new(String::from("/* outer /* inner */ still outer */ 1"), None);
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(tokens[0].token_type, TokenType::Number);
+
+        let mut scanner = Scanner::new(String::from("/* // not a line comment */ nil"), None);
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens[0].token_type, TokenType::Nil);
+
+        let mut scanner = Scanner::new(String::from("/* outer /* inner */ never closed"), None);
+        let tokens = scanner.scan_tokens();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(scanner.errors().len(), 1);
+        assert_eq!(scanner.errors()[0].message, "Unterminated block comment.");
+    }
+
+    fn scan_number(source: &str) -> Option<LoxType> {
+        let mut scanner = Scanner::new(source.to_string(), None);
+        let tokens = scanner.scan_tokens();
+        if !scanner.errors().is_empty() {
+            return None;
+        }
+        tokens[0].literal.clone()
+    }
+
+    #[test]
+    fn test_scanner_hex_number() {
+        assert_eq!(scan_number("0xFF"), Some(LoxType::new_num(255.0)));
+        assert_eq!(scan_number("0x10"), Some(LoxType::new_num(16.0)));
+        assert_eq!(
+            scan_number("0XdeadBEEF"),
+            Some(LoxType::new_num(3735928559.0))
+        );
+        assert_eq!(
+            scan_number("0xDEAD_BEEF"),
+            Some(LoxType::new_num(3735928559.0))
+        );
+        assert_eq!(scan_number("0xFF_00"), Some(LoxType::new_num(65280.0)));
+        assert_eq!(scan_number("0x"), None);
+        assert_eq!(scan_number("0xFG"), None);
+    }
+
+    #[test]
+    fn test_scanner_binary_octal_number() {
+        assert_eq!(scan_number("0b1111"), Some(LoxType::new_num(15.0)));
+        assert_eq!(scan_number("0B1010_1010"), Some(LoxType::new_num(170.0)));
+        assert_eq!(scan_number("0o17"), Some(LoxType::new_num(15.0)));
+        assert_eq!(scan_number("0O7_7"), Some(LoxType::new_num(63.0)));
+        assert_eq!(scan_number("0b2"), None);
+        assert_eq!(scan_number("0o8"), None);
+        // 没有 0o 前缀的前导零依然按十进制处理
+        assert_eq!(scan_number("017"), Some(LoxType::new_num(17.0)));
+    }
+
+    fn scan_string(source: &str) -> Result<LoxType, Vec<String>> {
+        let mut scanner = Scanner::new(source.to_string(), None);
+        let tokens = scanner.scan_tokens();
+        if !scanner.errors().is_empty() {
+            let messages = scanner.errors().iter().map(|e| e.message.clone());
+            return Err(messages.collect());
+        }
+        Ok(tokens[0].literal.clone().unwrap())
+    }
+
+    #[test]
+    fn test_scanner_string_escape() {
+        assert_eq!(scan_string(r#""a\nb""#), Ok(LoxType::new_str("a\nb")));
+        assert_eq!(scan_string(r#""a\rb""#), Ok(LoxType::new_str("a\rb")));
+        assert_eq!(scan_string(r#""a\tb""#), Ok(LoxType::new_str("a\tb")));
+        assert_eq!(scan_string(r#""a\0b""#), Ok(LoxType::new_str("a\0b")));
+        assert_eq!(scan_string(r#""a\\b""#), Ok(LoxType::new_str("a\\b")));
+        assert_eq!(
+            scan_string(r#""say \"hi\"""#),
+            Ok(LoxType::new_str("say \"hi\""))
+        );
+        assert_eq!(scan_string(r#""\\n""#), Ok(LoxType::new_str("\\n")));
+
+        assert_eq!(
+            scan_string(r#""a\qb""#),
+            Err(vec!["Unknown escape sequence: \\q".to_string()])
+        );
+        assert_eq!(
+            scan_string(r#""a\"#),
+            Err(vec![
+                "Unfinished escape sequence.".to_string(),
+                "Unterminated string.".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_scanner_large_unicode_source() {
+        // 约一万个字符的源码，逐字符访问为 O(1) 时可以很快完成扫描
+        let statement = "var 名字 = \"日本語 text\"; // 注释\n";
+        let source = statement.repeat(10_000 / statement.chars().count() + 1);
+        assert!(source.chars().count() >= 10_000);
+        let mut scanner = Scanner::new(source.clone(), None);
+        let tokens = scanner.scan_tokens();
+        assert!(scanner.errors().is_empty());
+        let statement_count = source.lines().count();
+        assert_eq!(tokens.len(), statement_count * 5 + 1);
+        assert_eq!(tokens[1].lexeme, "名字");
+        assert_eq!(tokens[3].literal, Some(LoxType::new_str("日本語 text")));
+        assert_eq!(tokens[tokens.len() - 2].line, statement_count);
+    }
+
+    #[test]
+    fn test_scanner_iterator() {
+        let scanner = Scanner::new("var a = 1; @ never scanned".to_string(), None);
+        let tokens = scanner.take(3).collect::<Vec<_>>();
+        let types = tokens.iter().map(|token| token.token_type.clone());
+        assert_eq!(
+            types.collect::<Vec<_>>(),
+            vec![TokenType::Var, TokenType::Identifier, TokenType::Equal]
+        );
+
+        let mut scanner = Scanner::new("// only a comment".to_string(), None);
+        assert_eq!(scanner.next().unwrap().token_type, TokenType::Eof);
+        assert!(scanner.next().is_none());
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn test_scanner_token_columns() {
+        let tokens = Scanner::new("var name = 1;".to_string(), None).scan_tokens();
+        let columns = tokens
+            .iter()
+            .map(|token| (token.col_start, token.col_end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![(0, 3), (4, 8), (9, 10), (11, 12), (12, 13), (13, 14)]
+        );
+    }
+
+    #[test]
+    fn test_scanner_ellipsis() {
+        let mut scanner = Scanner::new(String::from("[...rest] a.b .."), None);
+        let token_types = scanner
+            .scan_tokens()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            token_types,
+            vec![
+                TokenType::LeftBracket,
+                TokenType::Ellipsis,
+                TokenType::Identifier,
+                TokenType::RightBracket,
+                TokenType::Identifier,
+                TokenType::Dot,
+                TokenType::Identifier,
+                TokenType::Dot,
+                TokenType::Dot,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scanner_star_star() {
+        let mut scanner = Scanner::new(String::from("2 ** 3 * 4 *= 5"), None);
+        let token_types = scanner
+            .scan_tokens()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            token_types,
+            vec![
+                TokenType::Number,
+                TokenType::StarStar,
+                TokenType::Number,
+                TokenType::Star,
+                TokenType::Number,
+                TokenType::StarEqual,
+                TokenType::Number,
+                TokenType::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_count() {
+        let mut scanner = Scanner::new(String::from("@ var a = #$;\n^"), None);
+        assert!(!scanner.has_errors());
+        scanner.scan_tokens();
+        assert!(scanner.has_errors());
+        assert_eq!(scanner.error_count(), 4);
+    }
 }
@@ -1,5 +1,64 @@
-pub struct Error {
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+#[derive(Debug, Clone)]
+pub struct ScanError {
     pub line: usize,
     pub column: usize,
     pub message: String,
 }
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ScanError {}
+
+// LoxType 之间的运算在操作数类型不匹配时返回的错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError {
+    NilOperand,
+    NumberOperand,
+    NumberOperands,
+    NumberOrStringOperands,
+    DivisionByZero,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            TypeError::NilOperand => "Operand must not be nil.",
+            TypeError::NumberOperand => "Operand must be a number.",
+            TypeError::NumberOperands => "Operands must be numbers.",
+            TypeError::NumberOrStringOperands => "Operands must be numbers or strings.",
+            TypeError::DivisionByZero => "Division by zero.",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl Error for TypeError {}
+
+// LoxType 转换为 Rust 类型时，值的类型与目标类型不符
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoxTypeError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl LoxTypeError {
+    pub fn new(expected: &'static str, found: &'static str) -> Self {
+        LoxTypeError { expected, found }
+    }
+}
+
+impl fmt::Display for LoxTypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected a {} but got {}.", self.expected, self.found)
+    }
+}
+
+impl Error for LoxTypeError {}
@@ -1,9 +1,15 @@
-use crate::Lox;
+use crate::ast::error::LoxRuntimeError;
 use crate::ast::interpreter::Interpreter;
+use crate::class::{LoxClass, LoxInstance};
+use crate::scanner::error::{LoxTypeError, TypeError};
 use std::any::Any;
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::fmt::Debug;
-use std::sync::OnceLock;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::rc::Rc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
@@ -12,6 +18,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -19,6 +27,9 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
 
     // One or two character tokens.
     Bang,
@@ -29,6 +40,13 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    StarStar,
+    // 列表解构中收集剩余元素的 ...
+    Ellipsis,
 
     // Literals.
     Identifier,
@@ -52,17 +70,31 @@ pub enum TokenType {
     True,
     Var,
     While,
+    Break,
+    Continue,
+    Const,
+    In,
+    Write,
+    Switch,
+    Case,
+    Default,
+    Try,
+    Catch,
+    Throw,
+    Import,
 
     // EOF
     Eof,
 }
 
-pub trait Callable: Debug + Send + Sync {
+pub trait Callable: Debug {
+    // paren 为调用处的右括号，用于定位调用过程中产生的运行时错误
     fn call(
         &mut self,
         interpreter: &mut Interpreter,
-        arguments: &Vec<Option<LoxType>>,
-    ) -> Option<LoxType>;
+        paren: &Token,
+        arguments: &[LoxType],
+    ) -> Result<LoxType, LoxRuntimeError>;
 
     fn arity(&self) -> usize;
 
@@ -88,22 +120,73 @@ impl PartialEq for dyn Callable {
     }
 }
 
+#[derive(Debug)]
 pub struct LoxReturn {
-    pub value: Option<LoxType>,
+    pub value: LoxType,
 }
 
 impl LoxReturn {
-    pub fn new(value: Option<LoxType>) -> Self {
+    pub fn new(value: LoxType) -> Self {
         LoxReturn { value }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+// 函数体中 return 一个调用表达式时产生，由外层的 LoxFunction::call 在循环中完成调用
+#[derive(Debug)]
+pub struct LoxTailCall {
+    pub callee: Box<dyn Callable>,
+    pub paren: Token,
+    pub arguments: Vec<LoxType>,
+}
+
+impl LoxTailCall {
+    pub fn new(callee: Box<dyn Callable>, paren: Token, arguments: Vec<LoxType>) -> Self {
+        LoxTailCall {
+            callee,
+            paren,
+            arguments,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxBreak;
+
+#[derive(Debug)]
+pub struct LoxContinue;
+
+// 中断正常执行流程的信号，沿着调用栈向上传递直到被对应的语句捕获
+#[derive(Debug)]
+pub enum LoxInterrupt {
+    Return(LoxReturn),
+    Break(LoxBreak),
+    Continue(LoxContinue),
+    TailCall(Box<LoxTailCall>),
+    Error(LoxRuntimeError),
+}
+
+impl From<LoxRuntimeError> for LoxInterrupt {
+    fn from(error: LoxRuntimeError) -> Self {
+        LoxInterrupt::Error(error)
+    }
+}
+
+// 字符串保持装箱以控制枚举的大小
+// Nil、Str、Num、Bool 按值复制，clone 后互不影响；List 与 Map 按引用共享，
+// clone 只增加 Rc 的引用计数，赋值或传参得到的别名修改的是同一份数据
+#[allow(clippy::box_collection)]
+#[derive(Clone)]
 pub enum LoxType {
+    Nil,
     Str(Box<String>),
-    Num(Box<f64>),
-    Bool(Box<bool>),
+    Num(f64),
+    Bool(bool),
     Function(Box<dyn Callable>),
+    Instance(Box<LoxInstance>),
+    // 列表以引用方式共享，所有别名看到同一份元素
+    List(Rc<RefCell<Vec<LoxType>>>),
+    // 字典的键只能是字符串，与列表一样以引用方式共享
+    Map(Rc<RefCell<HashMap<String, LoxType>>>),
 }
 
 impl LoxType {
@@ -112,16 +195,270 @@ impl LoxType {
     }
 
     pub fn new_num(n: f64) -> Self {
-        LoxType::Num(Box::new(n))
+        LoxType::Num(n)
     }
 
     pub fn new_bool(b: bool) -> Self {
-        LoxType::Bool(Box::new(b))
+        LoxType::Bool(b)
     }
 
     pub fn new_function(func: Box<dyn Callable>) -> Self {
         LoxType::Function(func)
     }
+
+    pub fn new_list(elements: Vec<LoxType>) -> Self {
+        LoxType::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn new_map(entries: HashMap<String, LoxType>) -> Self {
+        LoxType::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    // 值在 Lox 中的类型名称，与 type_of 的返回值一致
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxType::Nil => "nil",
+            LoxType::Str(_) => "string",
+            LoxType::Num(_) => "number",
+            LoxType::Bool(_) => "boolean",
+            // 类同样以可调用对象的形式保存，需要区分出来
+            LoxType::Function(callable) if callable.as_any().is::<LoxClass>() => "class",
+            LoxType::Function(_) => "function",
+            LoxType::Instance(_) => "instance",
+            LoxType::List(_) => "list",
+            LoxType::Map(_) => "map",
+        }
+    }
+}
+
+impl From<f64> for LoxType {
+    fn from(n: f64) -> Self {
+        LoxType::new_num(n)
+    }
+}
+
+impl From<bool> for LoxType {
+    fn from(b: bool) -> Self {
+        LoxType::new_bool(b)
+    }
+}
+
+impl From<String> for LoxType {
+    fn from(s: String) -> Self {
+        LoxType::Str(Box::new(s))
+    }
+}
+
+impl From<&str> for LoxType {
+    fn from(s: &str) -> Self {
+        LoxType::new_str(s)
+    }
+}
+
+impl TryFrom<LoxType> for f64 {
+    type Error = LoxTypeError;
+
+    fn try_from(value: LoxType) -> Result<Self, Self::Error> {
+        match value {
+            LoxType::Num(n) => Ok(n),
+            value => Err(LoxTypeError::new("number", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<LoxType> for bool {
+    type Error = LoxTypeError;
+
+    fn try_from(value: LoxType) -> Result<Self, Self::Error> {
+        match value {
+            LoxType::Bool(b) => Ok(b),
+            value => Err(LoxTypeError::new("boolean", value.type_name())),
+        }
+    }
+}
+
+impl TryFrom<LoxType> for String {
+    type Error = LoxTypeError;
+
+    fn try_from(value: LoxType) -> Result<Self, Self::Error> {
+        match value {
+            LoxType::Str(s) => Ok(*s),
+            value => Err(LoxTypeError::new("string", value.type_name())),
+        }
+    }
+}
+
+// 列表与字典按引用比较，与实例一样只有同一个对象才相等，也避免比较包含自身的集合时无限递归
+impl PartialEq for LoxType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (LoxType::Nil, LoxType::Nil) => true,
+            (LoxType::Str(a), LoxType::Str(b)) => a == b,
+            (LoxType::Num(a), LoxType::Num(b)) => a == b,
+            (LoxType::Bool(a), LoxType::Bool(b)) => a == b,
+            (LoxType::Function(a), LoxType::Function(b)) => a == b,
+            (LoxType::Instance(a), LoxType::Instance(b)) => a == b,
+            (LoxType::List(a), LoxType::List(b)) => Rc::ptr_eq(a, b),
+            (LoxType::Map(a), LoxType::Map(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// 列表与字典的调试输出沿用带循环保护的 Display
+impl Debug for LoxType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxType::Nil => write!(f, "Nil"),
+            LoxType::Str(s) => f.debug_tuple("Str").field(s).finish(),
+            LoxType::Num(n) => f.debug_tuple("Num").field(n).finish(),
+            LoxType::Bool(b) => f.debug_tuple("Bool").field(b).finish(),
+            LoxType::Function(function) => f.debug_tuple("Function").field(function).finish(),
+            LoxType::Instance(instance) => f.debug_tuple("Instance").field(instance).finish(),
+            LoxType::List(_) => write!(f, "List({})", self),
+            LoxType::Map(_) => write!(f, "Map({})", self),
+        }
+    }
+}
+
+// Lox 中值的输出形式，整数形式的数字不带小数部分，字符串不带引号
+impl Display for LoxType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        self.fmt_nested(f, &mut Vec::new())
+    }
+}
+
+impl LoxType {
+    // visiting 记录正在输出的外层集合，集合包含自身时输出 [...] 或 {...}
+    fn fmt_nested(&self, f: &mut Formatter<'_>, visiting: &mut Vec<*const ()>) -> fmt::Result {
+        match self {
+            LoxType::Nil => write!(f, "nil"),
+            LoxType::Str(s) => write!(f, "{}", s),
+            LoxType::Num(n) => write!(f, "{}", n),
+            LoxType::Bool(b) => write!(f, "{}", b),
+            LoxType::Function(_) => write!(f, "<function>"),
+            LoxType::Instance(instance) => write!(f, "<{} instance>", instance.class.name),
+            LoxType::List(elements) => {
+                let pointer = Rc::as_ptr(elements) as *const ();
+                if visiting.contains(&pointer) {
+                    return write!(f, "[...]");
+                }
+                visiting.push(pointer);
+                write!(f, "[")?;
+                for (index, element) in elements.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, visiting)?;
+                }
+                visiting.pop();
+                write!(f, "]")
+            }
+            LoxType::Map(entries) => {
+                let pointer = Rc::as_ptr(entries) as *const ();
+                if visiting.contains(&pointer) {
+                    return write!(f, "{{...}}");
+                }
+                visiting.push(pointer);
+                // 按键排序，保证输出稳定
+                let entries = entries.borrow();
+                let mut keys = entries.keys().collect::<Vec<_>>();
+                keys.sort();
+                write!(f, "{{")?;
+                for (index, key) in keys.into_iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", key)?;
+                    entries[key].fmt_nested(f, visiting)?;
+                }
+                visiting.pop();
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+// 数字之间的算术运算，任一操作数不是数字时返回错误
+fn number_operands(left: LoxType, right: LoxType) -> Result<(f64, f64), TypeError> {
+    match (left, right) {
+        (LoxType::Num(left), LoxType::Num(right)) => Ok((left, right)),
+        _ => Err(TypeError::NumberOperands),
+    }
+}
+
+impl Add for LoxType {
+    type Output = Result<LoxType, TypeError>;
+
+    fn add(self, rhs: LoxType) -> Self::Output {
+        match (self, rhs) {
+            (LoxType::Num(left), LoxType::Num(right)) => Ok(LoxType::new_num(left + right)),
+            // 一侧为字符串，另一侧为字符串或数字时，进行字符串拼接
+            (LoxType::Str(left), LoxType::Str(right)) => {
+                Ok(LoxType::new_str(&format!("{}{}", left, right)))
+            }
+            (LoxType::Str(left), LoxType::Num(right)) => {
+                Ok(LoxType::new_str(&format!("{}{}", left, right)))
+            }
+            (LoxType::Num(left), LoxType::Str(right)) => {
+                Ok(LoxType::new_str(&format!("{}{}", left, right)))
+            }
+            (LoxType::Nil, _) | (_, LoxType::Nil) => Err(TypeError::NilOperand),
+            _ => Err(TypeError::NumberOrStringOperands),
+        }
+    }
+}
+
+impl Sub for LoxType {
+    type Output = Result<LoxType, TypeError>;
+
+    fn sub(self, rhs: LoxType) -> Self::Output {
+        let (left, right) = number_operands(self, rhs)?;
+        Ok(LoxType::new_num(left - right))
+    }
+}
+
+impl Mul for LoxType {
+    type Output = Result<LoxType, TypeError>;
+
+    fn mul(self, rhs: LoxType) -> Self::Output {
+        let (left, right) = number_operands(self, rhs)?;
+        Ok(LoxType::new_num(left * right))
+    }
+}
+
+impl Div for LoxType {
+    type Output = Result<LoxType, TypeError>;
+
+    fn div(self, rhs: LoxType) -> Self::Output {
+        let (left, right) = number_operands(self, rhs)?;
+        if right == 0.0 {
+            return Err(TypeError::DivisionByZero);
+        }
+        Ok(LoxType::new_num(left / right))
+    }
+}
+
+impl Neg for LoxType {
+    type Output = Result<LoxType, TypeError>;
+
+    fn neg(self) -> Self::Output {
+        match self {
+            LoxType::Num(num) => Ok(LoxType::new_num(-num)),
+            _ => Err(TypeError::NumberOperand),
+        }
+    }
+}
+
+// 数字按数值比较，字符串按字典序比较，其余类型之间没有顺序
+impl PartialOrd for LoxType {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (LoxType::Num(left), LoxType::Num(right)) => left.partial_cmp(right),
+            (LoxType::Str(left), LoxType::Str(right)) => left.partial_cmp(right),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -132,6 +469,7 @@ pub struct Token {
     pub col_start: usize,
     pub col_end: usize,
     pub literal: Option<LoxType>,
+    pub source_file: Option<Rc<str>>,
 }
 
 impl Token {
@@ -142,6 +480,7 @@ impl Token {
         col_start: usize,
         col_end: usize,
         literal: Option<LoxType>,
+        source_file: Option<Rc<str>>,
     ) -> Self {
         Token {
             token_type,
@@ -150,34 +489,370 @@ impl Token {
             col_start,
             col_end,
             literal,
+            source_file,
+        }
+    }
+
+    // 用于 --tokens 输出，在 Display 的基础上加入 token 所在的位置
+    pub fn format_debug(&self) -> String {
+        format!(
+            "{} '{}' [{}:{}-{}] literal={}",
+            self.token_type,
+            self.lexeme,
+            self.line,
+            self.col_start,
+            self.col_end,
+            self.literal_text()
+        )
+    }
+
+    // 字符串字面量带引号，没有字面量时输出 none
+    fn literal_text(&self) -> String {
+        match &self.literal {
+            Some(LoxType::Str(s)) => format!("{:?}", s),
+            Some(value) => value.to_string(),
+            None => "none".to_string(),
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' literal={}",
+            self.token_type,
+            self.lexeme,
+            self.literal_text()
+        )
+    }
+}
+
+impl TokenType {
+    pub fn is_keyword(&self) -> bool {
+        matches!(
+            self,
+            TokenType::And
+                | TokenType::Class
+                | TokenType::Else
+                | TokenType::False
+                | TokenType::For
+                | TokenType::Fun
+                | TokenType::If
+                | TokenType::Nil
+                | TokenType::Or
+                | TokenType::Print
+                | TokenType::Return
+                | TokenType::Super
+                | TokenType::This
+                | TokenType::True
+                | TokenType::Var
+                | TokenType::While
+                | TokenType::Break
+                | TokenType::Continue
+                | TokenType::Const
+                | TokenType::In
+                | TokenType::Write
+                | TokenType::Switch
+                | TokenType::Case
+                | TokenType::Default
+                | TokenType::Try
+                | TokenType::Catch
+                | TokenType::Throw
+                | TokenType::Import
+        )
+    }
+
+    // 可以直接作为字面量表达式的 token
+    pub fn is_literal(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Number
+                | TokenType::String
+                | TokenType::True
+                | TokenType::False
+                | TokenType::Nil
+        )
+    }
+
+    // 二元算术与比较运算符，不包括 and / or
+    pub fn is_binary_op(&self) -> bool {
+        matches!(
+            self,
+            TokenType::Plus
+                | TokenType::Minus
+                | TokenType::Star
+                | TokenType::Slash
+                | TokenType::Percent
+                | TokenType::StarStar
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::EqualEqual
+                | TokenType::BangEqual
+        )
+    }
+
+    pub fn is_unary_op(&self) -> bool {
+        matches!(self, TokenType::Bang | TokenType::Minus)
+    }
+}
+
+// 输出枚举成员的名称，例如 LeftParen
+impl Display for TokenType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+// 所有关键字，与 keyword_type 中的分支保持一致
+pub const KEYWORDS: [&str; 28] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while", "break", "continue", "const", "in", "write", "switch", "case",
+    "default", "try", "catch", "throw", "import",
+];
+
+// 关键字在编译期展开为字符串匹配，不需要在首次使用时构建哈希表
+pub fn keyword_type(text: &str) -> Option<TokenType> {
+    let token_type = match text {
+        "and" => TokenType::And,
+        "class" => TokenType::Class,
+        "else" => TokenType::Else,
+        "false" => TokenType::False,
+        "for" => TokenType::For,
+        "fun" => TokenType::Fun,
+        "if" => TokenType::If,
+        "nil" => TokenType::Nil,
+        "or" => TokenType::Or,
+        "print" => TokenType::Print,
+        "return" => TokenType::Return,
+        "super" => TokenType::Super,
+        "this" => TokenType::This,
+        "true" => TokenType::True,
+        "var" => TokenType::Var,
+        "while" => TokenType::While,
+        "break" => TokenType::Break,
+        "continue" => TokenType::Continue,
+        "const" => TokenType::Const,
+        "in" => TokenType::In,
+        "write" => TokenType::Write,
+        "switch" => TokenType::Switch,
+        "case" => TokenType::Case,
+        "default" => TokenType::Default,
+        "try" => TokenType::Try,
+        "catch" => TokenType::Catch,
+        "throw" => TokenType::Throw,
+        "import" => TokenType::Import,
+        _ => return None,
+    };
+    Some(token_type)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::class::{LoxClass, LoxInstance};
+    use crate::function::native::NativeFn;
+
+    #[test]
+    fn test_lox_type_display() {
+        assert_eq!(LoxType::Nil.to_string(), "nil");
+        assert_eq!(LoxType::new_str("hello").to_string(), "hello");
+        assert_eq!(LoxType::new_num(2.0).to_string(), "2");
+        assert_eq!(LoxType::new_num(-2.5).to_string(), "-2.5");
+        assert_eq!(LoxType::new_num(0.1).to_string(), "0.1");
+        assert_eq!(LoxType::new_bool(true).to_string(), "true");
+        assert_eq!(LoxType::new_bool(false).to_string(), "false");
+        let native = NativeFn::new("nothing".to_string(), 0, |_, _| LoxType::Nil);
+        let function = LoxType::new_function(Box::new(native));
+        assert_eq!(function.to_string(), "<function>");
+        let class = LoxClass::new("Point".to_string(), None, HashMap::new());
+        let instance = LoxType::Instance(Box::new(LoxInstance::new(class)));
+        assert_eq!(instance.to_string(), "<Point instance>");
+    }
+
+    #[test]
+    fn test_lox_type_arithmetic() {
+        assert_eq!(
+            LoxType::new_num(1.0) + LoxType::new_num(2.0),
+            Ok(LoxType::new_num(3.0))
+        );
+        assert_eq!(
+            LoxType::new_str("a") + LoxType::new_num(1.0),
+            Ok(LoxType::new_str("a1"))
+        );
+        assert_eq!(
+            LoxType::new_num(1.5) + LoxType::new_str("b"),
+            Ok(LoxType::new_str("1.5b"))
+        );
+        assert_eq!(
+            LoxType::new_num(1.0) + LoxType::Nil,
+            Err(TypeError::NilOperand)
+        );
+        assert_eq!(
+            LoxType::new_bool(true) + LoxType::new_num(1.0),
+            Err(TypeError::NumberOrStringOperands)
+        );
+        assert_eq!(
+            LoxType::new_num(5.0) - LoxType::new_num(2.0),
+            Ok(LoxType::new_num(3.0))
+        );
+        assert_eq!(
+            LoxType::new_num(2.0) * LoxType::new_str("a"),
+            Err(TypeError::NumberOperands)
+        );
+        assert_eq!(
+            LoxType::new_num(1.0) / LoxType::new_num(0.0),
+            Err(TypeError::DivisionByZero)
+        );
+        assert_eq!(-LoxType::new_num(2.0), Ok(LoxType::new_num(-2.0)));
+        assert_eq!(-LoxType::new_str("a"), Err(TypeError::NumberOperand));
+    }
+
+    #[test]
+    fn test_lox_type_compare() {
+        assert_eq!(LoxType::Nil, LoxType::Nil);
+        assert_ne!(LoxType::Nil, LoxType::new_bool(false));
+        assert!(LoxType::new_num(1.0) < LoxType::new_num(2.0));
+        assert!(LoxType::new_num(2.0) >= LoxType::new_num(2.0));
+        assert!(LoxType::new_str("apple") < LoxType::new_str("banana"));
+        assert!(LoxType::new_str("b") > LoxType::new_str("abc"));
+        assert_eq!(
+            LoxType::new_num(1.0).partial_cmp(&LoxType::new_str("1")),
+            None
+        );
+        assert_eq!(LoxType::Nil.partial_cmp(&LoxType::Nil), None);
+        assert!(!LoxType::new_num(1.0).lt(&LoxType::new_str("1")));
+    }
+
+    #[test]
+    fn test_lox_type_conversion() {
+        assert_eq!(LoxType::from(3.5), LoxType::new_num(3.5));
+        assert_eq!(LoxType::from(true), LoxType::new_bool(true));
+        assert_eq!(LoxType::from(String::from("a")), LoxType::new_str("a"));
+        let value: LoxType = "hello".into();
+        assert_eq!(value, LoxType::new_str("hello"));
+
+        assert_eq!(f64::try_from(LoxType::new_num(2.0)), Ok(2.0));
+        assert_eq!(bool::try_from(LoxType::new_bool(false)), Ok(false));
+        assert_eq!(
+            String::try_from(LoxType::new_str("s")),
+            Ok(String::from("s"))
+        );
+        let error = f64::try_from(LoxType::new_str("1")).unwrap_err();
+        assert_eq!(error, LoxTypeError::new("number", "string"));
+        assert_eq!(error.to_string(), "Expected a number but got string.");
+        assert!(bool::try_from(LoxType::Nil).is_err());
+        assert!(String::try_from(LoxType::new_num(1.0)).is_err());
+    }
+
+    #[test]
+    fn test_lox_type_self_reference() {
+        let list = LoxType::new_list(vec![LoxType::new_num(1.0)]);
+        let LoxType::List(elements) = &list else {
+            panic!("expected a list");
+        };
+        elements.borrow_mut().push(list.clone());
+        assert_eq!(list.to_string(), "[1, [...]]");
+        assert_eq!(format!("{:?}", list), "List([1, [...]])");
+        assert_eq!(list, list.clone());
+        assert_ne!(list, LoxType::new_list(vec![]));
+
+        let map = LoxType::new_map(HashMap::new());
+        let LoxType::Map(entries) = &map else {
+            panic!("expected a map");
+        };
+        entries
+            .borrow_mut()
+            .insert(String::from("self"), map.clone());
+        entries
+            .borrow_mut()
+            .insert(String::from("list"), list.clone());
+        assert_eq!(map.to_string(), "{list: [1, [...]], self: {...}}");
+        assert_eq!(map, map.clone());
+
+        // 内容相同的不同列表互不相等
+        assert_ne!(
+            LoxType::new_list(vec![LoxType::Nil]),
+            LoxType::new_list(vec![LoxType::Nil])
+        );
+    }
+
+    #[test]
+    fn test_lox_type_clone() {
+        let list = LoxType::new_list(vec![LoxType::new_num(1.0)]);
+        let (LoxType::List(a), LoxType::List(b)) = (&list, &list.clone()) else {
+            panic!("expected lists");
+        };
+        assert!(Rc::ptr_eq(a, b));
+        let map = LoxType::new_map(HashMap::new());
+        let (LoxType::Map(a), LoxType::Map(b)) = (&map, &map.clone()) else {
+            panic!("expected maps");
+        };
+        assert!(Rc::ptr_eq(a, b));
+        b.borrow_mut().insert(String::from("k"), LoxType::Nil);
+        assert_eq!(map.to_string(), "{k: nil}");
+    }
+
+    #[test]
+    fn test_lox_type_size() {
+        assert!(std::mem::size_of::<LoxType>() <= 24);
+    }
+
+    #[test]
+    fn test_token_display() {
+        assert_eq!(TokenType::LeftParen.to_string(), "LeftParen");
+        let token = Token::new(
+            TokenType::String,
+            "\"hi\"".to_string(),
+            1,
+            0,
+            4,
+            Some(LoxType::new_str("hi")),
+            None,
+        );
+        assert_eq!(format!("{}", token), "String '\"hi\"' literal=\"hi\"");
+        let token = Token::new(TokenType::Plus, "+".to_string(), 1, 0, 1, None, None);
+        assert_eq!(format!("{}", token), "Plus '+' literal=none");
+    }
+
+    #[test]
+    fn test_token_type_predicates() {
+        assert!(TokenType::While.is_keyword());
+        assert!(TokenType::Write.is_keyword());
+        assert!(!TokenType::Identifier.is_keyword());
+        assert!(!TokenType::Plus.is_keyword());
+        for keyword in ["and", "class", "fun", "while", "const", "in", "write"] {
+            assert!(keyword_type(keyword).unwrap().is_keyword());
         }
+
+        assert!(TokenType::Number.is_literal());
+        assert!(TokenType::Nil.is_literal());
+        assert!(!TokenType::Identifier.is_literal());
+        assert!(!TokenType::This.is_literal());
+
+        assert!(TokenType::Plus.is_binary_op());
+        assert!(TokenType::BangEqual.is_binary_op());
+        assert!(TokenType::StarStar.is_binary_op());
+        assert!(!TokenType::And.is_binary_op());
+        assert!(!TokenType::Equal.is_binary_op());
+        assert!(!TokenType::Bang.is_binary_op());
+
+        assert!(TokenType::Bang.is_unary_op());
+        assert!(TokenType::Minus.is_unary_op());
+        assert!(!TokenType::Plus.is_unary_op());
     }
 
-    pub fn to_string(&self) -> String {
-        format!("{:?} {} {:?}", self.token_type, self.lexeme, self.literal)
-    }
-}
-
-static KEYWORDS_MAP: OnceLock<HashMap<&'static str, TokenType>> = OnceLock::new();
-pub fn get_keywords_map() -> &'static HashMap<&'static str, TokenType> {
-    KEYWORDS_MAP.get_or_init(|| {
-        let mut m = HashMap::new();
-        m.insert("and", TokenType::And);
-        m.insert("class", TokenType::Class);
-        m.insert("else", TokenType::Else);
-        m.insert("false", TokenType::False);
-        m.insert("for", TokenType::For);
-        m.insert("fun", TokenType::Fun);
-        m.insert("if", TokenType::If);
-        m.insert("nil", TokenType::Nil);
-        m.insert("or", TokenType::Or);
-        m.insert("print", TokenType::Print);
-        m.insert("return", TokenType::Return);
-        m.insert("super", TokenType::Super);
-        m.insert("this", TokenType::This);
-        m.insert("true", TokenType::True);
-        m.insert("var", TokenType::Var);
-        m.insert("while", TokenType::While);
-        m
-    })
+    #[test]
+    fn test_keyword_type() {
+        assert_eq!(keyword_type("while"), Some(TokenType::While));
+        assert_eq!(keyword_type("write"), Some(TokenType::Write));
+        assert_eq!(keyword_type("While"), None);
+        assert_eq!(keyword_type("whilex"), None);
+        assert_eq!(keyword_type(""), None);
+        for keyword in KEYWORDS {
+            assert!(keyword_type(keyword).is_some(), "{}", keyword);
+        }
+    }
 }
@@ -0,0 +1,9 @@
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod error;
+#[allow(clippy::module_inception)]
+pub mod vm;
+
+// 重导出
+pub use chunk::{Chunk, OpCode};
@@ -0,0 +1,149 @@
+use crate::scanner::LoxType;
+
+// 字节码指令，每条指令占一个字节，部分指令之后紧跟一个字节的操作数
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    Constant,
+    Return,
+    Negate,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Nil,
+    True,
+    False,
+    Not,
+    Equal,
+    Greater,
+    Less,
+    // 输出栈顶的值但不弹出，编译器在其后紧跟一条 Pop
+    Print,
+    Pop,
+    DefineGlobal,
+    GetGlobal,
+    SetGlobal,
+    // 操作数为局部变量在栈中的槽位
+    GetLocal,
+    SetLocal,
+}
+
+impl OpCode {
+    // 按声明顺序排列，下标即为指令的字节值
+    const ALL: [OpCode; 21] = [
+        OpCode::Constant,
+        OpCode::Return,
+        OpCode::Negate,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Nil,
+        OpCode::True,
+        OpCode::False,
+        OpCode::Not,
+        OpCode::Equal,
+        OpCode::Greater,
+        OpCode::Less,
+        OpCode::Print,
+        OpCode::Pop,
+        OpCode::DefineGlobal,
+        OpCode::GetGlobal,
+        OpCode::SetGlobal,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+    ];
+
+    // 反汇编时使用的名称
+    pub fn name(self) -> &'static str {
+        match self {
+            OpCode::Constant => "OP_CONSTANT",
+            OpCode::Return => "OP_RETURN",
+            OpCode::Negate => "OP_NEGATE",
+            OpCode::Add => "OP_ADD",
+            OpCode::Subtract => "OP_SUBTRACT",
+            OpCode::Multiply => "OP_MULTIPLY",
+            OpCode::Divide => "OP_DIVIDE",
+            OpCode::Nil => "OP_NIL",
+            OpCode::True => "OP_TRUE",
+            OpCode::False => "OP_FALSE",
+            OpCode::Not => "OP_NOT",
+            OpCode::Equal => "OP_EQUAL",
+            OpCode::Greater => "OP_GREATER",
+            OpCode::Less => "OP_LESS",
+            OpCode::Print => "OP_PRINT",
+            OpCode::Pop => "OP_POP",
+            OpCode::DefineGlobal => "OP_DEFINE_GLOBAL",
+            OpCode::GetGlobal => "OP_GET_GLOBAL",
+            OpCode::SetGlobal => "OP_SET_GLOBAL",
+            OpCode::GetLocal => "OP_GET_LOCAL",
+            OpCode::SetLocal => "OP_SET_LOCAL",
+        }
+    }
+
+    // 操作数为常量表下标的指令
+    pub fn has_constant_operand(self) -> bool {
+        matches!(
+            self,
+            OpCode::Constant | OpCode::DefineGlobal | OpCode::GetGlobal | OpCode::SetGlobal
+        )
+    }
+
+    pub fn has_slot_operand(self) -> bool {
+        matches!(self, OpCode::GetLocal | OpCode::SetLocal)
+    }
+}
+
+impl From<OpCode> for u8 {
+    fn from(op: OpCode) -> Self {
+        op as u8
+    }
+}
+
+impl TryFrom<u8> for OpCode {
+    type Error = u8;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        OpCode::ALL.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+// 一段字节码，lines 与 code 一一对应，记录每个字节来自源码的哪一行
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<LoxType>,
+    pub lines: Vec<usize>,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Chunk::default()
+    }
+
+    pub fn write(&mut self, byte: u8, line: usize) {
+        self.code.push(byte);
+        self.lines.push(line);
+    }
+
+    // 返回常量在常量表中的下标，作为 Constant 等指令的操作数
+    pub fn add_constant(&mut self, value: LoxType) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_opcode_byte() {
+        for (byte, op) in OpCode::ALL.iter().enumerate() {
+            assert_eq!(u8::from(*op), byte as u8);
+            assert_eq!(OpCode::try_from(byte as u8), Ok(*op));
+        }
+        assert_eq!(OpCode::try_from(21), Err(21));
+    }
+}
@@ -0,0 +1,477 @@
+use crate::ast::{
+    Assign, Binary, Block, Break, Call, Class, Const, Continue, Expr, ExprVisitor, Expression,
+    ForIn, Function, Get, Grouping, If, Import, Lambda, ListLiteral, Literal, Logical, MapLiteral,
+    Print, Return, Set, Stmt, StmtVisitor, Subscript, SubscriptAssign, Super, Switch, Ternary,
+    This, Throw, Try, Unary, Var, VarList, Variable, While,
+};
+use crate::error::LoxError;
+use crate::scanner::token::LoxInterrupt;
+use crate::scanner::{LoxType, Token, TokenType};
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::error::CompileError;
+
+// 块中声明的局部变量，在 locals 中的下标即为它在栈中的槽位
+struct Local {
+    name: String,
+    depth: usize,
+}
+
+// 遍历语法树，将语句编译为字节码，目前只支持变量与基本的运算
+pub struct Compiler {
+    chunk: Chunk,
+    // 最近一次遇到的 token 所在的行，字面量等没有 token 的节点沿用这一行
+    line: usize,
+    locals: Vec<Local>,
+    // 0 表示全局作用域
+    scope_depth: usize,
+    errors: Vec<CompileError>,
+}
+
+impl Compiler {
+    pub fn compile(statements: &[Box<dyn Stmt>]) -> Result<Chunk, LoxError> {
+        let mut compiler = Compiler {
+            chunk: Chunk::new(),
+            line: 1,
+            locals: Vec::new(),
+            scope_depth: 0,
+            errors: Vec::new(),
+        };
+        for stmt in statements {
+            compiler.stmt(stmt.as_ref());
+        }
+        compiler.emit_op(OpCode::Return);
+        match compiler.errors.into_iter().next() {
+            Some(error) => Err(LoxError::Compile(error)),
+            None => Ok(compiler.chunk),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &dyn Stmt) {
+        let _ = stmt.accept(self);
+    }
+
+    fn expr(&mut self, expr: &dyn Expr) {
+        let _ = expr.accept(self);
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        self.chunk.write(byte, self.line);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit_byte(op.into());
+    }
+
+    // 常量表下标只占一个字节，超出时报错
+    fn make_constant(&mut self, value: LoxType) -> u8 {
+        let index = self.chunk.add_constant(value);
+        u8::try_from(index).unwrap_or_else(|_| {
+            self.error("Too many constants in one chunk.");
+            0
+        })
+    }
+
+    fn emit_constant(&mut self, op: OpCode, value: LoxType) {
+        let index = self.make_constant(value);
+        self.emit_op(op);
+        self.emit_byte(index);
+    }
+
+    // 全局变量以名称字符串作为常量，指令的操作数为该常量的下标
+    fn emit_global(&mut self, op: OpCode, name: &Token) {
+        self.line = name.line;
+        self.emit_constant(op, LoxType::new_str(&name.lexeme));
+    }
+
+    // 从内层向外查找，返回同名局部变量的槽位，找不到时按全局变量处理
+    fn resolve_local(&self, name: &Token) -> Option<u8> {
+        self.locals
+            .iter()
+            .rposition(|local| local.name == name.lexeme)
+            .map(|slot| slot as u8)
+    }
+
+    // 访问变量时优先使用局部变量的槽位
+    fn emit_variable(&mut self, local_op: OpCode, global_op: OpCode, name: &Token) {
+        match self.resolve_local(name) {
+            Some(slot) => {
+                self.line = name.line;
+                self.emit_op(local_op);
+                self.emit_byte(slot);
+            }
+            None => self.emit_global(global_op, name),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    // 离开作用域时弹出其中声明的局部变量
+    fn end_scope(&mut self) {
+        self.scope_depth -= 1;
+        while self
+            .locals
+            .last()
+            .is_some_and(|local| local.depth > self.scope_depth)
+        {
+            self.locals.pop();
+            self.emit_op(OpCode::Pop);
+        }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors.push(CompileError::new(self.line, 0, message));
+    }
+
+    fn unsupported(&mut self, token: &Token, what: &str) {
+        let message = format!("Can't compile {} to bytecode yet.", what);
+        self.errors
+            .push(CompileError::new(token.line, token.col_start, &message));
+    }
+}
+
+impl ExprVisitor for Compiler {
+    fn assign_visit(&mut self, expr: &Assign) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.value.as_ref());
+        // 赋值表达式的值留在栈上
+        self.emit_variable(OpCode::SetLocal, OpCode::SetGlobal, &expr.name);
+        Ok(LoxType::Nil)
+    }
+
+    fn binary_visit(&mut self, expr: &Binary) -> Result<LoxType, LoxInterrupt> {
+        self.line = expr.operator.line;
+        self.expr(expr.left.as_ref());
+        self.expr(expr.right.as_ref());
+        self.line = expr.operator.line;
+        // >=、<= 与 != 由相反的比较再取反得到
+        let ops: &[OpCode] = match expr.operator.token_type {
+            TokenType::Plus => &[OpCode::Add],
+            TokenType::Minus => &[OpCode::Subtract],
+            TokenType::Star => &[OpCode::Multiply],
+            TokenType::Slash => &[OpCode::Divide],
+            TokenType::EqualEqual => &[OpCode::Equal],
+            TokenType::BangEqual => &[OpCode::Equal, OpCode::Not],
+            TokenType::Greater => &[OpCode::Greater],
+            TokenType::GreaterEqual => &[OpCode::Less, OpCode::Not],
+            TokenType::Less => &[OpCode::Less],
+            TokenType::LessEqual => &[OpCode::Greater, OpCode::Not],
+            _ => {
+                let what = format!("operator '{}'", expr.operator.lexeme);
+                self.unsupported(&expr.operator, &what);
+                return Ok(LoxType::Nil);
+            }
+        };
+        for op in ops {
+            self.emit_op(*op);
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn grouping_visit(&mut self, expr: &Grouping) -> Result<LoxType, LoxInterrupt> {
+        self.expr(expr.expression.as_ref());
+        Ok(LoxType::Nil)
+    }
+
+    fn literal_visit(&mut self, expr: &Literal) -> Result<LoxType, LoxInterrupt> {
+        match &expr.value {
+            LoxType::Nil => self.emit_op(OpCode::Nil),
+            LoxType::Bool(true) => self.emit_op(OpCode::True),
+            LoxType::Bool(false) => self.emit_op(OpCode::False),
+            value => self.emit_constant(OpCode::Constant, value.clone()),
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn logical_visit(&mut self, expr: &Logical) -> Result<LoxType, LoxInterrupt> {
+        let what = format!("operator '{}'", expr.operator.lexeme);
+        self.unsupported(&expr.operator, &what);
+        Ok(LoxType::Nil)
+    }
+
+    fn unary_visit(&mut self, expr: &Unary) -> Result<LoxType, LoxInterrupt> {
+        self.line = expr.operator.line;
+        self.expr(expr.right.as_ref());
+        self.line = expr.operator.line;
+        match expr.operator.token_type {
+            TokenType::Minus => self.emit_op(OpCode::Negate),
+            TokenType::Bang => self.emit_op(OpCode::Not),
+            _ => {
+                let what = format!("operator '{}'", expr.operator.lexeme);
+                self.unsupported(&expr.operator, &what);
+            }
+        }
+        Ok(LoxType::Nil)
+    }
+
+    fn variable_visit(&mut self, expr: &Variable) -> Result<LoxType, LoxInterrupt> {
+        self.emit_variable(OpCode::GetLocal, OpCode::GetGlobal, &expr.name);
+        Ok(LoxType::Nil)
+    }
+
+    fn call_visit(&mut self, expr: &Call) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.paren, "calls");
+        Ok(LoxType::Nil)
+    }
+
+    fn get_visit(&mut self, expr: &Get) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.name, "property access");
+        Ok(LoxType::Nil)
+    }
+
+    fn set_visit(&mut self, expr: &Set) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.name, "property assignment");
+        Ok(LoxType::Nil)
+    }
+
+    fn super_visit(&mut self, expr: &Super) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.keyword, "'super'");
+        Ok(LoxType::Nil)
+    }
+
+    fn this_visit(&mut self, expr: &This) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.keyword, "'this'");
+        Ok(LoxType::Nil)
+    }
+
+    fn ternary_visit(&mut self, _expr: &Ternary) -> Result<LoxType, LoxInterrupt> {
+        self.error("Can't compile conditional expressions to bytecode yet.");
+        Ok(LoxType::Nil)
+    }
+
+    fn lambda_visit(&mut self, expr: &Lambda) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.function.name, "functions");
+        Ok(LoxType::Nil)
+    }
+
+    fn list_literal_visit(&mut self, expr: &ListLiteral) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.bracket, "lists");
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_visit(&mut self, expr: &Subscript) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.bracket, "subscripts");
+        Ok(LoxType::Nil)
+    }
+
+    fn subscript_assign_visit(&mut self, expr: &SubscriptAssign) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.bracket, "subscripts");
+        Ok(LoxType::Nil)
+    }
+
+    fn map_literal_visit(&mut self, expr: &MapLiteral) -> Result<LoxType, LoxInterrupt> {
+        self.unsupported(&expr.brace, "maps");
+        Ok(LoxType::Nil)
+    }
+}
+
+impl StmtVisitor for Compiler {
+    fn print_visit(&mut self, stmt: &Print) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.expression.as_ref());
+        if !stmt.newline {
+            self.error("Can't compile 'write' to bytecode yet.");
+        }
+        self.emit_op(OpCode::Print);
+        self.emit_op(OpCode::Pop);
+        Ok(None)
+    }
+
+    fn if_visit(&mut self, _stmt: &If) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.error("Can't compile 'if' to bytecode yet.");
+        Ok(None)
+    }
+
+    fn block_visit(&mut self, stmt: &Block) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.begin_scope();
+        for statement in stmt.statements.iter() {
+            self.stmt(statement.as_ref());
+        }
+        self.end_scope();
+        Ok(None)
+    }
+
+    fn expression_visit(&mut self, stmt: &Expression) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.expr(stmt.expression.as_ref());
+        self.emit_op(OpCode::Pop);
+        Ok(None)
+    }
+
+    fn var_visit(&mut self, stmt: &Var) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.line = stmt.name.line;
+        self.expr(stmt.initializer.as_ref());
+        if self.scope_depth == 0 {
+            self.emit_global(OpCode::DefineGlobal, &stmt.name);
+            return Ok(None);
+        }
+        // 局部变量的初始值留在栈上，作为它自己的槽位
+        if self.locals.len() > u8::MAX as usize {
+            self.unsupported(&stmt.name, "more than 256 local variables");
+            return Ok(None);
+        }
+        self.locals.push(Local {
+            name: stmt.name.lexeme.clone(),
+            depth: self.scope_depth,
+        });
+        Ok(None)
+    }
+
+    fn var_list_visit(&mut self, stmt: &VarList) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.bracket, "destructuring");
+        Ok(None)
+    }
+
+    fn const_visit(&mut self, stmt: &Const) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.name, "'const'");
+        Ok(None)
+    }
+
+    fn while_visit(&mut self, _stmt: &While) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.error("Can't compile loops to bytecode yet.");
+        Ok(None)
+    }
+
+    fn function_visit(&mut self, stmt: &Function) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.name, "functions");
+        Ok(None)
+    }
+
+    fn return_visit(&mut self, stmt: &Return) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'return'");
+        Ok(None)
+    }
+
+    fn break_visit(&mut self, stmt: &Break) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'break'");
+        Ok(None)
+    }
+
+    fn continue_visit(&mut self, stmt: &Continue) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'continue'");
+        Ok(None)
+    }
+
+    fn for_in_visit(&mut self, stmt: &ForIn) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.name, "loops");
+        Ok(None)
+    }
+
+    fn switch_visit(&mut self, stmt: &Switch) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'switch'");
+        Ok(None)
+    }
+
+    fn try_visit(&mut self, stmt: &Try) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'try'");
+        Ok(None)
+    }
+
+    fn throw_visit(&mut self, stmt: &Throw) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'throw'");
+        Ok(None)
+    }
+
+    fn import_visit(&mut self, stmt: &Import) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.keyword, "'import'");
+        Ok(None)
+    }
+
+    fn class_visit(&mut self, stmt: &Class) -> Result<Option<LoxType>, LoxInterrupt> {
+        self.unsupported(&stmt.name, "classes");
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::vm::disassembler::disassemble_chunk;
+
+    fn compile_source(source: &str) -> Result<Chunk, LoxError> {
+        let tokens = Scanner::new(source.to_string(), None).scan_tokens();
+        let statements = Parser::new(tokens).parse().unwrap();
+        Compiler::compile(&statements)
+    }
+
+    #[test]
+    fn test_compile_print() {
+        let chunk = compile_source("print 1 + 2;").unwrap();
+        assert_eq!(
+            disassemble_chunk(&chunk, "script"),
+            "== script ==\n\
+             0000    1 OP_CONSTANT         0 '1'\n\
+             0002    | OP_CONSTANT         1 '2'\n\
+             0004    | OP_ADD\n\
+             0005    | OP_PRINT\n\
+             0006    | OP_POP\n\
+             0007    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_precedence() {
+        let chunk = compile_source("print 1 + 2 * 3;").unwrap();
+        let listing = disassemble_chunk(&chunk, "script");
+        let positions = ["OP_CONSTANT", "OP_MULTIPLY", "OP_ADD", "OP_PRINT"]
+            .map(|name| listing.find(name).unwrap());
+        assert!(positions.is_sorted());
+    }
+
+    #[test]
+    fn test_compile_globals() {
+        let chunk = compile_source("var a = nil;\na = !(1 >= 2);\nprint a != true;").unwrap();
+        assert_eq!(
+            disassemble_chunk(&chunk, "script"),
+            "== script ==\n\
+             0000    1 OP_NIL\n\
+             0001    | OP_DEFINE_GLOBAL    0 'a'\n\
+             0003    2 OP_CONSTANT         1 '1'\n\
+             0005    | OP_CONSTANT         2 '2'\n\
+             0007    | OP_LESS\n\
+             0008    | OP_NOT\n\
+             0009    | OP_NOT\n\
+             0010    | OP_SET_GLOBAL       3 'a'\n\
+             0012    | OP_POP\n\
+             0013    3 OP_GET_GLOBAL       4 'a'\n\
+             0015    | OP_TRUE\n\
+             0016    | OP_EQUAL\n\
+             0017    | OP_NOT\n\
+             0018    | OP_PRINT\n\
+             0019    | OP_POP\n\
+             0020    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_locals() {
+        let chunk =
+            compile_source("var a = 1;\n{ var a = 2; { var b = a; b = 3; } print a; }").unwrap();
+        assert_eq!(
+            disassemble_chunk(&chunk, "script"),
+            "== script ==\n\
+             0000    1 OP_CONSTANT         0 '1'\n\
+             0002    | OP_DEFINE_GLOBAL    1 'a'\n\
+             0004    2 OP_CONSTANT         2 '2'\n\
+             0006    | OP_GET_LOCAL        0\n\
+             0008    | OP_CONSTANT         3 '3'\n\
+             0010    | OP_SET_LOCAL        1\n\
+             0012    | OP_POP\n\
+             0013    | OP_POP\n\
+             0014    | OP_GET_LOCAL        0\n\
+             0016    | OP_PRINT\n\
+             0017    | OP_POP\n\
+             0018    | OP_POP\n\
+             0019    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn test_compile_unsupported() {
+        let Err(LoxError::Compile(error)) = compile_source("print 1;\nfun f() {}") else {
+            panic!("expected a compile error");
+        };
+        assert_eq!(error.message, "Can't compile functions to bytecode yet.");
+        assert_eq!((error.line, error.column), (2, 4));
+    }
+}
@@ -0,0 +1,101 @@
+use crate::vm::chunk::{Chunk, OpCode};
+
+// 反汇编整段字节码，每行依次为偏移量、行号（与上一条指令相同时为 |）、指令与操作数
+pub fn disassemble_chunk(chunk: &Chunk, name: &str) -> String {
+    let mut output = format!("== {} ==\n", name);
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let (line, next) = disassemble_instruction(chunk, offset);
+        output.push_str(&line);
+        output.push('\n');
+        offset = next;
+    }
+    output
+}
+
+// 返回一条指令的文本与下一条指令的偏移量，带操作数的指令占两个字节
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let line = if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1] {
+        String::from("   |")
+    } else {
+        format!("{:4}", chunk.lines[offset])
+    };
+    let prefix = format!("{:04} {} ", offset, line);
+    let op = match OpCode::try_from(chunk.code[offset]) {
+        Ok(op) => op,
+        Err(byte) => return (format!("{}Unknown opcode {}", prefix, byte), offset + 1),
+    };
+    if !op.has_constant_operand() && !op.has_slot_operand() {
+        return (format!("{}{}", prefix, op.name()), offset + 1);
+    }
+    let Some(&operand) = chunk.code.get(offset + 1) else {
+        return (
+            format!("{}{} <missing operand>", prefix, op.name()),
+            offset + 1,
+        );
+    };
+    let text = if op.has_slot_operand() {
+        format!("{}{:<16} {:4}", prefix, op.name(), operand)
+    } else {
+        match chunk.constants.get(operand as usize) {
+            Some(value) => format!("{}{:<16} {:4} '{}'", prefix, op.name(), operand, value),
+            None => format!(
+                "{}{:<16} {:4} <invalid constant>",
+                prefix,
+                op.name(),
+                operand
+            ),
+        }
+    };
+    (text, offset + 2)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scanner::LoxType;
+
+    #[test]
+    fn test_disassemble_chunk() {
+        let mut chunk = Chunk::new();
+        let constant = chunk.add_constant(LoxType::new_num(1.2));
+        assert_eq!(constant, 0);
+        chunk.write(OpCode::Constant.into(), 1);
+        chunk.write(constant as u8, 1);
+        chunk.write(OpCode::Negate.into(), 1);
+        let name = chunk.add_constant(LoxType::new_str("a"));
+        chunk.write(OpCode::DefineGlobal.into(), 2);
+        chunk.write(name as u8, 2);
+        chunk.write(OpCode::GetLocal.into(), 3);
+        chunk.write(0, 3);
+        chunk.write(OpCode::Return.into(), 3);
+        chunk.write(200, 3);
+        assert_eq!(chunk.code.len(), chunk.lines.len());
+        assert_eq!(
+            disassemble_chunk(&chunk, "test"),
+            "== test ==\n\
+             0000    1 OP_CONSTANT         0 '1.2'\n\
+             0002    | OP_NEGATE\n\
+             0003    2 OP_DEFINE_GLOBAL    1 'a'\n\
+             0005    3 OP_GET_LOCAL        0\n\
+             0007    | OP_RETURN\n\
+             0008    | Unknown opcode 200\n"
+        );
+    }
+
+    #[test]
+    fn test_disassemble_instruction() {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Nil.into(), 1);
+        chunk.write(OpCode::Constant.into(), 2);
+        assert_eq!(
+            disassemble_instruction(&chunk, 0),
+            (String::from("0000    1 OP_NIL"), 1)
+        );
+        // 操作数缺失时只前进一个字节
+        assert_eq!(
+            disassemble_instruction(&chunk, 1),
+            (String::from("0001    2 OP_CONSTANT <missing operand>"), 2)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::Formatter;
+
+// 将语法树编译为字节码时的错误，目前主要是尚不支持编译的语法
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(line: usize, column: usize, message: &str) -> Self {
+        CompileError {
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for CompileError {}
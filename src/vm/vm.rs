@@ -0,0 +1,267 @@
+use crate::ast::error::LoxRuntimeError;
+use crate::ast::interpreter::Interpreter;
+use crate::error::LoxError;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::error::TypeError;
+use crate::scanner::{LoxType, Scanner, Token, TokenType};
+use crate::vm::chunk::{Chunk, OpCode};
+use crate::vm::compiler::Compiler;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+// 基于栈的虚拟机，逐条执行 Chunk 中的字节码
+pub struct VM {
+    chunk: Chunk,
+    // 下一条要执行的指令在 code 中的偏移量
+    ip: usize,
+    stack: Vec<LoxType>,
+    globals: HashMap<String, LoxType>,
+    output: Rc<RefCell<dyn Write>>,
+}
+
+impl VM {
+    pub fn new(chunk: Chunk, output: Rc<RefCell<dyn Write>>) -> Self {
+        VM {
+            chunk,
+            ip: 0,
+            stack: Vec::new(),
+            globals: HashMap::new(),
+            output,
+        }
+    }
+
+    // 扫描、解析、编译后在虚拟机中执行，返回第一个出现的错误
+    pub fn interpret(source: &str, output: Rc<RefCell<dyn Write>>) -> Result<(), LoxError> {
+        let mut scanner = Scanner::new(source.to_string(), None);
+        let tokens = scanner.scan_tokens();
+        if let Some(error) = scanner.errors().first() {
+            return Err(LoxError::Scan(error.clone()));
+        }
+        let statements = Parser::new(tokens)
+            .parse()
+            .map_err(|mut errors| LoxError::Parse(errors.remove(0)))?;
+        // 与树遍历解释器共用静态检查，例如在初始值中读取正在声明的局部变量
+        let mut interpreter = Interpreter::new();
+        let mut resolver = Resolver::new(&mut interpreter);
+        resolver.resolve(&statements);
+        if let Some(error) = resolver.errors().first() {
+            return Err(LoxError::Resolve(error.clone()));
+        }
+        let chunk = Compiler::compile(&statements)?;
+        VM::new(chunk, output).run().map_err(LoxError::Runtime)
+    }
+
+    pub fn run(&mut self) -> Result<(), LoxRuntimeError> {
+        while self.ip < self.chunk.code.len() {
+            let byte = self.read_byte();
+            let op = OpCode::try_from(byte)
+                .map_err(|byte| self.error(&format!("Unknown opcode {}.", byte)))?;
+            match op {
+                OpCode::Constant => {
+                    let value = self.read_constant();
+                    self.stack.push(value);
+                }
+                OpCode::Return => return Ok(()),
+                OpCode::Negate => {
+                    let value = self.pop();
+                    let result = self.operate(-value)?;
+                    self.stack.push(result);
+                }
+                OpCode::Add => self.binary(|a, b| a + b)?,
+                OpCode::Subtract => self.binary(|a, b| a - b)?,
+                OpCode::Multiply => self.binary(|a, b| a * b)?,
+                OpCode::Divide => self.binary(|a, b| a / b)?,
+                OpCode::Nil => self.stack.push(LoxType::Nil),
+                OpCode::True => self.stack.push(LoxType::new_bool(true)),
+                OpCode::False => self.stack.push(LoxType::new_bool(false)),
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(LoxType::new_bool(!is_truthy(&value)));
+                }
+                OpCode::Equal => {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.stack.push(LoxType::new_bool(a == b));
+                }
+                OpCode::Greater => self.compare(|a, b| a > b)?,
+                OpCode::Less => self.compare(|a, b| a < b)?,
+                OpCode::Print => {
+                    let value = self.stack.last().expect("Stack underflow");
+                    writeln!(self.output.borrow_mut(), "{}", value)
+                        .expect("Failed to write output");
+                }
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_constant().to_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_constant().to_string();
+                    let Some(value) = self.globals.get(&name) else {
+                        return Err(self.error(&format!("Undefined variable '{}'.", name)));
+                    };
+                    self.stack.push(value.clone());
+                }
+                OpCode::SetGlobal => {
+                    // 赋值表达式的值保留在栈上
+                    let name = self.read_constant().to_string();
+                    let value = self.stack.last().expect("Stack underflow").clone();
+                    let Some(slot) = self.globals.get_mut(&name) else {
+                        return Err(self.error(&format!("Undefined variable '{}'.", name)));
+                    };
+                    *slot = value;
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_byte() as usize;
+                    self.stack[slot] = self.stack.last().expect("Stack underflow").clone();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let byte = self.chunk.code[self.ip];
+        self.ip += 1;
+        byte
+    }
+
+    fn read_constant(&mut self) -> LoxType {
+        let index = self.read_byte();
+        self.chunk.constants[index as usize].clone()
+    }
+
+    // 编译器保证操作数已经入栈，栈为空说明字节码本身有误
+    fn pop(&mut self) -> LoxType {
+        self.stack.pop().expect("Stack underflow")
+    }
+
+    fn binary<F>(&mut self, op: F) -> Result<(), LoxRuntimeError>
+    where
+        F: FnOnce(LoxType, LoxType) -> Result<LoxType, TypeError>,
+    {
+        let b = self.pop();
+        let a = self.pop();
+        let result = self.operate(op(a, b))?;
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn compare<F>(&mut self, compare: F) -> Result<(), LoxRuntimeError>
+    where
+        F: FnOnce(&LoxType, &LoxType) -> bool,
+    {
+        let b = self.pop();
+        let a = self.pop();
+        match (&a, &b) {
+            (LoxType::Num(_), LoxType::Num(_)) | (LoxType::Str(_), LoxType::Str(_)) => {
+                self.stack.push(LoxType::new_bool(compare(&a, &b)));
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be two numbers or two strings.")),
+        }
+    }
+
+    fn operate(&self, result: Result<LoxType, TypeError>) -> Result<LoxType, LoxRuntimeError> {
+        result.map_err(|error| self.error(&error.to_string()))
+    }
+
+    // 字节码只记录了行号，错误指向当前指令所在的行
+    fn error(&self, message: &str) -> LoxRuntimeError {
+        let line = self.chunk.lines[self.ip - 1];
+        let token = Token::new(TokenType::Identifier, String::new(), line, 0, 0, None, None);
+        LoxRuntimeError::new(token, message)
+    }
+}
+
+fn is_truthy(value: &LoxType) -> bool {
+    !matches!(value, LoxType::Nil | LoxType::Bool(false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn interpret(source: &str) -> (Result<(), LoxError>, String) {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let result = VM::interpret(source, output.clone());
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        (result, output)
+    }
+
+    #[test]
+    fn test_run() {
+        let (result, output) = interpret(
+            "print 1 + 2 * 3;
+            print -(4 - 6) / 4;
+            print \"a\" + \"b\";
+            print !nil == (1 >= 2);
+            print \"a\" < \"b\";
+            var x = 1;
+            x = x + 1;
+            print x;",
+        );
+        result.unwrap();
+        assert_eq!(output, "7\n0.5\nab\nfalse\ntrue\n2\n");
+    }
+
+    #[test]
+    fn test_locals() {
+        let (result, output) = interpret(
+            "var a = \"global\";
+            var b = \"global b\";
+            {
+                var a = \"outer\";
+                {
+                    var a = \"inner\";
+                    print a;
+                    a = a + \"!\";
+                    print a;
+                    print b;
+                }
+                print a;
+                b = a;
+            }
+            print a;
+            print b;",
+        );
+        result.unwrap();
+        assert_eq!(output, "inner\ninner!\nglobal b\nouter\nglobal\nouter\n");
+    }
+
+    #[test]
+    fn test_run_errors() {
+        let (result, output) = interpret("print 1;\nprint -\"a\";");
+        let Err(LoxError::Runtime(error)) = result else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(output, "1\n");
+        assert_eq!(error.message, "Operand must be a number.");
+        assert_eq!(error.token.line, 2);
+
+        let (result, _) = interpret("y = 1;");
+        let Err(LoxError::Runtime(error)) = result else {
+            panic!("expected a runtime error");
+        };
+        assert_eq!(error.message, "Undefined variable 'y'.");
+
+        let (result, _) = interpret("print 1 < \"a\";");
+        assert!(matches!(result, Err(LoxError::Runtime(_))));
+        let (result, _) = interpret("print (1;");
+        assert!(matches!(result, Err(LoxError::Parse(_))));
+        let (result, _) = interpret("{ var a = a; }");
+        assert!(matches!(result, Err(LoxError::Resolve(_))));
+        let (result, _) = interpret("print [1];");
+        assert!(matches!(result, Err(LoxError::Compile(_))));
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// 以 "-" 作为文件参数运行，源码通过标准输入传入，返回退出码与标准输出
+fn run_with_stdin(flag: &str, source: &str) -> (Option<i32>, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .args([flag, "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+// 将源码写入临时文件后作为脚本运行，返回退出码与标准输出
+fn run_script(name: &str, source: &str) -> (Option<i32>, String) {
+    let path = std::env::temp_dir().join(format!("lox-rs-{}-{}.lox", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox-rs"))
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    (
+        output.status.code(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn test_dump_tokens() {
+    let (code, stdout) = run_with_stdin("--tokens", "var a = 1.5;\nprint \"hi\";");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "Var 'var' [1:0-3] literal=none\n\
+         Identifier 'a' [1:4-5] literal=none\n\
+         Equal '=' [1:6-7] literal=none\n\
+         Number '1.5' [1:8-11] literal=1.5\n\
+         Semicolon ';' [1:11-12] literal=none\n\
+         Print 'print' [2:0-5] literal=none\n\
+         String '\"hi\"' [2:6-10] literal=\"hi\"\n\
+         Semicolon ';' [2:10-11] literal=none\n\
+         Eof '' [2:11-12] literal=none\n"
+    );
+}
+
+#[test]
+fn test_dump_ast() {
+    let source = "fun f(n) { if (n > 1) return n * f(n - 1); return 1; }\nprint f(3);";
+    let (code, stdout) = run_with_stdin("--ast", source);
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "(fun f (n)\n  (if (> n 1)\n    (return (* n (call f (- n 1)))))\n  (return 1))\n\
+         (print (call f 3))\n"
+    );
+
+    let (code, stdout) = run_with_stdin("--ast", "print (1;");
+    assert_eq!(code, Some(65));
+    assert_eq!(stdout, "");
+}
+
+#[test]
+fn test_dump_bytecode() {
+    let (code, stdout) = run_with_stdin("--bytecode", "print -1;");
+    assert_eq!(code, Some(0));
+    assert_eq!(
+        stdout,
+        "== <script> ==\n\
+         0000    1 OP_CONSTANT         0 '1'\n\
+         0002    | OP_NEGATE\n\
+         0003    | OP_PRINT\n\
+         0004    | OP_POP\n\
+         0005    | OP_RETURN\n"
+    );
+
+    let (code, _) = run_with_stdin("--bytecode", "fun f() {}");
+    assert_eq!(code, Some(65));
+}
+
+#[test]
+fn test_exit() {
+    let (code, stdout) = run_script("exit", "print \"before\";\nexit(42.9);\nprint \"after\";");
+    assert_eq!(code, Some(42));
+    assert!(stdout.contains("before\n"));
+    assert!(!stdout.contains("after\n"));
+
+    // 参数不是数字时以 1 退出
+    let (code, _) = run_script("exit-nil", "exit(nil);");
+    assert_eq!(code, Some(1));
+}
+
+#[test]
+fn test_check() {
+    let (code, stdout) = run_with_stdin("--check", "print \"side effect\";");
+    assert_eq!(code, Some(0));
+    assert!(!stdout.contains("side effect\n"));
+
+    let (code, _) = run_with_stdin("--check", "var = 1;");
+    assert_eq!(code, Some(65));
+    let (code, _) = run_with_stdin("--check", "return 1;");
+    assert_eq!(code, Some(65));
+}
@@ -0,0 +1,38 @@
+use lox_rs::Lox;
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn write_script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("lox-rs-{}-{}.lox", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn test_run_files_shares_definitions() {
+    let library = write_script("library", "fun add(a, b) { return a + b; }");
+    let main = write_script("main", "print add(1, 2);");
+    let stdout = SharedBuffer::default();
+    let mut lox = Lox::builder().stdout(stdout.clone()).build();
+    let result = lox.run_files(&[library.to_str().unwrap(), main.to_str().unwrap()]);
+    std::fs::remove_file(&library).unwrap();
+    std::fs::remove_file(&main).unwrap();
+    result.unwrap();
+    assert_eq!(stdout.0.borrow().as_slice(), b"3\n");
+}